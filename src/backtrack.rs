@@ -26,10 +26,15 @@ on large bitsets.
 /// This overrides whatever was previously set via the `automatic` or
 /// `nfa` methods.
 
+
+use alloc::vec::Vec;
+
 use crate::context::Context;
-use crate::exec::ProgramCache;
+use crate::pikevm::Slot;
 use crate::repr::Integral;
-use crate::program::{Index, Program, Inst};
+use crate::program::{
+    Index, Program, ProgramCache, ProgramCacheInner, Inst, Steps
+};
 
 type Bits = u32;
 
@@ -53,23 +58,35 @@ pub fn should_exec(num_insts: usize, text_len: usize) -> bool {
 #[derive(Debug)]
 pub struct Bounded<'a, 'm, 'r, I: Integral> {
     prog: &'r Program<I>,
-    context: Context<I>,
+    context: &'r Context<I>,
     matches: &'m mut [bool],
-    m: &'a mut Cache<I>,
+    /// The slots of the match kept so far, see `captures`. May be empty.
+    slots: &'m mut [Slot],
+    m: &'a mut Cache,
+    /// The steps taken, against `Program::step_limit`.
+    steps: &'a mut Steps,
+    /// The position at which the last match state was reached.
+    last_end: Option<usize>,
+    /// Where the search starts. A program compiled in reverse walks the
+    /// input in place from the end back to it, never reading before it.
+    start: usize,
 }
 
 /// Shared cached state between multiple invocations of a backtracking engine
 /// in the same thread.
 #[derive(Clone, Debug)]
-pub struct Cache<I: Integral> {
-    jobs: Vec<Job<I>>,
+pub struct Cache {
+    jobs: Vec<Job>,
     visited: Vec<Bits>,
+    /// The slots along the path being explored, which `Job::SaveRestore`
+    /// jobs restore on the way back.
+    slots: Vec<Slot>,
 }
 
-impl<I: Integral> Cache<I> {
+impl Cache {
     /// Create new empty cache for the backtracking engine.
-    pub fn new(_prog: &Program<I>) -> Self {
-        Cache { jobs: vec![], visited: vec![] }
+    pub fn new<I: Integral>(_prog: &Program<I>) -> Self {
+        Cache { jobs: vec![], visited: vec![], slots: vec![] }
     }
 }
 
@@ -80,7 +97,12 @@ impl<I: Integral> Cache<I> {
 /// engine must keep track of old capture group values. We use the explicit
 /// stack to do it.
 #[derive(Clone, Copy, Debug)]
-struct Job<I: Integral> { ip: Index, at: I }
+enum Job {
+    Inst { ip: Index, at: usize },
+    /// Puts back the value a `Save` overwrote, once the paths through it
+    /// have been explored.
+    SaveRestore { slot: usize, old_pos: Slot },
+}
 
 impl<'a, 'm, 'r, I: Integral> Bounded<'a, 'm, 'r, I> {
     /// Execute the backtracking matching engine.
@@ -91,22 +113,83 @@ impl<'a, 'm, 'r, I: Integral> Bounded<'a, 'm, 'r, I> {
         prog: &'r Program<I>,
         cache: &ProgramCache<I>,
         matches: &'m mut [bool],
-        context: Context<I>,
+        context: &'r Context<I>,
         start: usize,
         end: usize,
     ) -> bool {
         let mut cache = cache.borrow_mut();
-        let cache = &mut cache.backtrack;
-        let start = context[start];
-        let mut b = Bounded { prog, context, matches, m: cache };
+        let ProgramCacheInner { backtrack: cache, steps, .. } = &mut *cache;
+        let mut b = Bounded {
+            prog, context, matches, slots: &mut [], m: cache, steps,
+            last_end: None, start
+        };
         b.exec_(start, end)
     }
 
+    /// Like `exec`, but returns the start and end positions of the
+    /// leftmost-first match instead of whether there is one. If `anchored`
+    /// is true, the match must start at `start` even if the program isn't
+    /// anchored.
+    pub fn find(
+        prog: &'r Program<I>,
+        cache: &ProgramCache<I>,
+        context: &'r Context<I>,
+        start: usize,
+        end: usize,
+        anchored: bool,
+    ) -> Option<(usize, usize)> {
+        let mut slots = [None, None];
+        if Self::captures(prog, cache, &mut slots, context, start, end,
+                          anchored)
+        {
+            Some((slots[0]?, slots[1]?))
+        } else {
+            None
+        }
+    }
+
+    /// Like `find`, but records the positions of the match in `slots` as
+    /// `pikevm::Fsm::exec` does: the first two are its start and end, and
+    /// slot `i` is where the last `Save` of slot `i` on the way to it was
+    /// passed. Returns whether there is a match.
+    pub fn captures(
+        prog: &'r Program<I>,
+        cache: &ProgramCache<I>,
+        slots: &'m mut [Slot],
+        context: &'r Context<I>,
+        start: usize,
+        end: usize,
+        anchored: bool,
+    ) -> bool {
+        let mut cache = cache.borrow_mut();
+        let ProgramCacheInner { backtrack: cache, steps, .. } = &mut *cache;
+        let mut b = Bounded {
+            prog, context, matches: &mut [false], slots, m: cache, steps,
+            last_end: None, start
+        };
+        b.clear();
+        for at in start..=end {
+            if end - at < prog.min_len || b.over_limit() {
+                break;
+            }
+            if b.backtrack(at) {
+                return true;
+            }
+            if anchored || prog.is_anchored_start {
+                break;
+            }
+        }
+        false
+    }
+
     /// Clears the cache such that the backtracking engine can be executed
     /// on some input of fixed length.
     fn clear(&mut self) {
         // Reset the job memory so that we start fresh.
         self.m.jobs.clear();
+        self.m.slots.clear();
+        self.m.slots.resize(self.slots.len(), None);
+        self.steps.taken = 0;
 
         // Now we need to clear the bit state set.
         // We do this by figuring out how much space we need to keep track
@@ -138,6 +221,11 @@ impl<'a, 'm, 'r, I: Integral> Bounded<'a, 'm, 'r, I> {
     /// for literal prefixes.
     fn exec_(&mut self, mut at: usize, end: usize) -> bool {
         self.clear();
+        // A program compiled in reverse is only run for a regex anchored at
+        // the end, so a single run from there back to `start` decides.
+        if self.prog.is_reverse {
+            return self.backtrack(end);
+        }
         // If this is an anchored regex at the beginning of the input, then
         // we're either already done or we only need to try backtracking once.
         if self.prog.is_anchored_start {
@@ -151,6 +239,10 @@ impl<'a, 'm, 'r, I: Integral> Bounded<'a, 'm, 'r, I> {
                     Some(at) => at,
                 };
             }
+            // No match fits in what is left, here or further on.
+            if end.saturating_sub(at) < self.prog.min_len || self.over_limit() {
+                break;
+            }
             matched = self.backtrack(at) || matched;
             if matched && self.prog.matches.len() == 1 {
                 return true;
@@ -158,31 +250,50 @@ impl<'a, 'm, 'r, I: Integral> Bounded<'a, 'm, 'r, I> {
             if at >= end {
                 break;
             }
-            at = self.context[at + 1];
+            at += 1;
         }
-        matched
+        matched && !self.over_limit()
     }
 
     /// The main backtracking loop starting at the given input position.
-    fn backtrack(&mut self, at: I) -> bool {
+    fn backtrack(&mut self, at: usize) -> bool {
         // N.B. We use an explicit stack to avoid recursion.
         // To avoid excessive pushing and popping, most transitions are handled
         // in the `step` helper function, which only pushes to the stack when
         // there's a capture or a branch.
         let mut matched = false;
-        self.m.jobs.push(Job { ip: 0, at });
+        if let Some(start) = self.m.slots.first_mut() {
+            *start = Some(at);
+        }
+        self.m.jobs.push(Job::Inst { ip: 0, at });
         while let Some(job) = self.m.jobs.pop() {
-            if self.step(job.ip, job.at) {
-                // Only quit if we're matching one regex.
-                // If we're matching a regex set, then mush on and
-                // try to find other matches (if we want them).
-                if self.prog.matches.len() == 1 {
-                    return true;
+            match job {
+                Job::Inst { ip, at } => if self.step(ip, at) {
+                    // Only quit if we're matching one regex, and not looking
+                    // for the longest match, which the remaining jobs may
+                    // still find. If we're matching a regex set, then mush
+                    // on and try to find other matches (if we want them).
+                    if self.prog.matches.len() == 1
+                        && !self.prog.longest_match
+                    {
+                        return true;
+                    }
+                    matched = true;
+                },
+                Job::SaveRestore { slot, old_pos } => {
+                    self.m.slots[slot] = old_pos;
                 }
-                matched = true;
             }
         }
-        matched
+        // A match kept before going over the limit may not be the one to
+        // report.
+        matched && !self.over_limit()
+    }
+
+    /// Returns true if the run went over `Program::step_limit`, and is
+    /// abandoned.
+    fn over_limit(&self) -> bool {
+        self.steps.taken > self.prog.step_limit
     }
 
     fn step(&mut self, mut ip: Index, mut at: usize) -> bool {
@@ -194,40 +305,107 @@ impl<'a, 'm, 'r, I: Integral> Bounded<'a, 'm, 'r, I> {
             if self.has_visited(ip, at) {
                 return false;
             }
+            if !self.steps.take(self.prog.step_limit) {
+                // Over the limit, so no job is worth running.
+                self.m.jobs.clear();
+                return false;
+            }
             match self.prog[ip] {
                 Inst::Match(slot) => {
                     if slot < self.matches.len() {
                         self.matches[slot] = true;
                     }
+                    // Looking for the longest match, one found later is only
+                    // kept if it is longer.
+                    let keep = match self.last_end {
+                        Some(end) if self.prog.longest_match => at > end,
+                        _ => true,
+                    };
+                    if keep {
+                        self.last_end = Some(at);
+                        self.slots.copy_from_slice(&self.m.slots);
+                        if let Some(end) = self.slots.get_mut(1) {
+                            *end = Some(at);
+                        }
+                    }
                     return true;
                 }
                 Inst::Split { goto1, goto2 } => {
-                    self.m.jobs.push(Job { ip: goto2, at });
+                    // A single regex stops at its first match, so if `goto1`
+                    // is bound to match then `goto2` would never be popped.
+                    // (`leads_to_match` is always false for regex sets.)
+                    // Looking for the longest match, it may still be longer.
+                    if self.prog.longest_match
+                        || !self.prog.leads_to_match(goto1)
+                    {
+                        self.m.jobs.push(Job::Inst { ip: goto2, at });
+                    }
                     ip = goto1;
                 }
                 Inst::Zero { goto, zero } => {
-                    if self.context.is_empty_match(at, zero) {
+                    // `Repr::rev` mirrored the anchors, which in place are
+                    // checked as they were written.
+                    let zero = if self.prog.is_reverse {
+                        zero.rev()
+                    } else {
+                        zero
+                    };
+                    if self.context.is_empty_match(
+                        at, &zero, self.prog.line_terminator, self.prog.crlf
+                    ) {
                         ip = goto;
                     } else {
                         return false;
                     }
                 }
-                Inst::One { goto, seq } => {
-                    if seq == self.context[at] {
+                Inst::One { goto, ref seq } if self.prog.is_reverse => {
+                    // The run was reversed, and is read backward from `at`.
+                    let before = &self.context[self.start..at];
+                    if before.len() >= seq.len()
+                        && before.iter().rev().zip(seq.iter())
+                            .all(|(a, b)| a == b)
+                    {
                         ip = goto;
-                        at = self.context[at + 1];
+                        at -= seq.len();
                     } else {
                         return false;
                     }
                 }
-                Inst::Interval { goto, interval } => {
-                    if interval.has(self.context[at]) {
+                Inst::One { goto, ref seq } => {
+                    // A literal run is compared in one go.
+                    if self.context[at..].starts_with(seq) {
                         ip = goto;
-                        at = self.context[at + 1];
+                        at += seq.len();
                     } else {
                         return false;
                     }
                 }
+                Inst::Interval { goto, interval } if self.prog.is_reverse => {
+                    match self.context.prev_pos(at)
+                        .filter(|&prev| prev >= self.start)
+                    {
+                        Some(prev) if interval.has(self.context[prev]) => {
+                            ip = goto;
+                            at = prev;
+                        }
+                        _ => return false,
+                    }
+                }
+                Inst::Interval { goto, interval } => match self.context.next(at) {
+                    Some(i) if interval.has(i) => {
+                        ip = goto;
+                        at += 1;
+                    }
+                    _ => return false,
+                },
+                Inst::Save { goto, slot } => {
+                    if slot < self.m.slots.len() {
+                        let old_pos = self.m.slots[slot];
+                        self.m.jobs.push(Job::SaveRestore { slot, old_pos });
+                        self.m.slots[slot] = Some(at);
+                    }
+                    ip = goto;
+                }
             }
         }
     }
@@ -235,7 +413,7 @@ impl<'a, 'm, 'r, I: Integral> Bounded<'a, 'm, 'r, I> {
     fn has_visited(&mut self, ip: Index, at: usize) -> bool {
         let k = ip * (self.context.len() + 1) + at;
         let k1 = k / BIT_SIZE;
-        let k2 = usize_to_u32(1 << (k & (BIT_SIZE - 1)));
+        let k2 = 1u32 << (k & (BIT_SIZE - 1));
         if self.m.visited[k1] & k2 == 0 {
             self.m.visited[k1] |= k2;
             false
@@ -245,9 +423,64 @@ impl<'a, 'm, 'r, I: Integral> Bounded<'a, 'm, 'r, I> {
     }
 }
 
-fn usize_to_u32(n: usize) -> u32 {
-    if (n as u64) > (u32::MAX as u64) {
-        panic!("BUG: {} is too big to fit into u32", n)
+#[cfg(test)]
+mod tests {
+    use core::{cell::RefCell, panic::AssertUnwindSafe};
+
+    use super::*;
+    use crate::program::ProgramCacheInner;
+    use crate::pikevm;
+    use crate::repr::Repr;
+    use crate::seq::Seq;
+
+    #[test]
+    fn split_into_match_pushes_no_job() {
+        // `(|a)`, i.e. an `Or` whose preferred branch matches immediately.
+        let mut prog = Program::new();
+        prog.insts = vec![
+            Inst::Split { goto1: 1, goto2: 2 },
+            Inst::Match(0),
+            Inst::One { goto: 1, seq: Seq::one('a') },
+        ];
+        prog.matches = vec![1];
+        let cache = AssertUnwindSafe(RefCell::new(
+            ProgramCacheInner::new(&prog)
+        ));
+        let context = Context::from("aaaa");
+        assert!(Bounded::exec(&prog, &cache, &mut [false], &context, 0, 4));
+        assert_eq!(cache.borrow().backtrack.jobs.capacity(), 0);
+    }
+
+    #[test]
+    fn captures_agree_with_pikevm() {
+        for pattern in [
+            "(?P<a>a*)(?P<b>ab)?", "(?P<x>a|ab)(?P<y>c|bcd)",
+            "((?P<x>a)|b)*", "(?P<x>a(?P<y>b)?)+c?", "(?P<x>)b",
+        ] {
+            let prog = Repr::try_from(pattern).unwrap().compile().unwrap();
+            let cache = AssertUnwindSafe(RefCell::new(
+                ProgramCacheInner::new(&prog)
+            ));
+            for text in ["", "abcd", "aab", "ababab", "bab", "abc", "xab"] {
+                let context = Context::from(text);
+                for start in 0..=context.len() {
+                    let mut expected = vec![None; prog.slots()];
+                    let matched = pikevm::Fsm::exec(
+                        &prog, &cache, &mut [], &mut expected, false,
+                        &context, start, context.len()
+                    );
+                    let mut slots = vec![None; prog.slots()];
+                    assert_eq!(
+                        Bounded::captures(&prog, &cache, &mut slots, &context,
+                                          start, context.len(), false),
+                        matched, "{} {:?} {}", pattern, text, start
+                    );
+                    if matched {
+                        assert_eq!(slots, expected, "{} {:?} {}",
+                                   pattern, text, start);
+                    }
+                }
+            }
+        }
     }
-    n as u32
 }
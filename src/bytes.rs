@@ -0,0 +1,181 @@
+//! Matching `Repr<char>` patterns against UTF-8 bytes without decoding.
+//!
+//! `Repr::to_utf8` lowers a pattern over `char` into one over `u8` matching
+//! the UTF-8 encodings of the words it matches, each `Interval` becoming an
+//! alternation of byte interval sequences as given by `Utf8Sequences`. The
+//! engines then run the resulting `Program<u8>` directly on the bytes.
+
+use alloc::string::String;
+use core::str;
+
+use regex_syntax::utf8::Utf8Sequences;
+use unconst::unconst;
+
+use crate::context::Context;
+use crate::error::CompileError;
+use crate::interval::Interval;
+use crate::program::Program;
+use crate::repr::{Integral, Repr, Zero};
+use crate::seq::Seq;
+use crate::unicode::is_empty_match_between;
+
+#[unconst]
+impl const Integral for u8 {
+    const MIN: Self = 0;
+    const MAX: Self = 0xFF;
+    fn succ(self) -> Self {
+        self.checked_add(1).unwrap()
+    }
+    fn pred(self) -> Self {
+        self.checked_sub(1).unwrap()
+    }
+}
+
+impl Repr<char> {
+    /// Returns the repr over bytes matching the UTF-8 encodings of the
+    /// words `self` matches, so that it can run on `&[u8]` without
+    /// decoding it first. On valid UTF-8, a match never starts or ends
+    /// inside the encoding of a character, except through `Not` and `And`,
+    /// which are complemented over all bytes.
+    pub fn to_utf8(&self) -> Repr<u8> {
+        match self {
+            Self::Zero(zero) => Repr::Zero(zero.clone()),
+            Self::One(seq) => {
+                let s: String = seq.iter().collect();
+                Repr::One(Seq::new(s.bytes()))
+            }
+            Self::Interval(_) if self.is_void() => Repr::void(),
+            Self::Interval(Interval(from, to)) => {
+                Repr::any(Utf8Sequences::new(*from, *to).map(|seq| {
+                    Repr::prod(seq.as_slice().iter().map(|range| {
+                        Repr::Interval(Interval(range.start, range.end))
+                    }))
+                }))
+            }
+            Self::Mul(lhs, rhs) => lhs.to_utf8().mul(rhs.to_utf8()),
+            Self::Or(lhs, rhs) => lhs.to_utf8().or(rhs.to_utf8()),
+            Self::Div(lhs, rhs) => lhs.to_utf8().div(rhs.to_utf8()),
+            Self::Exp(repr) => repr.to_utf8().exp(),
+            Self::Not(repr) => repr.to_utf8().not(),
+            Self::Add(lhs, rhs) => lhs.to_utf8().add(rhs.to_utf8()),
+            Self::And(lhs, rhs) => lhs.to_utf8().and(rhs.to_utf8()),
+            Self::Cap(repr, name) => repr.to_utf8().cap(name),
+            Self::Lazy(repr) => repr.to_utf8().exp_lazy(),
+        }
+    }
+
+    /// Compiles the UTF-8 lowering of this repr, see `to_utf8`, with the
+    /// default options.
+    pub fn compile_utf8(&self) -> Result<Program<u8>, CompileError> {
+        self.to_utf8().compile()
+    }
+}
+
+impl Context<u8> {
+    /// Like `Context::<char>::is_empty_match`, over UTF-8. Only Unicode
+    /// word boundaries decode the characters around `at`, as the other
+    /// assertions only look at ASCII, which is its own encoding. The line
+    /// terminator must therefore be ASCII. Bytes that aren't valid UTF-8
+    /// are not word characters.
+    pub fn is_empty_match(
+        &self,
+        at: usize,
+        look: &Zero,
+        line_terminator: char,
+        crlf: bool,
+    ) -> bool {
+        let (c1, c2) = match look {
+            Zero::WordBoundary | Zero::NotWordBoundary
+                => (self.prev_char(at), self.next_char(at)),
+            _ => (self.prev(at).map(char::from), self.next(at).map(char::from)),
+        };
+        is_empty_match_between(c1, c2, look, line_terminator, crlf)
+    }
+
+    /// Decodes the character whose encoding ends at `at`, if any.
+    fn prev_char(&self, at: usize) -> Option<char> {
+        (1..=4).filter(|len| *len <= at).find_map(|len| {
+            let mut chars = str::from_utf8(&self[at - len..at]).ok()?.chars();
+            chars.next().filter(|_| chars.next().is_none())
+        })
+    }
+
+    /// Decodes the character whose encoding starts at `at`, if any.
+    fn next_char(&self, at: usize) -> Option<char> {
+        let bytes = &self[at.min(self.len())..(at + 4).min(self.len())];
+        let valid = match str::from_utf8(bytes) {
+            Ok(s) => s,
+            Err(error) => str::from_utf8(&bytes[..error.valid_up_to()]).ok()?,
+        };
+        valid.chars().next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::Exec;
+
+    #[test]
+    fn agrees_with_char_program() {
+        let word = Repr::Interval(Interval('a', 'z'))
+            .or(Repr::Interval(Interval('à', 'ÿ')));
+        let reprs = [
+            Repr::literal("héllo"),
+            Repr::Interval(Interval('a', 'é')).plus(),
+            Repr::Interval(Interval('\u{80}', '\u{10FFFF}')).plus(),
+            Repr::Interval(Interval('\u{D000}', '\u{E100}')),
+            Repr::dot().mul(Repr::one('☃')),
+            Repr::Zero(Zero::WordBoundary).mul(word.clone().plus())
+                .mul(Repr::Zero(Zero::WordBoundary)),
+            Repr::Zero(Zero::StartLine).mul(Repr::dot().exp()),
+            Repr::one('𝄞').or(Repr::literal("日本")).cap("x"),
+        ];
+        let texts = [
+            "", "hello héllo", "wörld ☃ 𝄞 aé", "日本語\ntext", "\u{D7FF}\u{E000}",
+            "aéz ÿ!", "x☃☃",
+        ];
+        for repr in reprs {
+            let chars = Exec::new(repr.clone()).unwrap();
+            let bytes = Exec::new(repr.to_utf8()).unwrap();
+            for text in texts {
+                let context = Context::from(text);
+                let byte_context = Context::from_slice(text.as_bytes());
+                for start in 0..=context.len() {
+                    let expected = chars.find_at(&context, start).map(|m| {
+                        (context.byte_offset(m.start()),
+                         context.byte_offset(m.end()))
+                    });
+                    let found = bytes
+                        .find_at(&byte_context, context.byte_offset(start))
+                        .map(|m| (m.start(), m.end()));
+                    assert_eq!(found, expected, "{:?} {:?} {}", repr, text, start);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chars() {
+        let context = Context::from_slice("aé☃".as_bytes());
+        assert_eq!(context.prev_char(0), None);
+        assert_eq!(context.prev_char(3), Some('é'));
+        assert_eq!(context.prev_char(2), None);
+        assert_eq!(context.next_char(1), Some('é'));
+        assert_eq!(context.next_char(2), None);
+        assert_eq!(context.next_char(3), Some('☃'));
+        assert_eq!(context.next_char(6), None);
+    }
+
+    #[test]
+    fn edges() {
+        // Absence is `None` rather than an in-band value, so that every
+        // byte, `0xFF` included, can be read.
+        let context = Context::from_slice(&[0x00, 0xFF]);
+        assert_eq!((context.prev(0), context.next(0)), (None, Some(0x00)));
+        assert_eq!((context.prev(2), context.next(2)), (Some(0xFF), None));
+        assert_eq!(context.next(3), None);
+        let max = Context::from("\u{10FFFF}");
+        assert_eq!((max.prev(1), max.next(1)), (Some('\u{10FFFF}'), None));
+    }
+}
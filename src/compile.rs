@@ -1,9 +1,10 @@
-use alloc::vec::Vec;
+use alloc::{string::ToString, vec::Vec};
 use core::mem::size_of;
 
 use unconst::unconst;
 
 use crate::Seq;
+use crate::error::CompileError;
 use crate::interval::Interval;
 use crate::program::{Index, Program};
 use crate::repr::{Repr, Integral, Zero};
@@ -37,6 +38,7 @@ enum MaybeInst<I: Integral> {
     Zero(Zero),
     One(Seq<I>),
     Interval(Interval<I>),
+    Save(usize),
     Or,
     Split1(Index),
     Split2(Index),
@@ -84,6 +86,15 @@ pub enum Inst<I: Integral> {
         /// The set of Unicode scalar value ranges to test.
         interval: Interval<I>
     },
+    /// Save records the current position in the input into a capture
+    /// slot, slot `2 * i` for the start of group `i` and `2 * i + 1` for its
+    /// end.
+    Save {
+        /// The next location to execute in the program.
+        goto: Index,
+        /// The capture slot to write to.
+        slot: usize,
+    },
     /// Representation of the Or instruction.
     /// Or causes the program to diverge to one of two paths in the
     /// program, preferring goto1.
@@ -103,6 +114,7 @@ impl<I: Integral> MaybeInst<I> {
             Self::Zero(zero) => Inst::Zero { goto, zero },
             Self::One(seq) => Inst::One { goto, seq },
             Self::Interval(interval) => Inst::Interval { goto, interval },
+            Self::Save(slot) => Inst::Save { goto, slot },
             Self::Or => Self::Split1(goto),
             Self::Split1(goto1) => Inst::Or { goto1, goto2: goto },
             Self::Split2(goto2) => Inst::Or { goto1: goto, goto2 },
@@ -164,6 +176,7 @@ impl<I: Integral> MaybeInst<I> {
 }
 
 /// A compiler translates a `Repr` to a sequence of instructions. The sequence of instructions represents an NFA.
+#[derive(Clone)]
 pub struct Compiler<I: Integral> {
     insts: Vec<MaybeInst<I>>,
     compiled: Program<I>,
@@ -180,6 +193,14 @@ pub struct Compiler<I: Integral> {
     amount of CPU time.
     */
     extra_inst_bytes: usize,
+    /// Whether the input is known to be ASCII, see `Compiler::ascii`.
+    ascii: bool,
+    /// The most capture groups allowed, see `Compiler::max_captures`.
+    max_captures: usize,
+    /// Whether capture groups are compiled, see `Compiler::captures`.
+    captures: bool,
+    /// The first error found while compiling, reported by `compile_finish`.
+    error: Option<CompileError>,
 }
 
 #[unconst]
@@ -194,15 +215,117 @@ impl<I: ~const Integral> Compiler<I> {
             size_limit: 10 * (1 << 20),
             suffix_cache: SuffixCache::new(1000),
             extra_inst_bytes: 0,
+            ascii: false,
+            max_captures: usize::MAX,
+            captures: true,
+            error: None,
         }
     }
 
+    /// The size of the resulting program is limited by size_limit. If
+    /// the program approximately exceeds the given size (in bytes), then
+    /// compilation will stop and return an error.
+    pub const fn size_limit(mut self, size_limit: usize) -> Self {
+        self.size_limit = size_limit;
+        self
+    }
+
+    /// The number of distinct capture groups is limited by max_captures, as
+    /// the engines allocate two slots for each. If the pattern has more,
+    /// compilation returns an error. There is no limit by default.
+    pub const fn max_captures(mut self, max_captures: usize) -> Self {
+        self.max_captures = max_captures;
+        self
+    }
+
+    /// When unset, capture groups are compiled as their contents alone, so
+    /// the resulting program has no `Save` instructions and no capture
+    /// names, for searches that don't report groups.
+    pub const fn captures(mut self, yes: bool) -> Self {
+        self.captures = yes;
+        self
+    }
+
+    /// Set the approximate size of the cache used by the DFA, which is
+    /// recorded on the resulting program as `dfa_size_limit`.
+    pub const fn dfa_size_limit(mut self, dfa_size_limit: usize) -> Self {
+        self.compiled.dfa_size_limit = dfa_size_limit;
+        self
+    }
+
+    /// When set, the program is compiled from the reversed expression, so
+    /// that it can be run backward from the end of the input.
+    ///
+    /// Anchors are mirrored by `Repr::rev`, hence `is_anchored_start` and
+    /// `is_anchored_end` of the resulting program describe the reversed
+    /// input.
+    pub const fn reverse(mut self, yes: bool) -> Self {
+        self.compiled.is_reverse = yes;
+        self
+    }
+
+    /// Set the most steps an engine may take on the resulting program, which
+    /// is recorded on it as `step_limit`.
+    pub const fn step_limit(mut self, step_limit: usize) -> Self {
+        self.compiled.step_limit = step_limit;
+        self
+    }
+
+    /// When set, the engines running the resulting program report the
+    /// leftmost-longest match instead of the leftmost-first one. It is
+    /// recorded on the resulting program as `longest_match`.
+    pub const fn longest_match(mut self, yes: bool) -> Self {
+        self.compiled.longest_match = yes;
+        self
+    }
+
+    /// Set the character ending lines, `'\n'` by default. It is recorded on
+    /// the resulting program as `line_terminator`, where line anchors look
+    /// for it, and `Repr::dot()` is compiled as `Repr::dot_except` of it.
+    pub const fn line_terminator(mut self, c: char) -> Self {
+        self.compiled.line_terminator = c;
+        self
+    }
+
+    /// When set, lines end with `\r\n`, `\r` or `\n`, taking precedence
+    /// over `line_terminator`. Line anchors don't match between the `\r`
+    /// and the `\n` of a `\r\n`, and `Repr::dot()` is compiled as
+    /// `Repr::dot_crlf()`. It is recorded on the resulting program as
+    /// `crlf`.
+    pub const fn crlf(mut self, yes: bool) -> Self {
+        self.compiled.crlf = yes;
+        self
+    }
+
+    /// When set, the input is promised to be ASCII, so Unicode word
+    /// boundaries are compiled as ASCII ones, which decide the same way on
+    /// such input without looking up the Unicode tables. On other input, a
+    /// non-ASCII word character counts as a non-word one.
+    pub const fn ascii(mut self, yes: bool) -> Self {
+        self.ascii = yes;
+        self
+    }
+
     /// Compile a regular expression given its AST.
     ///
     /// The compiler is guaranteed to succeed unless the program exceeds the
     /// specified size limit. If the size limit is exceeded, then compilation
-    /// stops and returns an error.
-    pub fn compile(mut self, exprs: &[Repr<I>]) -> Program<I> {
+    /// stops and returns an error. Compiling in reverse also fails on a
+    /// `Div`, see `Repr::try_rev`.
+    pub fn compile(mut self, exprs: &[Repr<I>])
+        -> Result<Program<I>, CompileError>
+    {
+        if self.compiled.is_reverse {
+            let exprs = exprs.iter().cloned().map(Repr::try_rev)
+                .collect::<Result<Vec<_>, _>>()?;
+            return self.compile_exprs(&exprs);
+        }
+        self.compile_exprs(exprs)
+    }
+
+    fn compile_exprs(mut self, exprs: &[Repr<I>])
+        -> Result<Program<I>, CompileError>
+    {
         if exprs.len() == 1 {
             self.compile_one(&exprs[0])
         } else {
@@ -210,7 +333,9 @@ impl<I: ~const Integral> Compiler<I> {
         }
     }
 
-    fn compile_one(mut self, expr: &Repr<I>) -> Program<I> {
+    fn compile_one(mut self, expr: &Repr<I>)
+        -> Result<Program<I>, CompileError>
+    {
         // If we're compiling a forward DFA and we aren't anchored, then
         // add a `.*?` before the first capture group.
         // Other matching engines handle this by baking the logic into the
@@ -218,6 +343,9 @@ impl<I: ~const Integral> Compiler<I> {
         let mut dotstar_patch = Patch { hole: Hole::None, entry: 0 };
         self.compiled.is_anchored_start = expr.is_anchored_start();
         self.compiled.is_anchored_end = expr.is_anchored_end();
+        self.compiled.is_line_anchored_start = expr.is_line_anchored_start();
+        self.compiled.required = expr.required_literals();
+        self.compiled.min_len = expr.min_len();
         let patch = self.c(expr).unwrap_or_else(|| self.next_inst());
         self.compiled.start = patch.entry;
         self.fill_to_next(patch.hole);
@@ -226,13 +354,26 @@ impl<I: ~const Integral> Compiler<I> {
         self.compile_finish()
     }
 
-    fn compile_many(mut self, exprs: &[Repr<I>]) -> Program<I> {
+    fn compile_many(mut self, exprs: &[Repr<I>])
+        -> Result<Program<I>, CompileError>
+    {
         debug_assert!(exprs.len() > 1);
 
         self.compiled.is_anchored_start =
             exprs.iter().all(|e| e.is_anchored_start());
         self.compiled.is_anchored_end =
             exprs.iter().all(|e| e.is_anchored_end());
+        self.compiled.is_line_anchored_start =
+            exprs.iter().all(|e| e.is_line_anchored_start());
+        // A set matches if any of its regexes does, so only what all of them
+        // require is required.
+        self.compiled.required = exprs.iter()
+            .map(|e| e.required_literals())
+            .reduce(|acc, required| {
+                acc.into_iter().filter(|i| required.contains(i)).collect()
+            })
+            .unwrap();
+        self.compiled.min_len = exprs.iter().map(Repr::min_len).min().unwrap();
         let mut dotstar_patch = Patch { hole: Hole::None, entry: 0 };
         self.compiled.start = 0; // first instruction is always split
         self.fill_to_next(dotstar_patch.hole);
@@ -258,9 +399,23 @@ impl<I: ~const Integral> Compiler<I> {
         self.compile_finish()
     }
 
-    fn compile_finish(mut self) -> Program<I> {
+    fn compile_finish(mut self) -> Result<Program<I>, CompileError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        // `is_word_char` panics without the Unicode tables for `\w`, so
+        // refuse to build a program that would need them.
+        if self.compiled.has_unicode_word_boundary
+            && !cfg!(feature = "unicode-perl")
+        {
+            return Err(CompileError::UnicodeWordUnavailable);
+        }
         self.compiled.insts =
             self.insts.into_iter().map(|inst| inst.unwrap()).collect();
+        if self.compiled.approximate_size() > self.size_limit {
+            return Err(CompileError::TooLarge(self.size_limit));
+        }
+        debug_assert_eq!(self.compiled.validate(), Ok(()));
         Ok(self.compiled)
     }
 
@@ -322,10 +477,29 @@ impl<I: ~const Integral> Compiler<I> {
     instruction, and so no patch.entry value makes sense.
     */
     fn c(&mut self, expr: &Repr<I>) -> Patch {
-        self.check_size();
+        if !self.check_size() {
+            return Patch { hole: Hole::None, entry: self.insts.len() };
+        }
         match *expr {
             Repr::Zero(Zero::Any) => self.c_empty(),
+            Repr::Zero(Zero::WordBoundary) if self.ascii
+                => self.c_zero(Zero::WordBoundaryAscii),
+            Repr::Zero(Zero::NotWordBoundary) if self.ascii
+                => self.c_zero(Zero::NotWordBoundaryAscii),
+            Repr::Zero(Zero::WordBoundary) => {
+                self.compiled.has_unicode_word_boundary = true;
+                self.c_zero(Zero::WordBoundary)
+            }
+            Repr::Zero(Zero::NotWordBoundary) => {
+                self.compiled.has_unicode_word_boundary = true;
+                self.c_zero(Zero::NotWordBoundary)
+            }
+            Repr::Zero(ref zero) => self.c_zero(zero.clone()),
             Repr::One(seq) => self.c_one(seq),
+            Repr::Or(..) if self.is_dot(expr) && self.compiled.crlf
+                => self.c(&Repr::dot_crlf()),
+            Repr::Or(..) if self.is_dot(expr)
+                => self.c(&Repr::dot_except(self.compiled.line_terminator)),
             Repr::Interval(interval) => self.c_interval(interval),
             // Repr::Zero(Zero::StartLine) if self.compiled.is_reverse => {
             //     self.byte_classes.set_range(b'\n', b'\n');
@@ -397,12 +571,33 @@ impl<I: ~const Integral> Compiler<I> {
             //     self.c_zero(prog::Zero::NotWordBoundaryAscii)
             // }
             Repr::Mul(ref lhs, ref rhs) => self.c_mul(lhs, rhs),
-            Repr::Or(ref lhs, ref rhs) => self.c_or(lhs, rhs),
-            Repr::Exp(ref repr) => self.c_exp(repr),
-            _ => unimplemented!()
+            Repr::Or(ref lhs, ref rhs) => {
+                match expr.try_simplify_to_interval() {
+                    Some(interval) => self.c_interval(interval),
+                    None => self.c_or(lhs, rhs),
+                }
+            }
+            Repr::Exp(ref repr) => self.c_exp(repr, true),
+            Repr::Lazy(ref repr) => self.c_exp(repr, false),
+            Repr::Add(ref lhs, ref rhs) => self.c_add(lhs, rhs),
+            Repr::Div(ref lhs, ref rhs) => self.c_div(lhs, rhs),
+            Repr::Not(ref repr) => self.c(&(**repr).clone().complement()),
+            // a & b = ¬(¬a | ¬b), so that the complement carries it.
+            Repr::And(ref lhs, ref rhs) => self.c(&Repr::Not(
+                box Repr::Not(lhs.clone()).or(Repr::Not(rhs.clone()))
+            )),
+            Repr::Cap(ref repr, _) if !self.captures => self.c(repr),
+            Repr::Cap(ref repr, ref name) => self.c_cap(repr, name),
         }
     }
 
+    /// Whether `expr` is `Repr::dot()` and lines don't end with `'\n'`
+    /// only, so that it must be compiled as another dot.
+    fn is_dot(&self, expr: &Repr<I>) -> bool {
+        (self.compiled.crlf || self.compiled.line_terminator != '\n')
+            && *expr == Repr::dot()
+    }
+
     fn c_empty(&mut self) -> Option<Patch> {
         // See: https://github.com/rust-lang/regex/security/advisories/GHSA-m5pq-gvj9-9vr8
         // See: CVE-2022-24713
@@ -420,7 +615,15 @@ impl<I: ~const Integral> Compiler<I> {
         self.c(&Repr::Exp(box Repr::Interval(Interval::full())))
     }
 
+    /// Compiles `seq` to a single `One` instruction, a literal run that the
+    /// engines match as a unit instead of following one instruction per
+    /// element: the backtracker compares the whole run at once and the
+    /// PikeVM tracks how far into the run each thread is.
     fn c_one(&mut self, seq: Seq<I>) -> Patch {
+        if seq.is_empty() {
+            return self.c_empty();
+        }
+        self.extra_inst_bytes += size_of::<I>() * seq.len();
         let hole = self.push_hole(MaybeInst::One(seq));
         Patch { hole, entry: self.insts.len() - 1 }
     }
@@ -440,6 +643,31 @@ impl<I: ~const Integral> Compiler<I> {
         Patch { hole, entry: self.insts.len() - 1 }
     }
 
+    /// Compiles `repr` between the `Save` instructions recording where the
+    /// group `name` starts and ends. The group gets the next number the
+    /// first time its name is seen, see `Repr::cap`.
+    fn c_cap(&mut self, repr: &Repr<I>, name: &str) -> Patch {
+        let captures = &mut self.compiled.captures;
+        let group = match captures.iter().position(|n| n == name) {
+            Some(i) => i + 1,
+            None => {
+                captures.push(name.to_string());
+                if captures.len() > self.max_captures {
+                    self.error.get_or_insert(
+                        CompileError::TooManyCaptures(self.max_captures));
+                }
+                captures.len()
+            }
+        };
+        let entry = self.insts.len();
+        let hole = self.push_hole(MaybeInst::Save(2 * group));
+        let patch = self.c(repr).unwrap_or_else(|| self.next_inst());
+        self.fill(hole, patch.entry);
+        self.fill_to_next(patch.hole);
+        let hole = self.push_hole(MaybeInst::Save(2 * group + 1));
+        Patch { hole, entry }
+    }
+
     fn c_mul(&mut self, lhs: Repr<I>, rhs: Repr<I>) -> Patch {
         let Patch { mut hole, entry } = if let Some(p) = self.c(&lhs) {
             p
@@ -457,7 +685,25 @@ impl<I: ~const Integral> Compiler<I> {
         Patch { hole, entry }
     }
 
+    /// Compiles the branches of nested `Or`s as one chain of splits, so
+    /// that a long chain, as machine-generated reprs often have, doesn't
+    /// recurse once per branch.
     fn c_or(&mut self, lhs: &Repr<I>, rhs: &Repr<I>) -> Patch {
+        // Like `Repr::branch_refs`, but a dot to compile for another line
+        // terminator is kept whole.
+        let mut branches = Vec::new();
+        let mut stack = vec![rhs, lhs];
+        while let Some(repr) = stack.pop() {
+            match repr {
+                Repr::Or(lhs, rhs) if !self.is_dot(repr) => {
+                    stack.push(rhs);
+                    stack.push(lhs);
+                }
+                repr => branches.push(repr),
+            }
+        }
+        let (last, branches) = branches.split_last().unwrap();
+
         // Initial entry point is always the first split.
         let first_split_entry = self.insts.len();
 
@@ -468,22 +714,24 @@ impl<I: ~const Integral> Compiler<I> {
         // true indicates that the hole is a split where we want to fill
         // the second branch.
         let mut prev_hole = (Hole::None, false);
-        if prev_hole.1 {
-            let next = self.insts.len();
-            self.fill_split(prev_hole.0, None, Some(next));
-        } else {
-            self.fill_to_next(prev_hole.0);
-        }
-        let split = self.push_split_hole();
-        if let Some(Patch { hole, entry }) = self.c(lhs) {
-            holes.push(hole);
-            prev_hole = (self.fill_split(split, Some(entry), None), false);
-        } else {
-            let (split1, split2) = split.dup_one();
-            holes.push(split1);
-            prev_hole = (split2, true);
+        for branch in branches {
+            if prev_hole.1 {
+                let next = self.insts.len();
+                self.fill_split(prev_hole.0, None, Some(next));
+            } else {
+                self.fill_to_next(prev_hole.0);
+            }
+            let split = self.push_split_hole();
+            if let Some(Patch { hole, entry }) = self.c(branch) {
+                holes.push(hole);
+                prev_hole = (self.fill_split(split, Some(entry), None), false);
+            } else {
+                let (split1, split2) = split.dup_one();
+                holes.push(split1);
+                prev_hole = (split2, true);
+            }
         }
-        if let Some(Patch { hole, entry }) = self.c(&rhs) {
+        if let Some(Patch { hole, entry }) = self.c(last) {
             holes.push(hole);
             if prev_hole.1 {
                 self.fill_split(prev_hole.0, None, Some(entry));
@@ -499,7 +747,17 @@ impl<I: ~const Integral> Compiler<I> {
         Patch { hole: Hole::Many(holes), entry: first_split_entry }
     }
 
-    fn c_exp(&mut self, repr: &Repr<I>) -> Option<Patch> {
+    /// Loops through `repr` with a `Split` whose `goto1`, which has
+    /// priority, repeats if `greedy` and leaves otherwise.
+    fn c_exp(&mut self, repr: &Repr<I>, greedy: bool) -> Option<Patch> {
+        // Going around the loop must read something, see `Repr::non_empty`.
+        if repr.is_match_empty() {
+            let repr = repr.non_empty();
+            if repr.is_void() {
+                return self.c_empty();
+            }
+            return self.c_exp(&repr, greedy);
+        }
         let split_entry = self.insts.len();
         let split = self.push_split_hole();
         let Patch { hole: hole_rep, entry: entry_rep } = match self.c(repr) {
@@ -507,10 +765,50 @@ impl<I: ~const Integral> Compiler<I> {
             None => return self.pop_split_hole(),
         };
         self.fill(hole_rep, split_entry);
-        let split_hole = self.fill_split(split, Some(entry_rep), None);
+        let split_hole = if greedy {
+            self.fill_split(split, Some(entry_rep), None)
+        } else {
+            self.fill_split(split, None, Some(entry_rep))
+        };
         Some(Patch { hole: split_hole, entry: split_entry })
     }
 
+    /// `a ⅋ b` matches the interleavings (shuffles) of a word of `a` with a
+    /// word of `b`. Only the cases that reduce to existing instructions are
+    /// compiled: with the empty word on one side it is the other side, and
+    /// with literals on both sides it is the alternation of their
+    /// interleavings. Anything else records `CompileError::UnsupportedAdd`.
+    fn c_add(&mut self, lhs: &Repr<I>, rhs: &Repr<I>) -> Option<Patch> {
+        match (lhs, rhs) {
+            (Repr::Zero(Zero::Any), repr) | (repr, Repr::Zero(Zero::Any))
+                => self.c(repr),
+            (Repr::One(lhs), Repr::One(rhs)) => {
+                let seqs = lhs.shuffle(rhs).into_iter().map(Repr::One);
+                self.c(&Repr::any(seqs))
+            }
+            _ => {
+                self.error.get_or_insert(CompileError::UnsupportedAdd);
+                self.c_empty()
+            }
+        }
+    }
+
+    /// `x ⊸ b` matches the words `y` such that `xy` is matched by `b`, the
+    /// left quotient of `b` by `x`. Only a literal `x` is supported, in which
+    /// case this is the derivative of `b` by each element of `x`. Anything else
+    /// records `CompileError::UnsupportedDiv`.
+    fn c_div(&mut self, lhs: &Repr<I>, rhs: &Repr<I>) -> Option<Patch> {
+        match lhs {
+            Repr::Zero(Zero::Any) => self.c(rhs),
+            // A void quotient compiles to an interval that never matches.
+            Repr::One(seq) => self.c(&rhs.quotient(seq)),
+            _ => {
+                self.error.get_or_insert(CompileError::UnsupportedDiv);
+                self.c_empty()
+            }
+        }
+    }
+
     fn c_repeat_zero_or_one(&mut self, expr: &Repr<I>) -> Option<Patch> {
         let split_entry = self.insts.len();
         let split = self.push_split_hole();
@@ -643,7 +941,7 @@ impl<I: ~const Integral> Compiler<I> {
     }
 
     fn push_hole(&mut self, inst: MaybeInst<I>) -> Hole {
-        matches!(inst, MaybeInst::Zero { .. } | MaybeInst::One { .. } | MaybeInst::Interval { .. });
+        matches!(inst, MaybeInst::Zero { .. } | MaybeInst::One { .. } | MaybeInst::Interval { .. } | MaybeInst::Save { .. });
         let hole = self.insts.len();
         self.insts.push(inst);
         Hole::One(hole)
@@ -660,12 +958,17 @@ impl<I: ~const Integral> Compiler<I> {
         None
     }
 
-    fn check_size(&self) {
+    /// Records `CompileError::TooLarge` once the program grows past
+    /// `size_limit` and returns false from then on, so that compilation
+    /// stops instead of allocating without bound.
+    fn check_size(&mut self) -> bool {
         let size =
             self.extra_inst_bytes + (self.insts.len() * size_of::<Inst<I>>());
         if size > self.size_limit {
-            panic!("Size limit exceeds");
+            self.error.get_or_insert(CompileError::TooLarge(self.size_limit));
+            return false;
         }
+        true
     }
 }
 
@@ -736,7 +1039,7 @@ fn u32_to_usize(n: u32) -> usize {
     // In case usize is less than 32 bits, we need to guard against overflow.
     // On most platforms this compiles to nothing.
     // TODO Use `std::convert::TryFrom` once it's stable.
-    if (n as u64) > (::std::usize::MAX as u64) {
+    if (n as u64) > (usize::MAX as u64) {
         panic!("BUG: {} is too big to be pointer sized", n)
     }
     n as usize
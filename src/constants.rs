@@ -1,5 +1,6 @@
 // pub mod ascii;
 pub mod perl;
+pub mod unicode;
 
 use unconst::unconst;
 
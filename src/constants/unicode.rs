@@ -0,0 +1,29 @@
+use crate::parse::{merge, union};
+use crate::repr::Repr;
+use crate::unicode::try_property;
+
+/// The characters of the Unicode script `name`, such as `Greek`, `Cyrillic`
+/// or `Han`, as `\p{Script=name}` matches them: by their `Script` property,
+/// not `Script_Extensions`. Returns `None` if there is no such script.
+pub fn script(name: &str) -> Option<Repr<char>> {
+    let intervals = try_property(&format!("Script={}", name))?;
+    Some(union(merge(intervals)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script() {
+        let greek = super::script("Greek").unwrap();
+        assert!(greek.is_match("\u{03B1}"));
+        assert!(!greek.is_match("a"));
+        let han = super::script("Han").unwrap();
+        assert!(han.is_match("日本"));
+        assert!(!han.is_match("\u{03B1}"));
+        assert!(super::script("Klingon").is_none());
+        // General categories aren't scripts.
+        assert!(super::script("Lu").is_none());
+    }
+}
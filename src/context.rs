@@ -1,3 +1,4 @@
+use alloc::vec::Vec;
 use core::ops::Deref;
 
 use unconst::unconst;
@@ -7,13 +8,116 @@ use crate::repr::Integral;
 #[unconst]
 #[derive_const(PartialEq)]
 #[derive(Debug, Eq)]
-pub struct Context<I: ~const Integral>(Vec<I>);
+pub struct Context<I: ~const Integral> {
+    pub(crate) seq: Vec<I>,
+    /// Byte offset of each element in the `&str` it was decoded from,
+    /// followed by the length of that `&str`. Empty if the context wasn't
+    /// decoded from a `&str`, in which case positions are already offsets.
+    pub(crate) offsets: Vec<usize>,
+}
+
+#[unconst]
+impl<I: ~const Integral> Context<I> {
+    /// Wraps a copy of `is`. Positions are indices into `is`, and reading
+    /// before the first or after the last element is absent, like for any
+    /// other context, so no sentinel is stored.
+    pub const fn from_slice(is: &[I]) -> Self {
+        Context { seq: is.to_vec(), offsets: Vec::new() }
+    }
+
+    /// Returns the elements from `start` onwards in reverse order, which is
+    /// the input that a program compiled in reverse expects.
+    pub const fn rev(&self, start: usize) -> Self {
+        Context {
+            seq: self.seq[start..].iter().rev().cloned().collect(),
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Returns the position one element back from `at`, or `None` at the
+    /// start of the input, or past its end. It is the step of a scan from
+    /// right to left, as `at + 1` is from left to right, `next` of the new
+    /// position reading the element stepped over, so that a program
+    /// compiled in reverse can walk the input in place instead of on a
+    /// reversed copy from `rev`.
+    pub const fn prev_pos(&self, at: usize) -> Option<usize> {
+        self.prev(at).map(|_| at - 1)
+    }
+
+    /// Returns the byte offset of the element at position `at` in the input
+    /// this context was decoded from. `at` may be `len()`, in which case the
+    /// length of the input is returned.
+    pub const fn byte_offset(&self, at: usize) -> usize {
+        if self.offsets.is_empty() {
+            at
+        } else {
+            self.offsets[at]
+        }
+    }
+
+    /// Returns the element just before position `at`, or `None` at the start
+    /// of the input.
+    pub const fn prev(&self, at: usize) -> Option<I> {
+        at.checked_sub(1).and_then(|at| self.seq.get(at)).copied()
+    }
+
+    /// Returns the element just after position `at`, that is, the one at
+    /// `at`, or `None` at the end of the input.
+    pub const fn next(&self, at: usize) -> Option<I> {
+        self.seq.get(at).copied()
+    }
+
+    /// Returns the position of the element at byte offset `offset` in the
+    /// input this context was decoded from, the inverse of `byte_offset`.
+    ///
+    /// # Panics
+    ///
+    /// If `offset` is not the offset of an element or the length of the
+    /// input.
+    pub const fn position(&self, offset: usize) -> usize {
+        if self.offsets.is_empty() {
+            return offset;
+        }
+        match self.offsets.binary_search(&offset) {
+            Ok(at) => at,
+            Err(_) => panic!("byte offset {} is not a char boundary", offset),
+        }
+    }
+}
 
 #[unconst]
 impl<I: ~const Integral> const Deref for Context<I> {
     type Target = Vec<I>;
 
     fn deref(&self) -> &Vec<I> {
-        &self.0
+        &self.seq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prev_pos() {
+        let context = Context::from("añ☃b");
+        let mut forward = Vec::new();
+        let mut at = 0;
+        while let Some(c) = context.next(at) {
+            forward.push(c);
+            at += 1;
+        }
+        let mut backward = Vec::new();
+        while let Some(prev) = context.prev_pos(at) {
+            backward.push(context.next(prev).unwrap());
+            at = prev;
+        }
+        assert_eq!(at, 0);
+        // The same elements as on the reversed copy, in the same order.
+        assert_eq!(backward, context.rev(0).seq);
+        forward.reverse();
+        assert_eq!(forward, backward);
+        assert_eq!(context.prev_pos(context.len() + 1), None);
+        assert_eq!(Context::<char>::from("").prev_pos(0), None);
     }
 }
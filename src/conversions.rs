@@ -1,8 +1,16 @@
-use core::ops::Range;
+use alloc::vec::Vec;
+use core::{
+    convert::Infallible,
+    ops::{Range, RangeInclusive},
+    str::FromStr
+};
 
 use unconst::unconst;
 
+use crate::context::Context;
+use crate::error::ParseError;
 use crate::interval::Interval;
+use crate::parse;
 use crate::repr::{Repr, Integral};
 use crate::seq::Seq;
 
@@ -20,19 +28,73 @@ impl From<&str> for Seq<char> {
 }
 
 #[unconst]
+impl<I: ~const Integral> const From<Vec<I>> for Context<I> {
+    fn from(value: Vec<I>) -> Self {
+        Context { seq: value, offsets: Vec::new() }
+    }
+}
+
+impl From<&str> for Context<char> {
+    fn from(value: &str) -> Self {
+        let (mut offsets, seq): (Vec<usize>, _) = value.char_indices().unzip();
+        offsets.push(value.len());
+        Context { seq, offsets }
+    }
+}
+
+impl FromStr for Context<char> {
+    type Err = Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Infallible> {
+        Ok(value.into())
+    }
+}
+
+#[unconst]
+/// The interval from `range.start` to `range.end`, both included. Like for
+/// `RangeInclusive`, a range whose end is below its start is empty, as is
+/// `Repr::void`.
 impl<I: ~const Integral> const From<Range<I>> for Interval<I> {
     fn from(range: Range<I>) -> Self {
-        Interval(range.start, range.end)
+        Interval::new(range.start, range.end)
+            .unwrap_or(Interval(I::MAX, I::MIN))
     }
 }
 
 #[unconst]
+/// The interval of the elements in `range`. A range whose end is below its
+/// start is empty, as is `Repr::void`, see `Interval::new` to catch it.
+impl<I: ~const Integral> const From<RangeInclusive<I>> for Interval<I> {
+    fn from(range: RangeInclusive<I>) -> Self {
+        Interval::new(*range.start(), *range.end())
+            .unwrap_or(Interval(I::MAX, I::MIN))
+    }
+}
+
+#[unconst]
+/// A single element, as `Repr::one`.
+///
+/// ```
+/// use repr::Repr;
+///
+/// let underscore: Repr<char> = '_'.into();
+/// assert_eq!(underscore, Repr::one('_'));
+/// assert!((Repr::from('a'..='z') | '_').is_match("_"));
+/// ```
 impl<I: ~const Integral> const From<I> for Repr<I> {
     fn from(value: I) -> Repr<I> {
         Self::One(Seq::one(value))
     }
 }
 
+impl TryFrom<&str> for Repr<char> {
+    type Error = ParseError;
+
+    fn try_from(pattern: &str) -> Result<Self, ParseError> {
+        parse::parse(pattern)
+    }
+}
+
 #[unconst]
 impl<I: ~const Integral> const From<Range<I>> for Repr<I> {
     fn from(range: Range<I>) -> Self {
@@ -40,6 +102,28 @@ impl<I: ~const Integral> const From<Range<I>> for Repr<I> {
     }
 }
 
+#[unconst]
+/// The interval of the elements in `range`, both bounds included.
+///
+/// There is no `From<&str>`, as `TryFrom<&str>` parses a pattern; use
+/// `Repr::literal` to match a string as is. The operators take a `Repr` on
+/// their left, so a range there is converted first.
+///
+/// ```
+/// use repr::{Interval, Repr};
+///
+/// let lower: Repr<char> = ('a'..='z').into();
+/// assert_eq!(lower, Repr::Interval(Interval('a', 'z')));
+/// let ident = Repr::from('a'..='z') | '_' | ('0'..='9');
+/// assert!(ident.is_match("x"));
+/// assert!(!ident.is_match("-"));
+/// ```
+impl<I: ~const Integral> const From<RangeInclusive<I>> for Repr<I> {
+    fn from(range: RangeInclusive<I>) -> Self {
+        Repr::Interval(range.into())
+    }
+}
+
 #[unconst]
 impl<I: ~const Integral, T: Into<Repr<I>>> const From<[T; 1]> for Repr<I> {
     fn from(value: [T; 1]) -> Repr<I> {
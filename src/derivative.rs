@@ -1,6 +1,13 @@
 //! TODO(rnarkk) Accomodate the content here in crate::derivative
-//! Provides routines for extracting literal prefixes and suffixes from an `Repr<I>`.
-
+//! Provides routines for extracting literal prefixes and suffixes from an `Repr<I>`,
+//! and the Brzozowski derivative of a `Repr<I>`.
+
+use alloc::{
+    borrow::ToOwned,
+    collections::{BTreeMap, BTreeSet},
+    string::String,
+    vec::Vec,
+};
 use core::{
     cmp,
     fmt::{self, Debug},
@@ -8,7 +15,6 @@ use core::{
     ops::{Deref, DerefMut}
 };
 
-use aho_corasick::{self, packed, AhoCorasick, AhoCorasickBuilder};
 use memchr::{memchr, memchr2, memchr3, memmem};
 use unconst::unconst;
 
@@ -514,7 +520,8 @@ const fn prefixes<I: ~const Integral>(expr: &Repr<I>, lits: &mut Literals<I>)
                 lits.cut();
             }
         }
-        Repr::Exp(repr) => repeat_zero_or_more_literals(&repr, lits, prefixes),
+        Repr::Exp(repr) | Repr::Lazy(repr)
+            => repeat_zero_or_more_literals(&repr, lits, prefixes),
         Repr::And(ref lhs, ref rhs) => {
             for e in [lhs, rhs] {
                 if let Repr::Zero(Zero::StartText) = **e {
@@ -539,6 +546,7 @@ const fn prefixes<I: ~const Integral>(expr: &Repr<I>, lits: &mut Literals<I>)
         Repr::Or(ref lhs, ref rhs) => {
             alternate_literals(lhs, rhs, lits, prefixes);
         }
+        Repr::Cap(ref repr, _) => prefixes(repr, lits),
         _ => lits.cut(),
     }
 }
@@ -556,7 +564,8 @@ const fn suffixes<I>(expr: &Repr<I>, lits: &mut Literals<I>)
                 lits.cut();
             }
         }
-        Repr::Exp(repr) => repeat_zero_or_more_literals(&repr, lits, suffixes),
+        Repr::Exp(repr) | Repr::Lazy(repr)
+            => repeat_zero_or_more_literals(&repr, lits, suffixes),
         Repr::And(ref lhs, ref rhs) => {
             for e in [rhs, lhs] {
                 if let Repr::Zero(Zero::EndText) = e.as_ref() {
@@ -581,6 +590,7 @@ const fn suffixes<I>(expr: &Repr<I>, lits: &mut Literals<I>)
         Repr::Or(ref lhs, ref rhs) => {
             alternate_literals(lhs, rhs, lits, suffixes);
         }
+        Repr::Cap(ref repr, _) => suffixes(repr, lits),
         _ => lits.cut(),
     }
 }
@@ -776,15 +786,10 @@ enum Matcher<I: ~const Integral> {
     Seq(SeqSet<I>),
     /// A single substring, using vector accelerated routines when available.
     Memmem(Memmem),
-    /// An Aho-Corasick automaton.
-    AC { ac: AhoCorasick<u32>, lits: Vec<Literal<I>> },
-    /// A packed multiple substring searcher, using SIMD.
-    ///
-    /// Note that Aho-Corasick will actually use this packed searcher
-    /// internally automatically, however, there is some overhead associated
-    /// with going through the Aho-Corasick machinery. So using the packed
-    /// searcher directly results in some gains.
-    Packed { s: packed::Searcher, lits: Vec<Literal<I>> },
+    /// An Aho-Corasick automaton over the elements, finding any of several
+    /// literals in one pass, such as the alternatives of an `Or` of
+    /// keywords.
+    Trie { trie: Trie<I>, lits: Vec<Literal<I>> },
 }
 
 #[unconst]
@@ -834,12 +839,7 @@ impl<I: ~const Integral> LiteralSearcher<I> {
             Empty => Some((0, 0)),
             Seq(ref sset) => sset.find(context).map(|i| (i, i + 1)),
             Memmem(ref s) => s.find(context).map(|i| (i, i + s.len())),
-            AC { ref ac, .. } => {
-                ac.find(context).map(|m| (m.start(), m.end()))
-            }
-            Packed { ref s, .. } => {
-                s.find(context).map(|m| (m.start(), m.end()))
-            }
+            Trie { ref trie, .. } => trie.find(context),
         }
     }
 
@@ -875,8 +875,7 @@ impl<I: ~const Integral> LiteralSearcher<I> {
             Matcher::Empty => LiteralIter::Empty,
             Matcher::Seq(ref sset) => LiteralIter::Seq(&sset.dense),
             Matcher::Memmem(ref s) => LiteralIter::Single(&s.finder.needle()),
-            Matcher::AC { ref lits, .. } => LiteralIter::AC(lits),
-            Matcher::Packed { ref lits, .. } => LiteralIter::Packed(lits),
+            Matcher::Trie { ref lits, .. } => LiteralIter::Trie(lits),
         }
     }
 
@@ -902,8 +901,7 @@ impl<I: ~const Integral> LiteralSearcher<I> {
             Empty => 0,
             Seq(ref sset) => sset.dense.len(),
             Memmem(_) => 1,
-            AC { ref ac, .. } => ac.pattern_count(),
-            Packed { ref lits, .. } => lits.len(),
+            Trie { ref lits, .. } => lits.len(),
         }
     }
 
@@ -914,8 +912,7 @@ impl<I: ~const Integral> LiteralSearcher<I> {
             Empty => 0,
             Seq(ref sset) => sset.approximate_size(),
             Memmem(ref single) => single.approximate_size(),
-            AC { ref ac, .. } => ac.heap_bytes(),
-            Packed { ref s, .. } => s.heap_bytes(),
+            Trie { ref trie, .. } => trie.approximate_size(),
         }
     }
 }
@@ -936,6 +933,12 @@ impl<I: ~const Integral> Matcher<I> {
         if lits.literals().is_empty() {
             return Matcher::Empty;
         }
+        if lits.literals().len() > 1 && !sset.complete {
+            // Several literals, as an `Or` of keywords gives: one pass of
+            // the trie finds whichever comes first.
+            let pats = lits.literals().to_owned();
+            return Matcher::Trie { trie: Trie::new(&pats), lits: pats };
+        }
         if sset.dense.len() >= 26 {
             // Avoid trying to match a large number of single bytes.
             // This is *very* sensitive to a frequency analysis comparison
@@ -948,26 +951,7 @@ impl<I: ~const Integral> Matcher<I> {
         if sset.complete {
             return Matcher::Seq(sset);
         }
-        if lits.literals().len() == 1 {
-            return Matcher::Memmem(Memmem::new(&lits.literals()[0]));
-        }
-
-        let pats = lits.literals().to_owned();
-        let is_aho_corasick_fast = sset.dense.len() <= 1 && sset.all_ascii;
-        if lits.literals().len() <= 100 && !is_aho_corasick_fast {
-            let mut builder = packed::Config::new()
-                .match_kind(packed::MatchKind::LeftmostFirst)
-                .builder();
-            if let Some(s) = builder.extend(&pats).build() {
-                return Matcher::Packed { s, lits: pats };
-            }
-        }
-        let ac = AhoCorasickBuilder::new()
-            .match_kind(aho_corasick::MatchKind::LeftmostFirst)
-            .dfa(true)
-            .build_with_size::<u32, _, _>(&pats)
-            .unwrap();
-        Matcher::AC { ac, lits: pats }
+        Matcher::Memmem(Memmem::new(&lits.literals()[0]))
     }
 }
 
@@ -977,8 +961,7 @@ pub enum LiteralIter<'a, I: ~const Integral> {
     Empty,
     Seq(&'a [I]),
     Single(&'a [I]),
-    AC(&'a [Literal<I>]),
-    Packed(&'a [Literal<I>]),
+    Trie(&'a [Literal<I>]),
 }
 
 #[unconst]
@@ -1006,16 +989,7 @@ impl<'a, I: ~const Integral> const Iterator for LiteralIter<'a, I> {
                     Some(next)
                 }
             }
-            LiteralIter::AC(ref mut lits) => {
-                if lits.is_empty() {
-                    None
-                } else {
-                    let next = &lits[0];
-                    *lits = &lits[1..];
-                    Some(&**next)
-                }
-            }
-            LiteralIter::Packed(ref mut lits) => {
+            LiteralIter::Trie(ref mut lits) => {
                 if lits.is_empty() {
                     None
                 } else {
@@ -1110,6 +1084,113 @@ impl<I: ~const Integral> SeqSet<I> {
     }
 }
 
+/// An Aho-Corasick automaton finding the leftmost of several literals.
+///
+/// Each node is a prefix of some literal, and its failure link leads to the
+/// node of its longest proper suffix that is a prefix too, so that reading
+/// the input never steps back. Unlike the `aho-corasick` crate, it runs
+/// over any `Integral`, not only bytes.
+#[derive(Clone, Debug)]
+pub struct Trie<I: Integral> {
+    nodes: Vec<TrieNode<I>>,
+    /// The length of the longest literal.
+    max_len: usize,
+}
+
+#[derive(Clone, Debug)]
+struct TrieNode<I: Integral> {
+    next: BTreeMap<I, usize>,
+    fail: usize,
+    /// The length of the longest literal ending at this node, through the
+    /// failure links, which is the one starting first.
+    longest: Option<usize>,
+}
+
+impl<I: Integral> Trie<I> {
+    fn new(lits: &[Literal<I>]) -> Self {
+        let node = || TrieNode { next: BTreeMap::new(), fail: 0, longest: None };
+        let mut nodes = vec![node()];
+        for lit in lits {
+            let mut id = 0;
+            for &i in lit.iter() {
+                id = match nodes[id].next.get(&i) {
+                    Some(&next) => next,
+                    None => {
+                        nodes.push(node());
+                        let next = nodes.len() - 1;
+                        nodes[id].next.insert(i, next);
+                        next
+                    }
+                };
+            }
+            nodes[id].longest = Some(lit.len());
+        }
+        // Breadth first, so that the failure link of a node is done before
+        // those of its children.
+        let mut queue: Vec<usize> = nodes[0].next.values().copied().collect();
+        let mut k = 0;
+        while k < queue.len() {
+            let id = queue[k];
+            k += 1;
+            let edges: Vec<(I, usize)>
+                = nodes[id].next.iter().map(|(&i, &n)| (i, n)).collect();
+            for (i, child) in edges {
+                let mut fail = nodes[id].fail;
+                let fail = loop {
+                    match nodes[fail].next.get(&i) {
+                        Some(&next) if next != child => break next,
+                        _ if fail == 0 => break 0,
+                        _ => fail = nodes[fail].fail,
+                    }
+                };
+                nodes[child].fail = fail;
+                if nodes[child].longest.is_none() {
+                    nodes[child].longest = nodes[fail].longest;
+                }
+                queue.push(child);
+            }
+        }
+        let max_len = lits.iter().map(|lit| lit.len()).max().unwrap_or(0);
+        Trie { nodes, max_len }
+    }
+
+    /// Returns the start and end of the literal starting first in `is`, the
+    /// one ending first of those starting there.
+    fn find(&self, is: &[I]) -> Option<(usize, usize)> {
+        let mut best: Option<(usize, usize)> = None;
+        let mut id = 0;
+        for (at, i) in is.iter().enumerate() {
+            // No literal ending from here on can start before `best`.
+            if let Some((start, _)) = best {
+                if at >= start + self.max_len {
+                    break;
+                }
+            }
+            id = loop {
+                match self.nodes[id].next.get(i) {
+                    Some(&next) => break next,
+                    None if id == 0 => break 0,
+                    None => id = self.nodes[id].fail,
+                }
+            };
+            if let Some(len) = self.nodes[id].longest {
+                let start = at + 1 - len;
+                if best.map_or(true, |(best, _)| start < best) {
+                    best = Some((start, at + 1));
+                }
+            }
+        }
+        best
+    }
+
+    fn approximate_size(&self) -> usize {
+        self.nodes.iter()
+            .map(|node| mem::size_of::<TrieNode<I>>()
+                 + node.next.len() * mem::size_of::<(I, usize)>())
+            .sum()
+    }
+}
+
 /// A simple wrapper around the memchr crate's memmem implementation.
 ///
 /// The API this exposes mirrors the API of previous substring searchers that
@@ -1157,3 +1238,374 @@ impl Memmem {
 fn char_len_lossy(bytes: &[u8]) -> usize {
     String::from_utf8_lossy(bytes).chars().count()
 }
+
+/// Brzozowski derivatives.
+///
+/// The derivative of a repr by `i` matches every `w` such that `i` followed
+/// by `w` is matched by the repr. Folding derivatives over an input then
+/// checking `nullable` decides whether the whole input is matched. Zero-width
+/// assertions are treated as the empty word, since a derivative doesn't know
+/// its position in the input.
+#[unconst]
+impl<I: ~const Integral> Repr<I> {
+    /// Returns true if this repr matches the empty word.
+    pub const fn nullable(&self) -> bool {
+        match self {
+            Self::Zero(_) => true,
+            Self::One(seq) => seq.is_empty(),
+            Self::Interval(_) => false,
+            Self::Mul(lhs, rhs) | Self::Add(lhs, rhs) | Self::And(lhs, rhs)
+                => lhs.nullable() && rhs.nullable(),
+            Self::Or(lhs, rhs) => lhs.nullable() || rhs.nullable(),
+            Self::Exp(_) | Self::Lazy(_) => true,
+            Self::Not(repr) => !repr.nullable(),
+            Self::Cap(repr, _) => repr.nullable(),
+            Self::Div(lhs, rhs) => match &**lhs {
+                Self::Zero(Zero::Any) => rhs.nullable(),
+                Self::One(seq) => rhs.quotient(seq).nullable(),
+                _ => Self::quotients(lhs, rhs).iter().any(Self::nullable),
+            },
+        }
+    }
+
+    /// Returns the derivative of this repr by `i`, `Repr::void()` if no word
+    /// of it starts with `i`.
+    pub const fn derivative(&self, i: I) -> Self {
+        match self {
+            Self::Zero(_) => Self::void(),
+            Self::One(seq) => match seq.split_first() {
+                Some((first, [])) if *first == i => Self::zero(),
+                Some((first, rest)) if *first == i
+                    => Self::One(Seq::new(rest.iter().cloned())),
+                _ => Self::void()
+            },
+            Self::Interval(interval) => if interval.has(i) {
+                Self::zero()
+            } else {
+                Self::void()
+            },
+            Self::Mul(lhs, rhs) => {
+                let der = mul(lhs.derivative(i), *rhs.clone());
+                if lhs.nullable() {
+                    or(der, rhs.derivative(i))
+                } else {
+                    der
+                }
+            }
+            Self::Or(lhs, rhs) => or(lhs.derivative(i), rhs.derivative(i)),
+            Self::And(lhs, rhs) => {
+                match (lhs.derivative(i), rhs.derivative(i)) {
+                    (lhs, rhs) if lhs.is_void() || rhs.is_void() => Self::void(),
+                    (lhs, rhs) => lhs.and(rhs)
+                }
+            }
+            Self::Exp(repr) => mul(repr.derivative(i), self.clone()),
+            // Nor does laziness matter to them.
+            Self::Lazy(repr) => repr.clone().exp().derivative(i),
+            Self::Not(repr) => Self::Not(box repr.derivative(i)),
+            // Derivatives decide membership only, so groups are dropped.
+            Self::Cap(repr, _) => repr.derivative(i),
+            Self::Add(lhs, rhs) => or(
+                add(lhs.derivative(i), *rhs.clone()),
+                add(*lhs.clone(), rhs.derivative(i))
+            ),
+            Self::Div(lhs, rhs) => match &**lhs {
+                Self::Zero(Zero::Any) => rhs.derivative(i),
+                Self::One(seq) => rhs.quotient(seq).derivative(i),
+                _ => Self::any(Self::quotients(lhs, rhs).iter()
+                    .map(|repr| repr.derivative(i))),
+            },
+        }
+    }
+
+    /// Left quotient by `seq`, the derivative by each of its elements in
+    /// turn. This is what `seq ⊸ self` matches.
+    pub const fn quotient(&self, seq: &Seq<I>) -> Self {
+        seq.iter().fold(self.clone(), |repr, i| repr.derivative(*i))
+    }
+
+    /// The left quotients of `rhs` by the words of `lhs`, whose union is
+    /// what `lhs ⊸ rhs` matches, for any `lhs`.
+    ///
+    /// This walks the product of the automata of `complement` for both, as
+    /// `equivalent` does, and collects the derivative of `rhs` by each word
+    /// whose derivative of `lhs` is nullable. Pairs already seen, or with no
+    /// word of `lhs` left, aren't walked further, so it ends as there are
+    /// finitely many of them.
+    fn quotients(lhs: &Self, rhs: &Self) -> Vec<Self> {
+        let classes = lhs.clone().or(rhs.clone()).classes();
+        let mut seen = BTreeSet::new();
+        let mut found = BTreeSet::new();
+        let mut stack = vec![(lhs.clone().simplify(), rhs.clone().simplify())];
+        while let Some((lhs, rhs)) = stack.pop() {
+            if lhs.is_void() || seen.contains(&(lhs.clone(), rhs.clone())) {
+                continue;
+            }
+            if lhs.nullable() {
+                found.insert(rhs.clone());
+            }
+            for class in classes.iter() {
+                stack.push((lhs.derivative(class.0).simplify(),
+                            rhs.derivative(class.0).simplify()));
+            }
+            seen.insert((lhs, rhs));
+        }
+        found.into_iter().collect()
+    }
+
+    /// Rewrites this repr into a canonical form of the same language: voids
+    /// and empty words are dropped where they are units or absorbing, double
+    /// negations cancel, and nested `Or`s are flattened, deduplicated and
+    /// sorted. Sorting loses the priority between branches, and capture
+    /// groups and laziness are dropped, which only matters to engines
+    /// reporting positions.
+    ///
+    /// Derivatives taken in turn and simplified can only be one of finitely
+    /// many reprs, which is what keeps derivative-based matching bounded.
+    pub const fn simplify(self) -> Self {
+        match self {
+            Self::Mul(lhs, rhs) => mul(lhs.simplify(), rhs.simplify()),
+            Self::Or(lhs, rhs) => {
+                let mut branches: Vec<Self> = lhs.branches().into_iter()
+                    .chain(rhs.branches())
+                    .flat_map(|branch| branch.simplify().branches())
+                    .filter(|branch| !branch.is_void())
+                    .collect();
+                branches.sort();
+                branches.dedup();
+                if branches.is_empty() {
+                    Self::void()
+                } else {
+                    Self::any(branches.into_iter())
+                }
+            }
+            Self::And(lhs, rhs) => match (lhs.simplify(), rhs.simplify()) {
+                (lhs, rhs) if lhs.is_void() || rhs.is_void() => Self::void(),
+                (lhs, rhs) if lhs == rhs => lhs,
+                (lhs, rhs) => lhs.and(rhs)
+            },
+            Self::Exp(repr) => match repr.simplify() {
+                Self::Exp(repr) => Self::Exp(repr),
+                repr if repr.is_void() || repr == Self::zero() => Self::zero(),
+                repr => Self::Exp(box repr)
+            },
+            Self::Not(repr) => match repr.simplify() {
+                Self::Not(repr) => *repr,
+                repr => Self::Not(box repr)
+            },
+            Self::Lazy(repr) => Self::Exp(repr).simplify(),
+            Self::Add(lhs, rhs) => add(lhs.simplify(), rhs.simplify()),
+            Self::Div(lhs, rhs)
+                => Self::Div(box lhs.simplify(), box rhs.simplify()),
+            Self::Cap(repr, _) => repr.simplify(),
+            repr => repr
+        }
+    }
+
+    /// Returns the repr matching exactly the words this one doesn't.
+    ///
+    /// This builds the automaton whose states are the simplified derivatives
+    /// of this repr, stepping on classes of elements that no `One` or
+    /// `Interval` in it tells apart, flips which states accept, and turns it
+    /// back into a repr by eliminating states one by one. The classes cover
+    /// all of `I::MIN..=I::MAX`, which for `char` skips the surrogate gap
+    /// like any `Interval` does. As for derivatives, zero-width assertions
+    /// are taken as the empty word.
+    pub const fn complement(self) -> Self {
+        let classes = self.classes();
+        let mut states = vec![self.simplify()];
+        let mut ids = BTreeMap::new();
+        ids.insert(states[0].clone(), 0);
+        let mut transitions: Vec<Vec<(Interval<I>, usize)>> = Vec::new();
+        while transitions.len() < states.len() {
+            let state = states[transitions.len()].clone();
+            let mut edges = Vec::new();
+            for class in classes.iter() {
+                let der = state.derivative(class.0).simplify();
+                let j = match ids.get(&der) {
+                    Some(&j) => j,
+                    None => {
+                        ids.insert(der.clone(), states.len());
+                        states.push(der);
+                        states.len() - 1
+                    }
+                };
+                edges.push((*class, j));
+            }
+            transitions.push(edges);
+        }
+        let accepting: Vec<bool>
+            = states.iter().map(|state| !state.nullable()).collect();
+        eliminate(&transitions, &accepting)
+    }
+
+    /// Returns true if `self` and `other` match the same words, whatever
+    /// their structure: unlike `==`, it holds for `a | b` and `b | a`.
+    ///
+    /// This walks the product of the automata of `complement`, pairing the
+    /// simplified derivatives of both by the same words, and fails on the
+    /// first pair of which only one side is nullable. Pairs that are equal
+    /// aren't walked further. `Not` and `And` are supported, their
+    /// derivatives being the complement and product constructions. As for
+    /// derivatives, zero-width assertions are taken as the empty word and
+    /// groups are ignored.
+    pub const fn equivalent(&self, other: &Self) -> bool {
+        let classes = self.clone().or(other.clone()).classes();
+        let mut seen = BTreeSet::new();
+        let mut stack
+            = vec![(self.clone().simplify(), other.clone().simplify())];
+        while let Some((lhs, rhs)) = stack.pop() {
+            if lhs == rhs || seen.contains(&(lhs.clone(), rhs.clone())) {
+                continue;
+            }
+            if lhs.nullable() != rhs.nullable() {
+                return false;
+            }
+            for class in classes.iter() {
+                stack.push((lhs.derivative(class.0).simplify(),
+                            rhs.derivative(class.0).simplify()));
+            }
+            seen.insert((lhs, rhs));
+        }
+        true
+    }
+
+    /// Splits `I::MIN..=I::MAX` into intervals whose elements no `One` or
+    /// `Interval` of this repr tells apart, so that all of the elements of a
+    /// class have the same derivative.
+    fn classes(&self) -> Vec<Interval<I>> {
+        let mut bounds = Vec::new();
+        self.bounds(&mut bounds);
+        bounds.sort();
+        bounds.dedup();
+        let mut classes = Vec::new();
+        let mut from = I::MIN;
+        for bound in bounds {
+            if bound > from {
+                classes.push(Interval(from, bound.pred()));
+                from = bound;
+            }
+        }
+        classes.push(Interval(from, I::MAX));
+        classes
+    }
+
+    /// Pushes the elements at which a class starts, after the first.
+    fn bounds(&self, bounds: &mut Vec<I>) {
+        let mut push = |from: I, to: I| {
+            bounds.push(from);
+            if to != I::MAX {
+                bounds.push(to.succ());
+            }
+        };
+        match self {
+            Self::One(seq) => for i in seq.iter() {
+                push(*i, *i);
+            },
+            Self::Interval(Interval(from, to)) if from <= to => push(*from, *to),
+            Self::Mul(lhs, rhs) | Self::Or(lhs, rhs) | Self::Div(lhs, rhs)
+            | Self::Add(lhs, rhs) | Self::And(lhs, rhs) => {
+                lhs.bounds(bounds);
+                rhs.bounds(bounds);
+            }
+            Self::Exp(repr) | Self::Not(repr) | Self::Lazy(repr)
+            | Self::Cap(repr, _) => repr.bounds(bounds),
+            _ => {}
+        }
+    }
+}
+
+/// Turns the automaton starting at state 0 back into a repr, by
+/// eliminating every other state and rerouting the paths through it.
+#[unconst]
+const fn eliminate<I: ~const Integral>(
+    transitions: &[Vec<(Interval<I>, usize)>],
+    accepting: &[bool],
+) -> Repr<I> {
+    let n = transitions.len();
+    // `labels[p][q]` is what leads from `p` to `q`, the node `n` standing
+    // for acceptance.
+    let mut labels: Vec<Vec<Option<Repr<I>>>> = vec![vec![None; n + 1]; n + 1];
+    for (p, edges) in transitions.iter().enumerate() {
+        for &(class, q) in edges.iter() {
+            let class = if class.0 == class.1 {
+                Repr::one(class.0)
+            } else {
+                Repr::Interval(class)
+            };
+            labels[p][q] = Some(union(labels[p][q].take(), class));
+        }
+        if accepting[p] {
+            labels[p][n] = Some(Repr::zero());
+        }
+    }
+    for k in 1..n {
+        let repeat = labels[k][k].take().map(Repr::exp);
+        for p in 0..=n {
+            let into = match labels[p][k].take() {
+                Some(into) => into,
+                None => continue,
+            };
+            for q in 0..=n {
+                if let Some(out) = labels[k][q].clone() {
+                    let path = match repeat.clone() {
+                        Some(repeat) => mul(mul(into.clone(), repeat), out),
+                        None => mul(into.clone(), out),
+                    };
+                    labels[p][q] = Some(union(labels[p][q].take(), path));
+                }
+            }
+        }
+        for q in 0..=n {
+            labels[k][q] = None;
+        }
+    }
+    match (labels[0][0].take(), labels[0][n].take()) {
+        (_, None) => Repr::void(),
+        (None, Some(out)) => out,
+        (Some(repeat), Some(out)) => mul(repeat.exp(), out),
+    }
+}
+
+#[unconst]
+const fn union<I: ~const Integral>(lhs: Option<Repr<I>>, rhs: Repr<I>)
+    -> Repr<I>
+{
+    match lhs {
+        None => rhs,
+        Some(lhs) => or(lhs, rhs),
+    }
+}
+
+// The constructors below drop the void and the empty word where they are
+// units or absorbing, which keeps derivatives from growing at every step.
+
+#[unconst]
+const fn mul<I: ~const Integral>(lhs: Repr<I>, rhs: Repr<I>) -> Repr<I> {
+    match (lhs, rhs) {
+        (lhs, _) if lhs.is_void() => Repr::void(),
+        (_, rhs) if rhs.is_void() => Repr::void(),
+        (Repr::Zero(Zero::Any), repr) | (repr, Repr::Zero(Zero::Any)) => repr,
+        (lhs, rhs) => lhs.mul(rhs)
+    }
+}
+
+#[unconst]
+const fn or<I: ~const Integral>(lhs: Repr<I>, rhs: Repr<I>) -> Repr<I> {
+    match (lhs, rhs) {
+        (lhs, rhs) if lhs.is_void() => rhs,
+        (lhs, rhs) if rhs.is_void() => lhs,
+        (lhs, rhs) if lhs == rhs => lhs,
+        (lhs, rhs) => lhs.or(rhs)
+    }
+}
+
+#[unconst]
+const fn add<I: ~const Integral>(lhs: Repr<I>, rhs: Repr<I>) -> Repr<I> {
+    match (lhs, rhs) {
+        (lhs, _) if lhs.is_void() => Repr::void(),
+        (_, rhs) if rhs.is_void() => Repr::void(),
+        (Repr::Zero(Zero::Any), repr) | (repr, Repr::Zero(Zero::Any)) => repr,
+        (lhs, rhs) => lhs.add(rhs)
+    }
+}
@@ -0,0 +1,261 @@
+//! A lazy DFA over the instructions of a `Program`.
+//!
+//! Its states are sets of NFA threads. A state is built the first time a
+//! search reaches it and is cached along with its transitions, so that each
+//! element of the input usually costs one lookup instead of stepping every
+//! thread as the PikeVM does. The cache is bounded by the `dfa_size_limit`
+//! of the program: once it grows past it, it is cleared and the search
+//! quits, leaving the caller to fall back to an NFA engine.
+//!
+//! A DFA state doesn't know which of its threads has priority, so it can
+//! only tell whether there is a match and where the one ending first ends.
+//! That's all `is_match` and `shortest_match` need, while `find` uses it to
+//! reject inputs without a match before running an NFA for the span.
+//!
+//! Nor does a state remember the previous element, so `can_exec` turns down
+//! programs with assertions other than `StartText` and `EndText`, which are
+//! decided by the position alone. In terms of `Repr`, that is reprs with
+//! `Zero::StartLine`, `Zero::EndLine` or word boundaries. Regex sets are
+//! turned down too. `Not`, `And` and `Div` are compiled away before any
+//! engine runs, so they don't disqualify a repr by themselves.
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::mem::size_of;
+
+use crate::context::Context;
+use crate::program::ProgramCache;
+use crate::program::{Index, Inst, Program};
+use crate::repr::{Integral, Zero};
+
+/// The outcome of a search with the DFA.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Result<T> {
+    Match(T),
+    NoMatch,
+    /// The cache outgrew the size limit, so the search was abandoned.
+    Quit,
+}
+
+/// A thread of the NFA: an instruction, and for a `One` instruction how many
+/// elements of its literal run have been matched.
+type Thread = (Index, usize);
+
+/// The states built so far, shared between searches with the same program.
+#[derive(Clone, Debug)]
+pub struct Cache<I: Integral> {
+    states: Vec<State<I>>,
+    /// The id of each state by its threads and whether it matches.
+    ids: BTreeMap<(Vec<Thread>, bool), usize>,
+    /// Approximate heap usage of `states` and `ids` in bytes.
+    size: usize,
+    /// Threads that `closure` has already added.
+    seen: Vec<bool>,
+    stack: Vec<Index>,
+}
+
+#[derive(Clone, Debug)]
+struct State<I: Integral> {
+    /// The threads waiting for the next element, or at an `EndText`
+    /// assertion, sorted.
+    threads: Vec<Thread>,
+    /// Whether a match state was reached on the way to `threads`.
+    is_match: bool,
+    /// Transitions taken so far, by element.
+    next: BTreeMap<I, usize>,
+}
+
+impl<I: Integral> Cache<I> {
+    pub fn new(_prog: &Program<I>) -> Self {
+        Cache {
+            states: Vec::new(),
+            ids: BTreeMap::new(),
+            size: 0,
+            seen: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.states.clear();
+        self.ids.clear();
+        self.size = 0;
+    }
+}
+
+/// Returns true if the DFA can run `prog`.
+pub fn can_exec<I: Integral>(prog: &Program<I>) -> bool {
+    prog.matches.len() == 1
+        && !prog.is_reverse
+        && prog.iter().all(|inst| match *inst {
+            Inst::Zero { ref zero, .. }
+                => matches!(zero, Zero::StartText | Zero::EndText),
+            _ => true,
+        })
+}
+
+/// Searches `context` from `start` and returns the end of the match that
+/// ends first. If `anchored` is true, only matches starting at `start` are
+/// considered.
+pub fn shortest_match<I: Integral>(
+    prog: &Program<I>,
+    cache: &ProgramCache<I>,
+    context: &Context<I>,
+    start: usize,
+    anchored: bool,
+) -> Result<usize> {
+    let mut cache = cache.borrow_mut();
+    let cache = &mut cache.dfa;
+    if cache.seen.len() != prog.len() {
+        cache.clear();
+        cache.seen = vec![false; prog.len()];
+    }
+    let mut dfa = Dfa { prog, cache };
+    // Only a search from the start of the input can pass `StartText`, and
+    // from there on the unanchored start state is mixed into every state.
+    let unanchored = if anchored { None } else { Some(dfa.start(false)) };
+    let mut state = match (start == 0, unanchored) {
+        (false, Some(unanchored)) => unanchored,
+        (at_start, _) => dfa.start(at_start),
+    };
+    for at in start..context.len() {
+        if dfa.cache.states[state].is_match {
+            return Result::Match(at);
+        }
+        if anchored && dfa.cache.states[state].threads.is_empty() {
+            return Result::NoMatch;
+        }
+        if dfa.cache.size > prog.dfa_size_limit {
+            dfa.cache.clear();
+            return Result::Quit;
+        }
+        state = dfa.next(state, context[at], unanchored);
+    }
+    if dfa.cache.states[state].is_match || dfa.matches_at_end(state) {
+        Result::Match(context.len())
+    } else {
+        Result::NoMatch
+    }
+}
+
+struct Dfa<'a, I: Integral> {
+    prog: &'a Program<I>,
+    cache: &'a mut Cache<I>,
+}
+
+impl<'a, I: Integral> Dfa<'a, I> {
+    fn start(&mut self, at_start: bool) -> usize {
+        let mut threads = Vec::new();
+        let is_match = self.closure(&[0], at_start, false, &mut threads);
+        self.add(threads, is_match)
+    }
+
+    /// The state reached from `state` on `i`, with the threads of the
+    /// `unanchored` start state added, if any.
+    fn next(&mut self, state: usize, i: I, unanchored: Option<usize>) -> usize {
+        if let Some(&next) = self.cache.states[state].next.get(&i) {
+            return next;
+        }
+        let mut gotos = Vec::new();
+        let mut threads = Vec::new();
+        for &(ip, k) in self.cache.states[state].threads.iter() {
+            match self.prog[ip] {
+                Inst::One { goto, ref seq } if seq[k] == i => {
+                    if k + 1 == seq.len() {
+                        gotos.push(goto);
+                    } else {
+                        threads.push((ip, k + 1));
+                    }
+                }
+                Inst::Interval { goto, interval } if interval.has(i) => {
+                    gotos.push(goto);
+                }
+                _ => {}
+            }
+        }
+        let mut is_match = self.closure(&gotos, false, false, &mut threads);
+        if let Some(unanchored) = unanchored {
+            let unanchored = &self.cache.states[unanchored];
+            threads.extend_from_slice(&unanchored.threads);
+            is_match |= unanchored.is_match;
+        }
+        let next = self.add(threads, is_match);
+        self.cache.states[state].next.insert(i, next);
+        self.cache.size += size_of::<(I, usize)>();
+        next
+    }
+
+    /// Whether passing the `EndText` assertions of `state` at the end of the
+    /// input leads to a match.
+    fn matches_at_end(&mut self, state: usize) -> bool {
+        let gotos: Vec<Index> = self.cache.states[state].threads.iter()
+            .filter_map(|&(ip, _)| match self.prog[ip] {
+                Inst::Zero { goto, .. } => Some(goto),
+                _ => None,
+            })
+            .collect();
+        self.closure(&gotos, false, true, &mut Vec::new())
+    }
+
+    /// Follows the epsilon transitions from `ips`, pushing the threads that
+    /// wait for an element or for the end of the input onto `threads`, and
+    /// returns whether a match state was reached.
+    fn closure(
+        &mut self,
+        ips: &[Index],
+        at_start: bool,
+        at_end: bool,
+        threads: &mut Vec<Thread>,
+    ) -> bool {
+        let mut is_match = false;
+        let cache = &mut *self.cache;
+        for seen in cache.seen.iter_mut() {
+            *seen = false;
+        }
+        cache.stack.extend(ips.iter().rev());
+        while let Some(ip) = cache.stack.pop() {
+            if cache.seen[ip] {
+                continue;
+            }
+            cache.seen[ip] = true;
+            match self.prog[ip] {
+                Inst::Match(_) => is_match = true,
+                Inst::Split { goto1, goto2 } => {
+                    cache.stack.push(goto2);
+                    cache.stack.push(goto1);
+                }
+                Inst::Save { goto, .. } => cache.stack.push(goto),
+                Inst::Zero { goto, zero: Zero::StartText } => {
+                    if at_start {
+                        cache.stack.push(goto);
+                    }
+                }
+                Inst::Zero { goto, .. } => {
+                    if at_end {
+                        cache.stack.push(goto);
+                    } else {
+                        threads.push((ip, 0));
+                    }
+                }
+                _ => threads.push((ip, 0)),
+            }
+        }
+        is_match
+    }
+
+    /// Returns the id of the state with `threads`, adding it if it's new.
+    fn add(&mut self, mut threads: Vec<Thread>, is_match: bool) -> usize {
+        threads.sort();
+        threads.dedup();
+        let key = (threads, is_match);
+        if let Some(&id) = self.cache.ids.get(&key) {
+            return id;
+        }
+        let (threads, is_match) = key;
+        let id = self.cache.states.len();
+        self.cache.size += 2 * threads.len() * size_of::<Thread>()
+            + size_of::<State<I>>();
+        self.cache.ids.insert((threads.clone(), is_match), id);
+        self.cache.states.push(State { threads, is_match, next: BTreeMap::new() });
+        id
+    }
+}
@@ -0,0 +1,329 @@
+//! Rendering of `Repr<char>` in the conventional regex syntax read by
+//! `parse`.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::{self, Display, Write};
+
+use crate::interval::Interval;
+use crate::parse::negate;
+use crate::repr::{Integral, Repr, Zero};
+
+/// How tightly a rendered expression binds, loosest first. An operand that
+/// binds less tightly than its position requires is grouped.
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum Prec {
+    Alt,
+    Concat,
+    Repeat,
+}
+
+/// Renders the pattern that `Repr::try_from` parses back into a repr of the
+/// same language, adding only the groups precedence requires.
+///
+/// Some reprs have no such pattern: the empty language is rendered as
+/// `[^\s\S]`, line anchors and ASCII word boundaries as `(?m:^)`, `(?m:$)`,
+/// `(?-u:\b)` and `(?-u:\B)`, and `And`, `Add`, `Div` and `Not` as the
+/// operators `&`, `‖`, `/` and prefix `~`, none of which the parser reads.
+impl Display for Repr<char> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write(self, Prec::Alt, f)
+    }
+}
+
+fn write(repr: &Repr<char>, min: Prec, f: &mut fmt::Formatter<'_>)
+    -> fmt::Result
+{
+    if prec(repr) < min {
+        f.write_char('(')?;
+        write(repr, Prec::Alt, f)?;
+        return f.write_char(')');
+    }
+    if let Some(intervals) = class(repr) {
+        return write_class(intervals, f);
+    }
+    match repr {
+        Repr::Zero(zero) => f.write_str(match zero {
+            Zero::Any => "",
+            Zero::StartLine => "(?m:^)",
+            Zero::EndLine => "(?m:$)",
+            Zero::StartText => "^",
+            Zero::EndText => "$",
+            Zero::WordBoundary => r"\b",
+            Zero::NotWordBoundary => r"\B",
+            Zero::WordBoundaryAscii => r"(?-u:\b)",
+            Zero::NotWordBoundaryAscii => r"(?-u:\B)",
+        }),
+        Repr::One(seq) => {
+            for c in seq.iter() {
+                write_literal(*c, f)?;
+            }
+            Ok(())
+        }
+        // Non-empty intervals are classes.
+        Repr::Interval(_) => f.write_str(r"[^\s\S]"),
+        Repr::Or(lhs, rhs) if **rhs == Repr::zero() => {
+            write(lhs, Prec::Repeat, f)?;
+            f.write_char('?')
+        }
+        Repr::Mul(lhs, rhs) if **rhs == (**lhs).clone().exp() => {
+            write(lhs, Prec::Repeat, f)?;
+            f.write_char('+')
+        }
+        Repr::Exp(repr) => {
+            write(repr, Prec::Repeat, f)?;
+            f.write_char('*')
+        }
+        Repr::Lazy(repr) => {
+            write(repr, Prec::Repeat, f)?;
+            f.write_str("*?")
+        }
+        Repr::Mul(lhs, rhs) => {
+            write(lhs, Prec::Concat, f)?;
+            write(rhs, Prec::Concat, f)
+        }
+        Repr::Or(lhs, rhs) => write_infix(lhs, '|', rhs, Prec::Alt, f),
+        Repr::And(lhs, rhs) => write_infix(lhs, '&', rhs, Prec::Concat, f),
+        Repr::Add(lhs, rhs) => write_infix(lhs, '‖', rhs, Prec::Concat, f),
+        Repr::Div(lhs, rhs) => write_infix(lhs, '/', rhs, Prec::Concat, f),
+        Repr::Not(repr) => {
+            f.write_char('~')?;
+            write(repr, Prec::Repeat, f)
+        }
+        Repr::Cap(repr, name) => {
+            write!(f, "(?P<{}>", name)?;
+            write(repr, Prec::Alt, f)?;
+            f.write_char(')')
+        }
+    }
+}
+
+fn write_infix(
+    lhs: &Repr<char>,
+    op: char,
+    rhs: &Repr<char>,
+    min: Prec,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    write(lhs, min, f)?;
+    f.write_char(op)?;
+    write(rhs, min, f)
+}
+
+fn prec(repr: &Repr<char>) -> Prec {
+    if class(repr).is_some() {
+        return Prec::Repeat;
+    }
+    match repr {
+        Repr::Zero(Zero::Any) => Prec::Concat,
+        Repr::One(seq) if seq.len() > 1 => Prec::Concat,
+        Repr::Or(_, rhs) if **rhs == Repr::zero() => Prec::Repeat,
+        Repr::Mul(lhs, rhs) if **rhs == (**lhs).clone().exp()
+            => Prec::Repeat,
+        Repr::Mul(..) | Repr::Not(_) => Prec::Concat,
+        Repr::Cap(..) => Prec::Repeat,
+        Repr::Or(..) | Repr::And(..) | Repr::Add(..) | Repr::Div(..)
+            => Prec::Alt,
+        _ => Prec::Repeat,
+    }
+}
+
+/// The intervals of `repr` if it is a class, that is, a non-empty
+/// `Interval` or an `Or` of them and single characters.
+fn class(repr: &Repr<char>) -> Option<Vec<Interval<char>>> {
+    match repr {
+        Repr::Interval(Interval(from, to)) if from <= to
+            => Some(vec![Interval(*from, *to)]),
+        Repr::Or(lhs, rhs) => {
+            let mut intervals = class(lhs).or_else(|| single(lhs))?;
+            intervals.extend(class(rhs).or_else(|| single(rhs))?);
+            Some(intervals)
+        }
+        _ => None,
+    }
+}
+
+fn single(repr: &Repr<char>) -> Option<Vec<Interval<char>>> {
+    match repr {
+        Repr::One(seq) if seq.len() == 1 => Some(vec![Interval(seq[0], seq[0])]),
+        _ => None,
+    }
+}
+
+/// Writes `intervals` as `.`, as a class, or as a negated class if they
+/// reach `char::MAX`, so that negated classes read back as written.
+fn write_class(intervals: Vec<Interval<char>>, f: &mut fmt::Formatter<'_>)
+    -> fmt::Result
+{
+    if Repr::any(intervals.iter().map(|&i| Repr::Interval(i))) == Repr::dot() {
+        return f.write_char('.');
+    }
+    let negated = intervals.iter().any(|i| i.1 == <char as Integral>::MAX);
+    let intervals = if negated {
+        f.write_str("[^")?;
+        negate(intervals)
+    } else {
+        f.write_char('[')?;
+        intervals
+    };
+    for Interval(from, to) in intervals {
+        write_class_literal(from, f)?;
+        if from != to {
+            f.write_char('-')?;
+            write_class_literal(to, f)?;
+        }
+    }
+    f.write_char(']')
+}
+
+impl Interval<char> {
+    /// Returns the class `[lo-hi]` matching this interval, in the syntax
+    /// `parse` and `regex_syntax` read, with `\`, `[`, `]`, `^` and `-`
+    /// escaped in it. A single element is written as a literal instead,
+    /// escaped if it is a metacharacter. Control and other non-printable
+    /// characters are written as `\xHH` or `\x{H...}`. The empty interval
+    /// is `[^\s\S]`, as for `Display`.
+    pub fn to_class_string(&self) -> String {
+        let mut out = String::new();
+        // Writing into a `String` never fails.
+        match *self {
+            Interval(from, to) if to < from => out.push_str(r"[^\s\S]"),
+            Interval(from, to) if from == to
+                => write_literal(from, &mut out).unwrap(),
+            Interval(from, to) => {
+                out.push('[');
+                write_class_literal(from, &mut out).unwrap();
+                out.push('-');
+                write_class_literal(to, &mut out).unwrap();
+                out.push(']');
+            }
+        }
+        out
+    }
+}
+
+fn write_literal<W: Write>(c: char, f: &mut W) -> fmt::Result {
+    match c {
+        '\\' | '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{'
+        | '}' | '^' | '$' => {
+            f.write_char('\\')?;
+            f.write_char(c)
+        }
+        c => write_escaped(c, f),
+    }
+}
+
+fn write_class_literal<W: Write>(c: char, f: &mut W) -> fmt::Result {
+    match c {
+        '\\' | '[' | ']' | '^' | '-' => {
+            f.write_char('\\')?;
+            f.write_char(c)
+        }
+        c => write_escaped(c, f),
+    }
+}
+
+/// Writes `c`, escaping it as `\xHH` or `\x{H...}` if it is a control
+/// character or one `char::escape_debug` doesn't print as is.
+fn write_escaped<W: Write>(c: char, f: &mut W) -> fmt::Result {
+    match c {
+        '\n' => f.write_str(r"\n"),
+        '\r' => f.write_str(r"\r"),
+        '\t' => f.write_str(r"\t"),
+        c if c.is_control() || c.escape_debug().nth(1) == Some('u') => {
+            match c as u32 {
+                n @ 0..=0xFF => write!(f, r"\x{:02X}", n),
+                n => write!(f, r"\x{{{:X}}}", n),
+            }
+        }
+        c => f.write_char(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(pattern: &str) -> Repr<char> {
+        Repr::try_from(pattern).unwrap()
+    }
+
+    /// Every string over `a`, `b`, `.` and `\n` up to length 3.
+    fn words() -> Vec<String> {
+        let mut words = vec![String::new()];
+        let mut last = words.clone();
+        for _ in 0..3 {
+            last = last.iter()
+                .flat_map(|w| "ab.\n".chars().map(move |c| format!("{}{}", w, c)))
+                .collect();
+            words.extend(last.iter().cloned());
+        }
+        words
+    }
+
+    #[test]
+    fn round_trip() {
+        for pattern in [
+            "abc", "a|b|", r"\d+", "(ab)?$", "[a-c_]", r"[\]]", "^[^aeiou]*$",
+            ".", r"a\.b\*", "(a|bc)*d", r"[\w.-]+@(\w+\.)+", r"x(|y)",
+            r"[\n\-]", "(a*)*", "(?P<y>a|b)c", "(?P<y>a)+", "<.*?>",
+        ] {
+            let repr = parse(pattern);
+            assert_eq!(parse(&repr.to_string()), repr, "{}", pattern);
+        }
+        assert_eq!(parse("abc").to_string(), "abc");
+        assert_eq!(parse("(a|bc)*d").to_string(), "(a|bc)*d");
+        assert_eq!(parse("^[^aeiou]*$").to_string(), "^[^aeiou]*$");
+        assert_eq!(parse(r"\d+").to_string(), "[0-9]+");
+        assert_eq!(parse("a.b").to_string(), "a.b");
+    }
+
+    #[test]
+    fn round_trip_language() {
+        let a = Repr::one('a');
+        let b = Repr::one('b');
+        for repr in [
+            a.clone().mul(b.clone().or(Repr::zero())).exp(),
+            a.clone().or(b.clone()).repeat_range(1, Some(3)),
+            Repr::literal("ab").exp().mul(Repr::zero()).mul(Repr::dot()),
+            a.clone().or(b.clone().mul(a.clone())).or(Repr::zero()).plus(),
+            Repr::literal("a.").or(Repr::one('\n')).mul(b.exp()),
+            parse(r"[\w.]+b{2,4}"),
+            parse(r"(a|\.){1,}b?"),
+        ] {
+            let parsed = parse(&repr.to_string());
+            for word in words() {
+                assert_eq!(parsed.is_match_derivative(&word),
+                           repr.is_match_derivative(&word),
+                           "{} {:?}", repr, word);
+            }
+        }
+    }
+
+    #[test]
+    fn to_class_string() {
+        let class = |from, to| Interval(from, to).to_class_string();
+        assert_eq!(class('a', 'z'), "[a-z]");
+        assert_eq!(class(']', 'a'), r"[\]-a]");
+        assert_eq!(class('-', '/'), r"[\--/]");
+        assert_eq!(class('\\', '^'), r"[\\-\^]");
+        assert_eq!(class('\0', '\x1F'), r"[\x00-\x1F]");
+        assert_eq!(class('\n', ' '), r"[\n- ]");
+        assert_eq!(class('\u{7F}', '\u{200B}'), r"[\x7F-\x{200B}]");
+        assert_eq!(class(']', ']'), r"\]");
+        assert_eq!(class('-', '-'), "-");
+        assert_eq!(class('.', '.'), r"\.");
+        assert_eq!(class('\u{85}', '\u{85}'), r"\x85");
+        assert_eq!(class('z', 'a'), r"[^\s\S]");
+        // They read back as written.
+        for (from, to) in [(']', 'a'), ('-', '/'), ('\\', '^'), ('\0', '\x1F'),
+                           ('\u{7F}', '\u{200B}'), ('-', '-'), ('.', '.')] {
+            let expected = if from == to {
+                Repr::one(from)
+            } else {
+                Repr::Interval(Interval(from, to))
+            };
+            assert_eq!(parse(&class(from, to)), expected, "{:?}", (from, to));
+        }
+        assert_eq!(parse(r"\x{1F600}"), Repr::one('\u{1F600}'));
+    }
+}
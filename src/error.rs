@@ -0,0 +1,266 @@
+use core::fmt;
+
+/// An error that occurred while compiling a `Repr` into a `Program`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CompileError {
+    /// The pattern contains a Unicode word boundary (`\b` or `\B`), but the
+    /// Unicode tables for `\w` are not available because the `unicode-perl`
+    /// feature is disabled.
+    UnicodeWordUnavailable,
+    /// The pattern contains an `Add` (par) that can't be compiled. Only
+    /// `Add`s with the empty word on one side, or literals on both sides, are
+    /// supported.
+    UnsupportedAdd,
+    /// The pattern contains a `Div` (linear implication) whose left side
+    /// isn't a literal.
+    UnsupportedDiv,
+    /// The compiled program would exceed the size limit, in bytes, given
+    /// here.
+    TooLarge(usize),
+    /// The pattern has more capture groups than the limit given here.
+    TooManyCaptures(usize),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::UnicodeWordUnavailable => write!(
+                f,
+                "Unicode word boundaries are unavailable when the \
+                 unicode-perl feature is disabled"
+            ),
+            CompileError::UnsupportedAdd => write!(
+                f,
+                "`Add` is only supported with the empty word on one side or \
+                 literals on both sides"
+            ),
+            CompileError::UnsupportedDiv => write!(
+                f,
+                "`Div` is only supported with a literal on its left side"
+            ),
+            CompileError::TooLarge(limit) => write!(
+                f,
+                "compiled program exceeds the size limit of {} bytes",
+                limit
+            ),
+            CompileError::TooManyCaptures(limit) => write!(
+                f,
+                "pattern has more than {} capture groups",
+                limit
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CompileError {}
+
+/// An inconsistency found by `Program::validate`, which a correct compiler
+/// never produces.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProgramError {
+    /// The start of the program isn't an instruction.
+    StartOutOfRange(usize),
+    /// The instruction at `ip` goes to `goto`, which isn't an instruction.
+    GotoOutOfRange { ip: usize, goto: usize },
+    /// The `Match` at `ip` reports `slot`, which isn't less than the number
+    /// of `Match` instructions.
+    MatchOutOfRange { ip: usize, slot: usize },
+}
+
+impl fmt::Display for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramError::StartOutOfRange(start)
+                => write!(f, "start {} is out of range", start),
+            ProgramError::GotoOutOfRange { ip, goto }
+                => write!(f, "instruction {} goes to {}, out of range", ip,
+                          goto),
+            ProgramError::MatchOutOfRange { ip, slot }
+                => write!(f, "match {} at instruction {} is out of range",
+                          slot, ip),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProgramError {}
+
+/// An error that occurred while searching, which stopped the search before
+/// it could tell whether there is a match. Searches that compile their repr
+/// first also report why it couldn't be compiled, see `From<CompileError>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MatchError {
+    /// The repr contains an operator, named here, that the engines can't
+    /// run where it is, such as an `Add` of two reprs that aren't literals.
+    UnsupportedOperator(&'static str),
+    /// The compiled program would exceed the size limit, in bytes, given
+    /// here.
+    SizeLimitExceeded(usize),
+    /// The repr has more capture groups than the limit given here.
+    TooManyCaptures(usize),
+    /// An engine took more steps than `Options::step_limit` allows.
+    StepLimitExceeded,
+}
+
+impl From<CompileError> for MatchError {
+    fn from(error: CompileError) -> Self {
+        match error {
+            CompileError::UnicodeWordUnavailable
+                => MatchError::UnsupportedOperator("WordBoundary"),
+            CompileError::UnsupportedAdd
+                => MatchError::UnsupportedOperator("Add"),
+            CompileError::UnsupportedDiv
+                => MatchError::UnsupportedOperator("Div"),
+            CompileError::TooLarge(limit)
+                => MatchError::SizeLimitExceeded(limit),
+            CompileError::TooManyCaptures(limit)
+                => MatchError::TooManyCaptures(limit),
+        }
+    }
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchError::UnsupportedOperator(operator)
+                => write!(f, "`{}` is unsupported here", operator),
+            MatchError::SizeLimitExceeded(limit) => write!(
+                f,
+                "compiled program exceeds the size limit of {} bytes",
+                limit
+            ),
+            MatchError::TooManyCaptures(limit) => write!(
+                f,
+                "pattern has more than {} capture groups",
+                limit
+            ),
+            MatchError::StepLimitExceeded
+                => write!(f, "search exceeded the step limit"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MatchError {}
+
+/// An error that stopped `Repr::replace_all_write`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReplaceError {
+    /// The repr couldn't be run, see `MatchError`.
+    Match(MatchError),
+    /// Writing into the output failed.
+    Write(fmt::Error),
+}
+
+impl From<MatchError> for ReplaceError {
+    fn from(error: MatchError) -> Self {
+        ReplaceError::Match(error)
+    }
+}
+
+impl From<fmt::Error> for ReplaceError {
+    fn from(error: fmt::Error) -> Self {
+        ReplaceError::Write(error)
+    }
+}
+
+impl fmt::Display for ReplaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplaceError::Match(error) => write!(f, "{}", error),
+            ReplaceError::Write(_) => write!(f, "writing the output failed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReplaceError {}
+
+/// An `Ast` that doesn't mirror any `Repr`, found while converting it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AstError {
+    /// A node of the variant `variant` has `found` operands instead of
+    /// `expected`.
+    Arity { variant: &'static str, expected: usize, found: usize },
+    /// An interval whose start is after its end.
+    InvalidInterval(char, char),
+}
+
+impl fmt::Display for AstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AstError::Arity { variant, expected, found }
+                => write!(f, "`{}` takes {} operands, found {}", variant,
+                          expected, found),
+            AstError::InvalidInterval(from, to)
+                => write!(f, "interval {:?}..={:?} is empty", from, to),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AstError {}
+
+/// An error that occurred while parsing a pattern into a `Repr`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseError {
+    /// Byte offset in the pattern where the error was found.
+    pub offset: usize,
+    pub kind: ParseErrorKind,
+}
+
+/// The kind of a `ParseError`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseErrorKind {
+    /// A `(` without its `)`.
+    UnclosedGroup,
+    /// A `)` without its `(`.
+    UnopenedGroup,
+    /// A `[` without its `]`.
+    UnclosedClass,
+    /// A class that contains nothing, like `[^\s\S]`.
+    EmptyClass,
+    /// A class range whose start is after its end, like `[z-a]`, or whose
+    /// bound is a class, like `[a-\d]`.
+    InvalidRange,
+    /// A repetition operator with nothing to repeat.
+    RepetitionMissing,
+    /// A malformed `{n}`, `{n,}` or `{n,m}`, one with `n > m`, or one with
+    /// a count above 1000.
+    InvalidRepetition,
+    /// A `\` followed by nothing or by an unsupported escape.
+    InvalidEscape,
+    /// A `(?` not followed by `P<name>` or `<name>` with a valid name, or
+    /// an unsupported group flag.
+    InvalidGroupName,
+    /// A group nested in more than 250 others.
+    NestLimitExceeded,
+    /// A counted repetition that would expand to more than 2^20 nodes,
+    /// counting those of the counted repetitions nested in it.
+    SizeLimitExceeded,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self.kind {
+            ParseErrorKind::UnclosedGroup => "unclosed group",
+            ParseErrorKind::UnopenedGroup => "unopened group",
+            ParseErrorKind::UnclosedClass => "unclosed character class",
+            ParseErrorKind::EmptyClass => "empty character class",
+            ParseErrorKind::InvalidRange => "invalid character class range",
+            ParseErrorKind::RepetitionMissing
+                => "repetition operator missing expression",
+            ParseErrorKind::InvalidRepetition => "invalid repetition",
+            ParseErrorKind::InvalidEscape => "invalid escape sequence",
+            ParseErrorKind::InvalidGroupName => "invalid capture group name",
+            ParseErrorKind::NestLimitExceeded => "groups nested too deeply",
+            ParseErrorKind::SizeLimitExceeded
+                => "repetition expands to too large a pattern",
+        };
+        write!(f, "{} at byte {}", message, self.offset)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
@@ -5,14 +5,16 @@ use core::panic::AssertUnwindSafe;
 use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
 use unconst::unconst;
 
-use crate::{Repr, Integral, Seq, Partition, Context, pikevm};
+use crate::{Repr, Integral, Seq, Partition, Context, dfa, pikevm};
 use crate::backtrack;
 use crate::compile::Compiler;
+use crate::error::{CompileError, MatchError};
 use crate::derivative::{Literals, LiteralSearcher};
 use crate::options::Options;
-use crate::partition::Match;
+use crate::partition::{Captures, Match};
 use crate::pool::Pool;
-use crate::program::Program;
+use crate::program::{Program, ProgramCache, ProgramCacheInner};
+use crate::repr::Zero;
 
 // use super::pikevm;
 
@@ -218,6 +220,12 @@ struct ExecReadOnly<I: Integral> {
     /// N.B. It is not possibly to make this byte-based from the public API.
     /// It is only used for testing byte based programs in the NFA simulations.
     nfa: Program<I>,
+    /// The same as `nfa`, except compiled from the reversed regex.
+    ///
+    /// It is only compiled when the regex is anchored at the end but not at
+    /// the start, where scanning backward from the end of the input can only
+    /// ever try a single starting position, see `choose_match_type`.
+    nfa_reverse: Option<Program<I>>,
     /// A set of suffix literals extracted from the regex.
     ///
     /// Prefix literals are stored on the `Program`, since they are used inside
@@ -256,7 +264,7 @@ impl<I: ~const Integral> Exec<I> {
     /// let set = RegexSet::new(&[r"\w+", r"\d+"]).unwrap();
     /// assert!(set.is_match("foo"));
     /// ```
-    pub const fn new(repr: Repr<I>) -> Exec<I> {
+    pub const fn new(repr: Repr<I>) -> Result<Exec<I>, CompileError> {
         Options::new(repr).build()
     }
 
@@ -320,7 +328,9 @@ impl<I: ~const Integral> Exec<I> {
     /// context into consideration. For example, the `\A` anchor can only
     /// match when `start == 0`.
     pub const fn is_match_at(&self, context: &Context<I>, start: usize) -> bool {
-        if !self.is_anchor_end_match(context) {
+        if !self.is_anchor_end_match(context)
+            || !self.has_required_literals(context, start)
+        {
             return false;
         }
         // We need to do this dance because shortest_match relies on the NFA
@@ -331,6 +341,13 @@ impl<I: ~const Integral> Exec<I> {
             MatchType::Seq(ty)
                 => self.find_literals(ty, context, start).is_some(),
             MatchType::Nfa => self.match_nfa(context, start),
+            MatchType::NfaAnchoredReverse
+                => self.match_nfa_reverse(context, start),
+            MatchType::Dfa => match self.shortest_dfa(context, start) {
+                dfa::Result::Match(_) => true,
+                dfa::Result::NoMatch => false,
+                dfa::Result::Quit => self.match_nfa(context, start),
+            },
             MatchType::Nothing => false,
         }
     }
@@ -347,6 +364,16 @@ impl<I: ~const Integral> Exec<I> {
         true
     }
 
+    /// Returns false if some element required by every match is missing
+    /// from `context` after `start`, in which case there can't be a match.
+    #[cfg_attr(feature = "perf-inline", inline(always))]
+    pub const fn has_required_literals(&self, context: &Context<I>, start: usize)
+        -> bool
+    {
+        let context = &context[start..];
+        self.ro.nfa.required.iter().all(|i| context.contains(i))
+    }
+
     /// Finds the leftmost-first match using only literal search.
     #[cfg(feature = "perf-literal")]
     #[cfg_attr(feature = "perf-inline", inline(always))]
@@ -395,6 +422,32 @@ impl<I: ~const Integral> Exec<I> {
         -> bool
     {
         self.exec_nfa(
+            &self.ro.nfa,
+            &mut [false],
+            true,
+            false,
+            context,
+            start,
+            context.len(),
+        )
+    }
+
+    /// Executes the reverse NFA, walking the input in place from the end of
+    /// `context` back to `start`. The assertions at `start` still look at
+    /// the element before it, as they do in a forward search.
+    ///
+    /// This is only correct when the regex is anchored at the end, since
+    /// then the reverse program is anchored at the start and a single run
+    /// decides whether there is a match. Without a reverse program, runs
+    /// the forward NFA instead.
+    pub const fn match_nfa_reverse(&self, context: &Context<I>, start: usize)
+        -> bool
+    {
+        let Some(prog) = &self.ro.nfa_reverse else {
+            return self.match_nfa(context, start);
+        };
+        self.exec_nfa(
+            prog,
             &mut [false],
             true,
             false,
@@ -406,6 +459,7 @@ impl<I: ~const Integral> Exec<I> {
 
     pub const fn exec_nfa(
         &self,
+        prog: &Program<I>,
         matches: &mut [bool],
         quit_after_match: bool,
         quit_after_match_with_pos: bool,
@@ -413,7 +467,7 @@ impl<I: ~const Integral> Exec<I> {
         start: usize,
         end: usize,
     ) -> bool {
-        let bt = if backtrack::should_exec(self.ro.nfa.len(), context.len()) {
+        let bt = if backtrack::should_exec(prog.len(), context.len()) {
             true
         } else {
             false
@@ -423,6 +477,7 @@ impl<I: ~const Integral> Exec<I> {
         // to run an NFA, then use the PikeVM.
         if quit_after_match_with_pos || false {
             self.exec_pikevm(
+                prog,
                 matches,
                 quit_after_match,
                 context,
@@ -430,13 +485,14 @@ impl<I: ~const Integral> Exec<I> {
                 end,
             )
         } else {
-            self.exec_backtrack(matches, context, start, end)
+            self.exec_backtrack(prog, matches, context, start, end)
         }
     }
 
     /// Always run the NFA algorithm.
     pub const fn exec_pikevm(
         &self,
+        prog: &Program<I>,
         matches: &mut [bool],
         quit_after_match: bool,
         context: &Context<I>,
@@ -444,9 +500,10 @@ impl<I: ~const Integral> Exec<I> {
         end: usize,
     ) -> bool {
         pikevm::Fsm::exec(
-            &self.ro.nfa,
+            prog,
             self.cache.value(),
             matches,
+            &mut [],
             quit_after_match,
             context,
             start,
@@ -457,13 +514,14 @@ impl<I: ~const Integral> Exec<I> {
     /// Always runs the NFA using bounded backtracking.
     pub const fn exec_backtrack(
         &self,
+        prog: &Program<I>,
         matches: &mut [bool],
         context: &Context<I>,
         start: usize,
         end: usize,
     ) -> bool {
         backtrack::Bounded::exec(
-            &self.ro.nfa,
+            prog,
             self.cache.value(),
             matches,
             context,
@@ -510,7 +568,9 @@ impl<I: ~const Integral> Exec<I> {
     pub const fn shortest_match_at(&self, context: &Context<I>, start: usize)
         -> Option<usize>
     {
-        if !self.is_anchor_end_match(context) {
+        if !self.is_anchor_end_match(context)
+            || !self.has_required_literals(context, start)
+        {
             return None;
         }
         match self.ro.match_type {
@@ -518,13 +578,45 @@ impl<I: ~const Integral> Exec<I> {
                 self.find_literals(ty, context, start).map(|(_, e)| e)
             }
             MatchType::Nfa => self.shortest_nfa(context, start),
+            MatchType::Dfa => match self.shortest_dfa(context, start) {
+                dfa::Result::Match(end) => Some(end),
+                dfa::Result::NoMatch => None,
+                dfa::Result::Quit => self.shortest_nfa(context, start),
+            },
+            // A reverse match always ends at the end of the input.
+            MatchType::NfaAnchoredReverse => {
+                if self.match_nfa_reverse(context, start) {
+                    Some(context.len())
+                } else {
+                    None
+                }
+            }
             MatchType::Nothing => None,
         }
     }
 
-    /// Finds the shortest match using an NFA.
-    const fn shortest_nfa(&self, context: &Context<I>, start: usize) -> Option<usize> {
-        None
+    /// Finds where the match ending first ends using the lazy DFA.
+    fn shortest_dfa(&self, context: &Context<I>, start: usize)
+        -> dfa::Result<usize>
+    {
+        dfa::shortest_match(&self.ro.nfa, self.cache.value(), context, start,
+                            false)
+    }
+
+    /// Finds where the match ending first ends using the PikeVM, which
+    /// stops at the first `Match` it reaches, whatever the priority of the
+    /// thread. The backtracker explores one start at a time, so it can't.
+    const fn shortest_nfa(&self, context: &Context<I>, start: usize)
+        -> Option<usize>
+    {
+        let mut slots = [None, None];
+        if pikevm::Fsm::exec(&self.ro.nfa, self.cache.value(), &mut [],
+                             &mut slots, true, context, start, context.len())
+        {
+            slots[1]
+        } else {
+            None
+        }
     }
 
     /// Returns the start and end byte range of the leftmost-first match in
@@ -567,23 +659,165 @@ impl<I: ~const Integral> Exec<I> {
     pub const fn find_at<'c>(&self, context: &'c Context<I>, start: usize)
         -> Option<Match<'c, I>>
     {
-        if !self.is_anchor_end_match(context) {
+        if !self.is_anchor_end_match(context)
+            || !self.has_required_literals(context, start)
+        {
+            return None;
+        }
+        let nfa = &self.ro.nfa;
+        if nfa.is_anchored_start && start > 0 {
+            return None;
+        }
+        // Only try the starts of lines rather than have an engine probe
+        // every position in between.
+        if nfa.is_line_anchored_start && !nfa.is_anchored_start
+            && !matches!(self.ro.match_type, MatchType::Nothing)
+        {
+            let mut at = start;
+            while let Some(start) = self.line_start(context, at) {
+                if let Some((s, e)) = self.find_nfa(context, start, true) {
+                    return Some(Match::new(context, s, e));
+                }
+                at = start + 1;
+            }
             return None;
         }
         let output = match self.ro.match_type {
             #[cfg(feature = "perf-literal")]
             MatchType::Seq(ty) => self.find_literals(ty, context, start),
-            MatchType::Nfa => self.find_nfa(context, start),
+            MatchType::Nfa | MatchType::NfaAnchoredReverse
+                => self.find_nfa(context, start, false),
+            // The DFA only rules out inputs without a match.
+            MatchType::Dfa => match self.shortest_dfa(context, start) {
+                dfa::Result::NoMatch => None,
+                dfa::Result::Match(_) | dfa::Result::Quit
+                    => self.find_nfa(context, start, false),
+            },
             MatchType::Nothing => None,
         };
         output.map(|(s, e)| Match::new(context, s, e))
     }
 
-    /// Like find, but executes an NFA engine.
-    fn find_nfa(&self, context: &Context<I>, start: usize)
+    /// Like `find_at`, but if an engine takes more steps than
+    /// `Options::step_limit` allows, returns
+    /// `MatchError::StepLimitExceeded` rather than no match.
+    pub fn try_find_at<'c>(&self, context: &'c Context<I>, start: usize)
+        -> Result<Option<Match<'c, I>>, MatchError>
+    {
+        self.limited(|| self.find_at(context, start))
+    }
+
+    /// Like `is_match_at`, with the step limit reported as for
+    /// `try_find_at`.
+    pub fn try_is_match_at(&self, context: &Context<I>, start: usize)
+        -> Result<bool, MatchError>
+    {
+        self.limited(|| self.is_match_at(context, start))
+    }
+
+    /// Like `find_anchored_at`, with the step limit reported as for
+    /// `try_find_at`.
+    pub fn try_find_anchored_at<'c>(&self, context: &'c Context<I>,
+                                    start: usize)
+        -> Result<Option<Match<'c, I>>, MatchError>
+    {
+        self.limited(|| self.find_anchored_at(context, start))
+    }
+
+    /// Runs `search`, and fails if an engine went over the step limit
+    /// during it.
+    fn limited<T>(&self, search: impl FnOnce() -> T) -> Result<T, MatchError> {
+        self.cache.value().borrow_mut().steps.exceeded = false;
+        let output = search();
+        if self.cache.value().borrow().steps.exceeded {
+            Err(MatchError::StepLimitExceeded)
+        } else {
+            Ok(output)
+        }
+    }
+
+    /// Returns the first position from `at` on that starts a line, the
+    /// only positions where a program with `is_line_anchored_start` can
+    /// match.
+    fn line_start(&self, context: &Context<I>, at: usize) -> Option<usize> {
+        let nfa = &self.ro.nfa;
+        (at..=context.len()).find(|&at| context.is_empty_match(
+            at, &Zero::StartLine, nfa.line_terminator, nfa.crlf
+        ))
+    }
+
+    /// Like `find_at`, but the match must start exactly at `start`, as if
+    /// the regex began with an anchor there. This lets a tokenizer advance
+    /// a cursor through `context` without skipping anything.
+    ///
+    /// The literal matchers only search, so this always runs an NFA engine.
+    pub const fn find_anchored_at<'c>(&self, context: &'c Context<I>,
+                                      start: usize)
+        -> Option<Match<'c, I>>
+    {
+        if !self.is_anchor_end_match(context)
+            || !self.has_required_literals(context, start)
+        {
+            return None;
+        }
+        let output = match self.ro.match_type {
+            MatchType::Nothing => None,
+            _ => self.find_nfa(context, start, true),
+        };
+        output.map(|(s, e)| Match::new(context, s, e))
+    }
+
+    /// Returns the spans of the groups of the leftmost-first match in
+    /// `context` starting the search at `start`.
+    ///
+    /// The literal matchers and the DFA don't record groups, so once the
+    /// match is found an NFA engine runs again anchored at its start, to
+    /// the same match.
+    pub fn captures_at<'c>(&self, context: &'c Context<I>, start: usize)
+        -> Option<Captures<'c, I>>
+    {
+        let m = self.find_at(context, start)?;
+        let nfa = &self.ro.nfa;
+        let mut slots = vec![None; nfa.slots()];
+        let matched = if backtrack::should_exec(nfa.len(), context.len()) {
+            backtrack::Bounded::captures(nfa, self.cache.value(), &mut slots,
+                                         context, m.start(), context.len(),
+                                         true)
+        } else {
+            pikevm::Fsm::exec_anchored(nfa, self.cache.value(), &mut [],
+                                       &mut slots, false, context, m.start(),
+                                       context.len(), true)
+        };
+        if !matched {
+            return None;
+        }
+        Some(Captures::new(context, slots, nfa.captures.clone()))
+    }
+
+    /// Like find, but executes an NFA engine. If `anchored` is true, the
+    /// match must start at `start`.
+    fn find_nfa(&self, context: &Context<I>, start: usize, anchored: bool)
         -> Option<(usize, usize)>
     {
-        None
+        if backtrack::should_exec(self.ro.nfa.len(), context.len()) {
+            backtrack::Bounded::find(
+                &self.ro.nfa,
+                self.cache.value(),
+                context,
+                start,
+                context.len(),
+                anchored,
+            )
+        } else {
+            pikevm::Fsm::find(
+                &self.ro.nfa,
+                self.cache.value(),
+                context,
+                start,
+                context.len(),
+                anchored,
+            )
+        }
     }
 
     /// Returns an iterator for each successive non-overlapping match in
@@ -699,7 +933,8 @@ impl<I: ~const Integral> Exec<I> {
                 matches[0] = self.find_literals(ty, context, start).is_some();
                 matches[0]
             }
-            Nfa => self.exec_nfa(
+            Nfa | NfaAnchoredReverse | Dfa => self.exec_nfa(
+                &self.ro.nfa,
                 matches,
                 false,
                 false,
@@ -804,12 +1039,42 @@ impl<I: ~const Integral> ExecBuilder<I> {
         }
     }
 
+    /// A compiler configured by `options`, for both the forward and the
+    /// reverse program.
+    fn compiler(options: &Options<I>) -> Compiler<I> {
+        Compiler::new()
+            .size_limit(options.size_limit)
+            .dfa_size_limit(options.dfa_size_limit)
+            .max_captures(options.max_captures)
+            .captures(options.captures)
+            .step_limit(options.step_limit)
+            .longest_match(options.longest_match)
+            .ascii(options.ascii)
+            .line_terminator(options.line_terminator)
+            .crlf(options.crlf)
+    }
+
     /// Build an executor that can run a regular expression.
-    pub fn build(self) -> Exec<I> {
+    pub fn build(self) -> Result<Exec<I>, CompileError> {
         let parsed = self.parse();
-        let mut nfa = Compiler::new()
-            .size_limit(self.options.size_limit)
-            .compile(&parsed.reprs);
+        let compiler = Self::compiler(&self.options);
+        let mut nfa = compiler.clone().compile(&parsed.reprs)?;
+
+        // Only a regex anchored at the end but not at the start is ever run
+        // in reverse. A `Div` can't be reversed, so such a regex is run
+        // forward instead.
+        let reversible = nfa.is_anchored_end && !nfa.is_anchored_start
+            && !matches!(self.match_type, Some(MatchType::Nfa));
+        let nfa_reverse = if reversible {
+            let reverse = compiler.reverse(true).compile(&parsed.reprs);
+            match reverse {
+                Ok(prog) => Some(prog),
+                Err(CompileError::UnsupportedDiv) => None,
+                Err(error) => return Err(error),
+            }
+        } else {
+            None
+        };
 
         #[cfg(feature = "perf-literal")]
         let ac = self.build_aho_corasick(&parsed);
@@ -817,6 +1082,7 @@ impl<I: ~const Integral> ExecBuilder<I> {
 
         let mut ro = ExecReadOnly {
             nfa,
+            nfa_reverse,
             suffixes: LiteralSearcher::suffixes(parsed.suffixes),
             #[cfg(feature = "perf-literal")]
             ac,
@@ -826,7 +1092,7 @@ impl<I: ~const Integral> ExecBuilder<I> {
 
         let ro = Arc::new(ro);
         let pool = ExecReadOnly::new_pool(&ro);
-        Exec { ro, pool }
+        Ok(Exec { ro, pool })
     }
 
     #[cfg(feature = "perf-literal")]
@@ -883,6 +1149,13 @@ impl<I: Integral> ExecReadOnly<I> {
         if let Some(literalty) = self.choose_literal_match_type() {
             return literalty;
         }
+        // Anchored at the end only, so a single backward run suffices.
+        if self.nfa_reverse.is_some() {
+            return MatchType::NfaAnchoredReverse;
+        }
+        if dfa::can_exec(&self.nfa) {
+            return MatchType::Dfa;
+        }
         // We're so totally hosed.
         MatchType::Nfa
     }
@@ -905,6 +1178,10 @@ impl<I: Integral> ExecReadOnly<I> {
             if ro.res.len() != 1 {
                 return None;
             }
+            // Literal searchers report the first literal in priority order.
+            if ro.nfa.longest_match {
+                return None;
+            }
             if ro.ac.is_some() {
                 return Some(MatchType::Seq(
                     MatchSeqType::AhoCorasick,
@@ -936,7 +1213,7 @@ impl<I: Integral> ExecReadOnly<I> {
     fn new_pool(ro: &Arc<ExecReadOnly<I>>) -> Box<Pool<ProgramCache<I>>> {
         let ro = ro.clone();
         Box::new(Pool::new(Box::new(move || {
-            AssertUnwindSafe(RefCell::new(ProgramCacheInner::new(&ro)))
+            AssertUnwindSafe(RefCell::new(ProgramCacheInner::new(&ro.nfa)))
         })))
     }
 }
@@ -949,6 +1226,13 @@ enum MatchType {
     Seq(MatchSeqType),
     /// An NFA variant.
     Nfa,
+    /// An NFA run backward from the end of the input. This is only used
+    /// when the regex is anchored at the end but not at the start.
+    NfaAnchoredReverse,
+    /// The lazy DFA, which decides whether there is a match by itself but
+    /// leaves reporting its span to an NFA. See `dfa::can_exec` for which
+    /// programs it runs.
+    Dfa,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -965,30 +1249,6 @@ enum MatchSeqType {
     AhoCorasick,
 }
 
-/// `ProgramCache` maintains reusable allocations for each matching engine
-/// available to a particular program.
-///
-/// We declare this as unwind safe since it's a cache that's only used for
-/// performance purposes. If a panic occurs, it is (or should be) always safe
-/// to continue using the same regex object.
-pub type ProgramCache<I: Integral>
-    = AssertUnwindSafe<RefCell<ProgramCacheInner<I>>>;
-
-#[derive(Debug)]
-pub struct ProgramCacheInner<I: Integral> {
-    pub pikevm: pikevm::Cache,
-    pub backtrack: backtrack::Cache<I>,
-}
-
-impl<I: Integral> ProgramCacheInner<I> {
-    fn new(ro: &ExecReadOnly<I>) -> Self {
-        ProgramCacheInner {
-            pikevm: pikevm::Cache::new(&ro.nfa),
-            backtrack: backtrack::Cache::new(&ro.nfa),
-        }
-    }
-}
-
 // /// Alternation literals checks if the given HIR is a simple alternation of
 // /// literals, and if so, returns them. Otherwise, this returns None.
 // #[cfg(feature = "perf-literal")]
@@ -1026,3 +1286,445 @@ impl<I: Integral> ProgramCacheInner<I> {
 
 //     Some(constants)
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interval::Interval;
+    use crate::partition::Cursor;
+    use crate::program::Inst;
+
+    /// Inputs over a small alphabet drawn from a fixed-seed linear
+    /// congruential generator, so that failures are reproducible.
+    fn inputs(n: usize) -> Vec<Context<char>> {
+        let mut seed = 0x2545_F491_4F6C_DD1D_u64;
+        (0..n).map(|_| {
+            seed = seed.wrapping_mul(6_364_136_223_846_793_005)
+                       .wrapping_add(1_442_695_040_888_963_407);
+            let len = (seed >> 59) as usize;
+            (0..len)
+                .map(|i| ['a', 'b', 'c', 'x'][(seed >> (2 * i)) as usize & 3])
+                .collect::<Vec<_>>()
+                .into()
+        }).collect()
+    }
+
+    #[test]
+    fn reverse_agrees_with_forward() {
+        let tail = Repr::Interval(Interval('a', 'c')).exp()
+            .mul(Repr::Zero(Zero::EndText));
+        let reprs = [
+            Repr::one('a').mul(tail.clone()),
+            // Looks at the element before `start`.
+            Repr::Zero(Zero::WordBoundaryAscii).mul(Repr::literal("ab"))
+                .mul(tail),
+        ];
+        for repr in reprs {
+            let exec = Exec::new(repr).unwrap();
+            assert!(matches!(exec.ro.match_type,
+                             MatchType::NfaAnchoredReverse));
+            for context in inputs(200) {
+                for start in 0..=context.len() {
+                    assert_eq!(
+                        exec.match_nfa_reverse(&context, start),
+                        exec.match_nfa(&context, start),
+                        "{:?} {}", context, start
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reverse_only_when_anchored_at_the_end() {
+        let exec = Exec::new(Repr::literal("ab").exp()).unwrap();
+        assert!(exec.ro.nfa_reverse.is_none());
+        let exec = Exec::new(Repr::literal("ab").exp().anchored()).unwrap();
+        assert!(exec.ro.nfa_reverse.is_none());
+        // A `Div` can't be reversed, so it is run forward.
+        let repr = Repr::literal("ab").div(Repr::literal("abc"))
+            .anchored_end();
+        let exec = Exec::new(repr).unwrap();
+        assert!(exec.ro.nfa_reverse.is_none());
+        assert!(exec.is_match_at(&Context::from("xc"), 0));
+        assert!(!exec.is_match_at(&Context::from("cx"), 0));
+    }
+
+    #[test]
+    fn tiny_dfa_size_limit() {
+        let repr = Repr::one('a').mul(Repr::Interval(Interval('a', 'c')).exp());
+        let exec = Exec::new(repr.clone()).unwrap();
+        let tiny = Options { dfa_size_limit: 0, ..Options::new(repr) }
+            .build()
+            .unwrap();
+        assert_eq!(tiny.ro.nfa.dfa_size_limit, 0);
+        for context in inputs(200) {
+            assert_eq!(tiny.is_match(&context), exec.is_match(&context));
+        }
+    }
+
+    #[test]
+    fn shortest_match_past_dfa_size_limit() {
+        let abc = Repr::Interval(Interval('a', 'c'));
+        // Every match has the same length, so the one ending first is the
+        // leftmost-first one. The class is too big for the literals to
+        // cover, so the DFA is chosen.
+        let lower = Repr::Interval(Interval('a', 'z'));
+        let fixed = [
+            Repr::one('a').mul(lower.clone()).mul(Repr::one('x')),
+            lower.clone().mul(lower),
+        ];
+        for repr in fixed {
+            let tiny = Options { dfa_size_limit: 0, ..Options::new(repr) }
+                .build()
+                .unwrap();
+            assert!(matches!(tiny.ro.match_type, MatchType::Dfa));
+            for context in inputs(200) {
+                assert_eq!(tiny.shortest_match(&context),
+                           tiny.find(&context).map(|m| m.end()),
+                           "{:?}", context);
+            }
+        }
+        // The match ending first ends right after the leftmost `a`.
+        let repr = Repr::one('a').mul(abc.exp());
+        let tiny = Options { dfa_size_limit: 0, ..Options::new(repr) }
+            .build()
+            .unwrap();
+        for context in inputs(200) {
+            assert_eq!(tiny.shortest_match(&context),
+                       tiny.find(&context).map(|m| m.start() + 1),
+                       "{:?}", context);
+        }
+    }
+
+    #[test]
+    fn unicode_word_boundary_sets_flag() {
+        let repr = Repr::Zero(Zero::WordBoundary).mul(Repr::one('a'));
+        let result = Exec::new(repr);
+        if cfg!(feature = "unicode-perl") {
+            assert!(result.unwrap().ro.nfa.has_unicode_word_boundary);
+        } else {
+            assert_eq!(result.unwrap_err(), CompileError::UnicodeWordUnavailable);
+        }
+    }
+
+    #[test]
+    fn required_literals() {
+        let word = Repr::Interval(Interval('a', 'z'));
+        let repr = word.clone() * .. & '@' & word * ..;
+        let exec = Exec::new(repr).unwrap();
+        assert_eq!(exec.ro.nfa.required_literals(), vec!['@']);
+        assert!(!exec.has_required_literals(&"ab.cd".into(), 0));
+        assert!(exec.is_match(&"ab@cd".into()));
+        assert!(!exec.is_match(&"ab.cd".into()));
+    }
+
+    #[test]
+    fn pikevm_agrees_with_backtrack() {
+        let abc = Repr::Interval(Interval('a', 'c'));
+        let reprs = [
+            Repr::one('a').mul(abc.clone().exp()).mul(Repr::one('x')),
+            Repr::one('b').or(Repr::one('a').mul(Repr::one('b'))),
+            abc.clone().mul(abc.clone()).or(Repr::one('x')).exp(),
+            Repr::Zero(Zero::StartText).mul(Repr::one('a').exp()),
+        ];
+        for repr in reprs {
+            let exec = Exec::new(repr.clone()).unwrap();
+            for context in inputs(200) {
+                for (start, anchored) in (0..=context.len())
+                    .flat_map(|start| [(start, false), (start, true)])
+                {
+                    assert_eq!(
+                        pikevm::Fsm::find(&exec.ro.nfa, exec.cache.value(),
+                                          &context, start, context.len(),
+                                          anchored),
+                        backtrack::Bounded::find(&exec.ro.nfa,
+                                                 exec.cache.value(),
+                                                 &context, start,
+                                                 context.len(), anchored),
+                        "{:?} {:?} {} {}", repr, context, start, anchored
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn longest_match() {
+        let a = Repr::one('a');
+        let b = Repr::one('b');
+        let cases = [
+            // (repr, input, leftmost-first, leftmost-longest)
+            (a.clone().or(a.clone().mul(b.clone())), "ab", (0, 1), (0, 2)),
+            (Repr::one('x').mul(a.clone().or(a.clone().mul(b.clone()))),
+             "xab", (0, 2), (0, 3)),
+            (a.clone().or(a.clone().mul(a.clone())).exp().mul(b.clone())
+                .or(a.clone()),
+             "aab", (0, 3), (0, 3)),
+            (Repr::literal("ab").or(Repr::literal("bcd")), "abcd",
+             (0, 2), (0, 2)),
+            (a.clone().or(Repr::literal("aaa")).or(Repr::literal("aa")),
+             "aaaa", (0, 1), (0, 3)),
+        ];
+        for (repr, input, first, longest) in cases {
+            let context = Context::from(input);
+            for (yes, expected) in [(false, first), (true, longest)] {
+                let mut options = Options::new(repr.clone());
+                options.longest_match = yes;
+                let exec = options.build().unwrap();
+                let nfa = &exec.ro.nfa;
+                assert_eq!(
+                    backtrack::Bounded::find(nfa, exec.cache.value(),
+                                             &context, 0, context.len(),
+                                             false),
+                    Some(expected), "{:?} {} {}", repr, input, yes
+                );
+                assert_eq!(
+                    pikevm::Fsm::find(nfa, exec.cache.value(), &context, 0,
+                                      context.len(), false),
+                    Some(expected), "{:?} {} {}", repr, input, yes
+                );
+                let m = exec.find(&context).unwrap();
+                assert_eq!((m.start(), m.end()), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let word = Repr::Interval(Interval('a', 'z')).plus();
+        let reprs = [
+            Repr::Zero(Zero::WordBoundary).mul(word.clone())
+                .mul(Repr::Zero(Zero::WordBoundary)),
+            Repr::Zero(Zero::NotWordBoundary).mul(Repr::one('b')),
+            word.clone().mul(Repr::Zero(Zero::WordBoundary)).exp(),
+        ];
+        let texts = ["ab cd", "abc", "a-b_c d", " b ", "", "x1b", "ab.\ncd"];
+        for repr in reprs {
+            let unicode = Exec::new(repr.clone()).unwrap();
+            let mut options = Options::new(repr.clone());
+            options.ascii = true;
+            let ascii = options.build().unwrap();
+            assert!(!ascii.ro.nfa.has_unicode_word_boundary);
+            assert!(ascii.ro.nfa.iter().all(|inst| !matches!(
+                inst,
+                Inst::Zero { zero: Zero::WordBoundary, .. }
+                    | Inst::Zero { zero: Zero::NotWordBoundary, .. }
+            )));
+            for text in texts {
+                let context = Context::from(text);
+                for start in 0..=context.len() {
+                    assert_eq!(
+                        ascii.find_at(&context, start)
+                            .map(|m| (m.start(), m.end())),
+                        unicode.find_at(&context, start)
+                            .map(|m| (m.start(), m.end())),
+                        "{:?} {:?} {}", repr, text, start
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn size_limit() {
+        let lower = Repr::Interval(Interval('a', 'z'));
+        let mut options = Options::new(lower.clone().repeat_range(0, Some(2_000)));
+        options.size_limit = 1 << 14;
+        assert_eq!(options.build().err(), Some(CompileError::TooLarge(1 << 14)));
+
+        let mut options = Options::new(lower.repeat_range(0, Some(10)));
+        options.size_limit = 1 << 14;
+        assert!(options.build().is_ok());
+    }
+
+    #[test]
+    fn step_limit() {
+        // The Unicode word boundary keeps the DFA out, which isn't limited.
+        let a = Repr::one('a');
+        let repr = Repr::Zero(Zero::WordBoundary).mul(a.exp().exp())
+            .mul(Repr::one('b').or(Repr::one('c')));
+        let expensive = Context::from("a".repeat(100).as_str());
+        let cheap = Context::from("ab");
+        let mut options = Options::new(repr.clone());
+        options.step_limit = 100;
+        let exec = options.build().unwrap();
+        assert!(matches!(exec.ro.match_type, MatchType::Nfa));
+        assert_eq!(exec.try_find_at(&expensive, 0).err(),
+                   Some(MatchError::StepLimitExceeded));
+        assert_eq!(exec.try_is_match_at(&expensive, 0),
+                   Err(MatchError::StepLimitExceeded));
+        assert_eq!(exec.find_at(&expensive, 0), None);
+        assert_eq!(exec.try_find_at(&cheap, 0).unwrap()
+                       .map(|m| (m.start(), m.end())), Some((0, 2)));
+        assert_eq!(exec.try_is_match_at(&cheap, 0), Ok(true));
+
+        let exec = Exec::new(repr).unwrap();
+        assert_eq!(exec.try_is_match_at(&expensive, 0), Ok(false));
+    }
+
+    #[test]
+    fn max_captures() {
+        let groups = |n: usize| Repr::prod((0..n).map(|i| {
+            Repr::one('a').cap(&format!("g{}", i))
+        }));
+        let mut options = Options::new(groups(4));
+        options.max_captures = 3;
+        assert_eq!(options.build().err(), Some(CompileError::TooManyCaptures(3)));
+
+        let mut options = Options::new(groups(3));
+        options.max_captures = 3;
+        assert!(options.build().is_ok());
+        // A name used twice is a single group.
+        let mut options = Options::new(groups(3).mul(Repr::one('b').cap("g0")));
+        options.max_captures = 3;
+        assert!(options.build().is_ok());
+    }
+
+    #[test]
+    fn dfa_agrees_with_nfa() {
+        let abc = Repr::Interval(Interval('a', 'c'));
+        let reprs = [
+            Repr::one('a').mul(abc.clone().exp()).mul(Repr::one('x')),
+            Repr::literal("ab").or(Repr::literal("bca")).exp().mul(Repr::one('c')),
+            Repr::Zero(Zero::StartText).mul(Repr::one('b').exp()).mul(Repr::one('c')),
+            abc.clone().mul(Repr::one('x')).mul(Repr::Zero(Zero::EndText))
+                .or(Repr::literal("cc")),
+            Repr::one('x').exp(),
+        ];
+        for repr in reprs {
+            let exec = Exec::new(repr.clone()).unwrap();
+            assert!(matches!(exec.ro.match_type, MatchType::Dfa), "{:?}", repr);
+            for context in inputs(200) {
+                for start in 0..=context.len() {
+                    let span = pikevm::Fsm::find(&exec.ro.nfa, exec.cache.value(),
+                                                 &context, start, context.len(),
+                                                 false);
+                    let end = match exec.shortest_dfa(&context, start) {
+                        dfa::Result::Match(end) => Some(end),
+                        dfa::Result::NoMatch => None,
+                        dfa::Result::Quit => panic!("{:?} quit", repr),
+                    };
+                    assert_eq!(end.is_some(), span.is_some(),
+                               "{:?} {:?} {}", repr, context, start);
+                    if let (Some(end), Some((_, span_end))) = (end, span) {
+                        assert!(end <= span_end,
+                                "{:?} {:?} {}", repr, context, start);
+                    }
+                    assert_eq!(exec.find_at(&context, start)
+                                   .map(|m| (m.start(), m.end())),
+                               span);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn line_terminator() {
+        let end = Repr::one('a').mul(Repr::Zero(Zero::EndLine));
+        let mut options = Options::new(end.clone());
+        options.line_terminator = '\r';
+        let cr = options.build().unwrap();
+        let lf = Exec::new(end).unwrap();
+        let context = Context::from("a\rb\na");
+        assert_eq!(cr.find(&context).map(|m| (m.start(), m.end())), Some((0, 1)));
+        assert_eq!(lf.find(&context).map(|m| (m.start(), m.end())), Some((4, 5)));
+
+        let start = Repr::Zero(Zero::StartLine).mul(Repr::one('b'));
+        let mut options = Options::new(start);
+        options.line_terminator = '\r';
+        let cr = options.build().unwrap();
+        assert!(cr.is_match(&Context::from("a\rb")));
+        assert!(!cr.is_match(&Context::from("a\nb")));
+
+        let mut options = Options::new(Repr::one('a').or(Repr::dot()));
+        options.line_terminator = '\r';
+        let cr = options.build().unwrap();
+        assert!(cr.is_match(&Context::from("\n")));
+        assert!(!cr.is_match(&Context::from("\r")));
+    }
+
+    #[test]
+    fn anchored_find_iter() {
+        let spans = |exec: &Exec<char>, context: &Context<char>| {
+            let mut cursor = Cursor::new();
+            let mut spans = Vec::new();
+            while let Some(m) = exec.find_at(context, cursor.last_end) {
+                if cursor.advance(m.start(), m.end()) {
+                    spans.push((m.start(), m.end()));
+                }
+            }
+            spans
+        };
+        let a = Repr::one('a').plus();
+        let line = Exec::new(Repr::Zero(Zero::StartLine).mul(a.clone())).unwrap();
+        assert!(line.ro.nfa.is_line_anchored_start);
+        let context = Context::from("ab\naa\nxa\na");
+        assert_eq!(spans(&line, &context), vec![(0, 1), (3, 5), (9, 10)]);
+        assert_eq!(line.line_start(&context, 1), Some(3));
+        assert_eq!(line.line_start(&context, 3), Some(3));
+        assert_eq!(line.line_start(&context, 7), Some(9));
+        assert_eq!(line.line_start(&context, 10), None);
+
+        let text = Exec::new(Repr::Zero(Zero::StartText).mul(a)).unwrap();
+        assert!(text.ro.nfa.is_line_anchored_start);
+        let context = Context::from(&*"a".repeat(1000));
+        assert_eq!(spans(&text, &context), vec![(0, 1000)]);
+        assert!(text.find_at(&context, 1).is_none());
+    }
+
+    #[test]
+    fn crlf() {
+        let build = |repr: Repr<char>| {
+            let mut options = Options::new(repr);
+            options.crlf = true;
+            options.build().unwrap()
+        };
+        let spans = |exec: &Exec<char>, text: &str| {
+            let context = Context::from(text);
+            let mut cursor = Cursor::new();
+            let mut spans = Vec::new();
+            while let Some(m) = exec.find_at(&context, cursor.last_end) {
+                if cursor.advance(m.start(), m.end()) {
+                    spans.push((m.start(), m.end()));
+                }
+            }
+            spans
+        };
+        let start = build(Repr::Zero(Zero::StartLine));
+        let end = build(Repr::Zero(Zero::EndLine));
+        assert_eq!(spans(&start, "a\r\nb"), vec![(0, 0), (3, 3)]);
+        assert_eq!(spans(&end, "a\r\nb"), vec![(1, 1), (4, 4)]);
+        assert_eq!(spans(&start, "\r\n"), vec![(0, 0), (2, 2)]);
+        assert_eq!(spans(&end, "\r\n"), vec![(0, 0), (2, 2)]);
+        // A lone `\r` or `\n` ends a line too.
+        assert_eq!(spans(&start, "a\rb\nc"), vec![(0, 0), (2, 2), (4, 4)]);
+        assert_eq!(spans(&end, "a\rb\nc"), vec![(1, 1), (3, 3), (5, 5)]);
+
+        let line = build(Repr::Zero(Zero::StartLine)
+            .mul(Repr::dot().plus())
+            .mul(Repr::Zero(Zero::EndLine)));
+        assert_eq!(spans(&line, "ab\r\ncd\r\n"), vec![(0, 2), (4, 6)]);
+    }
+
+    #[test]
+    fn lazy() {
+        let tag = |star: fn(Repr<char>) -> Repr<char>| {
+            Repr::one('<').mul(star(Repr::dot()).cap("x")).mul(Repr::one('>'))
+        };
+        let context = Context::from("<a><b>");
+        let greedy = Exec::new(tag(Repr::exp)).unwrap();
+        let lazy = Exec::new(tag(Repr::exp_lazy)).unwrap();
+        assert_eq!(greedy.find(&context).map(|m| (m.start(), m.end())),
+                   Some((0, 6)));
+        assert_eq!(lazy.find(&context).map(|m| (m.start(), m.end())),
+                   Some((0, 3)));
+        let x = |exec: &Exec<char>| exec.captures_at(&context, 0)
+            .and_then(|captures| captures.name("x"))
+            .map(|m| (m.start(), m.end()));
+        assert_eq!(x(&greedy), Some((1, 5)));
+        assert_eq!(x(&lazy), Some((1, 2)));
+        assert_eq!(Repr::try_from("<(?P<x>.*?)>").unwrap(),
+                   tag(Repr::exp_lazy));
+    }
+}
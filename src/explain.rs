@@ -0,0 +1,252 @@
+//! Tracing a search for `Repr::explain`.
+//!
+//! The tracer walks the compiled program like the bounded backtracker does,
+//! with an explicit stack of jobs and each instruction visited at most once
+//! per position, but it remembers the steps of the path it is on, so that
+//! it can tell how the match it finds was reached, or where the path that
+//! read the most of the text failed.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+use crate::context::Context;
+use crate::program::{Index, Inst, Program};
+use crate::repr::Repr;
+
+/// The most instructions a trace steps through before giving up, so that
+/// reprs that backtrack a lot still get an answer.
+const STEP_LIMIT: usize = 10_000;
+
+/// The most steps of a path that are written out. Past it, the middle of
+/// the path is left out.
+const LINE_LIMIT: usize = 64;
+
+impl Repr<char> {
+    /// Returns a readable trace of the search of `text` for `self`, to find
+    /// out why a repr matches or doesn't where it was expected to. It lists
+    /// the compiled program, then for the leftmost-first match, the path
+    /// through it: which branch of each `Split` was taken, and where each
+    /// element was read and each assertion held. Without a match, it is the
+    /// path that read the most of the text before failing, the first one if
+    /// there are several, and where it failed. Positions are character
+    /// positions in `text`.
+    ///
+    /// The trace is bounded in steps and in lines, so a repr backtracking a
+    /// lot is cut short, which the verdict says. If the repr can't be
+    /// compiled, the trace is the `CompileError` instead.
+    pub fn explain(&self, text: &str) -> String {
+        let prog = match self.compile() {
+            Ok(prog) => prog,
+            Err(error) => return format!("can't compile: {}", error),
+        };
+        let context = Context::from(text);
+        let mut tracer = Tracer {
+            prog: &prog,
+            context: &context,
+            visited: vec![false; prog.len() * (context.len() + 1)],
+            steps: 0,
+            path: Vec::new(),
+            last: Vec::new(),
+            read: 0,
+        };
+        let mut out = format!("{:?}", prog);
+        let verdict = (0..=context.len())
+            .find_map(|start| tracer.search(start).map(|end| (start, end)));
+        let (path, verdict) = match verdict {
+            Some((start, Some(end))) => {
+                (&tracer.path, format!("match at {}..{}", start, end))
+            }
+            Some((_, None)) => {
+                (&tracer.last, format!("gave up after {} steps", STEP_LIMIT))
+            }
+            None => (&tracer.last, String::from("no match")),
+        };
+        let (head, tail) = if path.len() > LINE_LIMIT {
+            (LINE_LIMIT / 2, path.len() - LINE_LIMIT / 2)
+        } else {
+            (path.len(), path.len())
+        };
+        for step in path[..head].iter() {
+            writeln!(out, "{}", describe(&prog, &context, step)).unwrap();
+        }
+        if head < tail {
+            writeln!(out, "... {} steps ...", tail - head).unwrap();
+        }
+        for step in path[tail..].iter() {
+            writeln!(out, "{}", describe(&prog, &context, step)).unwrap();
+        }
+        out.push_str(&verdict);
+        out
+    }
+}
+
+/// A step of a path: the instruction at `ip` reached at position `at`.
+#[derive(Clone, Copy)]
+struct Step {
+    ip: Index,
+    at: usize,
+    kind: Kind,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    /// The instruction was passed, a `Split` through its first branch.
+    Pass,
+    /// A `Split` was passed through its second branch.
+    Second,
+    /// The instruction failed, which ends the path.
+    Fail,
+}
+
+struct Tracer<'a> {
+    prog: &'a Program<char>,
+    context: &'a Context<char>,
+    visited: Vec<bool>,
+    steps: usize,
+    /// The path being tried.
+    path: Vec<Step>,
+    /// The failed path that read the most, its failing step included.
+    last: Vec<Step>,
+    /// How many elements `last` read.
+    read: usize,
+}
+
+impl<'a> Tracer<'a> {
+    /// Searches for a match starting at `start`, returning `None` if there
+    /// is none, `Some(Some(end))` if there is one, leaving its path in
+    /// `path`, and `Some(None)` if the step limit was reached.
+    fn search(&mut self, start: usize) -> Option<Option<usize>> {
+        // A job resumes at `ip` with the path cut back to `depth` steps. If
+        // `second`, `ip` is a `Split` whose first branch failed.
+        let mut jobs = vec![(0, start, 0, false)];
+        while let Some((mut ip, mut at, depth, second)) = jobs.pop() {
+            self.path.truncate(depth);
+            if second {
+                if let Inst::Split { goto2, .. } = self.prog[ip] {
+                    self.path.push(Step { ip, at, kind: Kind::Second });
+                    ip = goto2;
+                }
+            }
+            loop {
+                let k = ip * (self.context.len() + 1) + at;
+                if self.visited[k] {
+                    break;
+                }
+                self.visited[k] = true;
+                self.steps += 1;
+                if self.steps > STEP_LIMIT {
+                    self.last = self.path.clone();
+                    return Some(None);
+                }
+                let next = match self.prog[ip] {
+                    Inst::Match(_) => {
+                        self.path.push(Step { ip, at, kind: Kind::Pass });
+                        return Some(Some(at));
+                    }
+                    Inst::Split { goto1, .. } => {
+                        jobs.push((ip, at, self.path.len(), true));
+                        Some((goto1, at))
+                    }
+                    Inst::Zero { goto, zero } => self.context.is_empty_match(
+                        at, &zero, self.prog.line_terminator, self.prog.crlf
+                    ).then_some((goto, at)),
+                    Inst::One { goto, ref seq } => self.context[at..]
+                        .starts_with(seq)
+                        .then_some((goto, at + seq.len())),
+                    Inst::Interval { goto, interval } => self.context.next(at)
+                        .filter(|&c| interval.has(c))
+                        .map(|_| (goto, at + 1)),
+                    Inst::Save { goto, .. } => Some((goto, at)),
+                };
+                match next {
+                    Some((goto, next)) => {
+                        self.path.push(Step { ip, at, kind: Kind::Pass });
+                        (ip, at) = (goto, next);
+                    }
+                    None => {
+                        if self.last.is_empty() || at - start > self.read {
+                            self.last = self.path.clone();
+                            self.last.push(Step { ip, at, kind: Kind::Fail });
+                            self.read = at - start;
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+fn describe(prog: &Program<char>, context: &Context<char>, step: &Step)
+    -> String
+{
+    let Step { ip, at, kind } = *step;
+    let failed = matches!(kind, Kind::Fail);
+    let what = match prog[ip] {
+        Inst::Match(_) => String::from("Match"),
+        Inst::Split { goto1, goto2 } => match kind {
+            Kind::Second => format!("Split, second branch to {}", goto2),
+            _ => format!("Split, first branch to {}", goto1),
+        },
+        Inst::Zero { zero, .. } if failed => format!("{:?} fails", zero),
+        Inst::Zero { zero, .. } => format!("{:?} holds", zero),
+        Inst::One { ref seq, .. } => {
+            let seq: String = seq.iter().collect();
+            if failed {
+                format!("{:?} not found", seq)
+            } else {
+                format!("{:?} read", seq)
+            }
+        }
+        Inst::Interval { interval, .. } => match context.next(at) {
+            Some(c) if failed => format!("{:?} not in {:?}", c, interval),
+            None => format!("end of input, not in {:?}", interval),
+            Some(c) => format!("{:?} in {:?}", c, interval),
+        },
+        Inst::Save { slot, .. } => format!("Save({})", slot),
+    };
+    format!("{:04} at {}: {}", ip, at, what)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::CompileError;
+    use crate::repr::Zero;
+
+    #[test]
+    fn explain() {
+        let tag = Repr::one('<').mul(Repr::dot().exp_lazy())
+            .mul(Repr::one('>'));
+        let trace = tag.explain("<a><b>");
+        assert!(trace.ends_with("match at 0..3"), "{}", trace);
+        assert!(trace.contains("at 0: \"<\" read"), "{}", trace);
+        // Lazily, `>` is tried first, then `.` once.
+        assert!(trace.contains("at 1: Split, second branch"), "{}", trace);
+        assert!(trace.contains("at 1: 'a' in"), "{}", trace);
+        assert!(trace.contains("at 2: Split, first branch"), "{}", trace);
+        assert!(trace.contains("at 2: \">\" read"), "{}", trace);
+
+        let end = Repr::literal("ab").mul(Repr::Zero(Zero::EndText));
+        let trace = end.explain("abc");
+        assert!(trace.ends_with("no match"), "{}", trace);
+        assert!(trace.contains("EndText fails"), "{}", trace);
+
+        let div = Repr::one('a').exp().div(Repr::one('a'));
+        assert_eq!(div.explain("a"), format!("can't compile: {}",
+                                             CompileError::UnsupportedDiv));
+    }
+
+    #[test]
+    fn bounded() {
+        let a = Repr::one('a');
+        let repr = a.clone().exp().exp().mul(Repr::one('b'));
+        let trace = repr.explain(&"a".repeat(200));
+        assert!(trace.ends_with("no match")
+                || trace.ends_with(&format!("gave up after {} steps",
+                                            STEP_LIMIT)), "{}", trace);
+        assert!(trace.lines().count() < repr.compile().unwrap().len()
+                + LINE_LIMIT + 4);
+    }
+}
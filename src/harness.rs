@@ -0,0 +1,193 @@
+//! Differential testing of the matching engines.
+//!
+//! `check` runs a repr over an input through the backtracker, the PikeVM
+//! and the derivative matcher and panics if they disagree. Reprs and inputs
+//! are drawn from `Rng`, which can be seeded from raw bytes so that a fuzzer
+//! can drive it:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| {
+//!     let mut rng = Rng::from_bytes(data);
+//!     let repr = rng.repr(4);
+//!     let input = rng.input(8);
+//!     check(&repr, &input);
+//! });
+//! ```
+
+use core::{cell::RefCell, iter::Step, panic::AssertUnwindSafe};
+
+use crate::backtrack::Bounded;
+use crate::compile::Compiler;
+use crate::context::Context;
+use crate::interval::Interval;
+use crate::pikevm::Fsm;
+use crate::program::{Program, ProgramCache, ProgramCacheInner};
+use crate::repr::{Integral, Repr, Zero};
+
+/// The symbols reprs and inputs are made of. Few, so that matches are
+/// likely.
+const ALPHABET: [char; 3] = ['a', 'b', 'c'];
+
+/// A fixed-seed linear congruential generator, so that failures are
+/// reproducible.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Self {
+        Rng(data.iter().fold(0x2545_F491_4F6C_DD1D, |seed, &byte| {
+            (seed ^ byte as u64).wrapping_mul(0x100_0000_01B3)
+        }))
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6_364_136_223_846_793_005)
+                       .wrapping_add(1_442_695_040_888_963_407);
+        (self.0 >> 33) as usize % n
+    }
+
+    fn symbol(&mut self) -> char {
+        ALPHABET[self.below(ALPHABET.len())]
+    }
+
+    /// A repr at most `depth` operators deep, made only of what both the
+    /// compiler and the derivatives support, and without assertions, which
+    /// the derivative matcher doesn't see.
+    pub fn repr(&mut self, depth: usize) -> Repr<char> {
+        let choice = if depth == 0 { self.below(3) } else { self.below(8) };
+        match choice {
+            0 => Repr::one(self.symbol()),
+            1 => {
+                let (a, b) = (self.symbol(), self.symbol());
+                Repr::Interval(Interval::new(a.min(b), a.max(b)).unwrap())
+            }
+            2 => Repr::zero(),
+            3 | 4 => self.repr(depth - 1).mul(self.repr(depth - 1)),
+            5 => self.repr(depth - 1).or(self.repr(depth - 1)),
+            6 => self.repr(depth - 1).exp(),
+            _ => Repr::one(self.symbol()).div(self.repr(depth - 1)),
+        }
+    }
+
+    /// An input of at most `len` symbols.
+    pub fn input(&mut self, len: usize) -> String {
+        let len = self.below(len + 1);
+        (0..len).map(|_| self.symbol()).collect()
+    }
+}
+
+/// Panics unless the engines agree on `repr` over `input`:
+///
+/// - the backtracker and the PikeVM find the same span from every start,
+///   anchored there or not,
+/// - the derivative matcher accepts every span they find, and
+/// - all three agree on whether the whole of `input` is matched.
+pub fn check(repr: &Repr<char>, input: &str) {
+    let context = Context::from(input);
+    let prog = Compiler::new().compile(&[repr.clone()]).unwrap();
+    let cache = cache(&prog);
+    for (start, anchored) in (0..=context.len())
+        .flat_map(|start| [(start, false), (start, true)])
+    {
+        let span = Bounded::find(&prog, &cache, &context, start, context.len(),
+                                 anchored);
+        assert_eq!(
+            span,
+            Fsm::find(&prog, &cache, &context, start, context.len(), anchored),
+            "spans of {:?} in {:?} from {} ({})", repr, input, start, anchored
+        );
+        if let Some((start, end)) = span {
+            let text = &input[context.byte_offset(start)..context.byte_offset(end)];
+            assert!(repr.is_match_derivative(text),
+                    "{:?} doesn't match {:?} found in {:?}", repr, text, input);
+        }
+    }
+
+    let anchored = Repr::Zero(Zero::StartText)
+        .mul(repr.clone())
+        .mul(Repr::Zero(Zero::EndText));
+    let prog = Compiler::new().compile(&[anchored]).unwrap();
+    let cache = cache(&prog);
+    let expected = repr.is_match_derivative(input);
+    assert_eq!(
+        Bounded::exec(&prog, &cache, &mut [false], &context, 0, context.len()),
+        expected,
+        "backtracker on {:?} over all of {:?}", repr, input
+    );
+    assert_eq!(
+        Fsm::exec(&prog, &cache, &mut [false], &mut [], true, &context, 0,
+                  context.len()),
+        expected,
+        "PikeVM on {:?} over all of {:?}", repr, input
+    );
+}
+
+/// Panics if the `Integral` impl of `I` breaks what intervals and
+/// complements rely on: `MIN <= MAX`, `succ` and `pred` undoing each other
+/// and moving by one element in both `Ord` and `Step`, and nothing outside
+/// `MIN..=MAX`. It is checked near both bounds, at evenly spaced elements in
+/// between, and near each of `around`, which is where to put elements the
+/// impl treats specially, such as the ends of a gap.
+pub fn assert_integral_laws<I: Integral>(around: &[I]) {
+    assert!(I::MIN <= I::MAX, "MIN {:?} above MAX {:?}", I::MIN, I::MAX);
+    assert_eq!(Step::backward_checked(I::MIN, 1), None::<I>, "below MIN");
+    assert_eq!(Step::forward_checked(I::MAX, 1), None::<I>, "above MAX");
+    let len = Step::steps_between(&I::MIN, &I::MAX)
+        .expect("MAX not reachable from MIN");
+    let mut samples = Vec::new();
+    for (at, steps) in [(I::MIN, 0), (I::MAX, 16)].into_iter()
+        .chain((1..64).map(|k| (Step::forward(I::MIN, len / 64 * k), 8)))
+        .chain(around.iter().map(|&i| (i, 8)))
+    {
+        let from = Step::backward_checked(at, steps).unwrap_or(I::MIN);
+        samples.extend((from..=I::MAX).take(2 * steps + 16));
+    }
+    for &i in samples.iter() {
+        if i != I::MAX {
+            let succ = i.succ();
+            assert!(i < succ, "{:?}.succ() is {:?}", i, succ);
+            assert_eq!(succ.pred(), i, "{:?}.succ().pred()", i);
+            assert_eq!(Step::forward_checked(i, 1), Some(succ), "{:?}", i);
+        }
+        if i != I::MIN {
+            let pred = i.pred();
+            assert!(pred < i, "{:?}.pred() is {:?}", i, pred);
+            assert_eq!(pred.succ(), i, "{:?}.pred().succ()", i);
+            assert_eq!(Step::backward_checked(i, 1), Some(pred), "{:?}", i);
+        }
+    }
+    for (&i, &j) in samples.iter().zip(samples.iter().skip(1)) {
+        assert_eq!(i.partial_cmp(&j), Some(i.cmp(&j)), "{:?} {:?}", i, j);
+        assert_eq!(Step::steps_between(&i, &j).is_some(), i <= j,
+                   "{:?} {:?}", i, j);
+    }
+}
+
+fn cache(prog: &Program<char>) -> ProgramCache<char> {
+    AssertUnwindSafe(RefCell::new(ProgramCacheInner::new(prog)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn engines_agree() {
+        let mut rng = Rng::new(0);
+        for _ in 0..500 {
+            let repr = rng.repr(4);
+            for _ in 0..8 {
+                check(&repr, &rng.input(8));
+            }
+        }
+    }
+
+    #[test]
+    fn integral_laws() {
+        assert_integral_laws::<char>(&['\u{D7FF}', '\u{E000}']);
+        assert_integral_laws::<u8>(&[0x7F]);
+    }
+}
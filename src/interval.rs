@@ -28,12 +28,15 @@
 //
 // Tests on this are relegated to the public API of HIR in src/hir.rs.
 // Tests for interval sets are written in src/hir.rs against the public API.
+use alloc::vec::Vec;
 use core::{
     cmp::{max, min},
+    fmt::{self, Debug},
     iter::{IntoIterator, Step},
     ops::RangeInclusive
 };
 
+use regex_syntax::hir::{ClassUnicode, ClassUnicodeRange};
 use unconst::unconst;
 
 use crate::repr::Integral;
@@ -43,17 +46,21 @@ use crate::repr::Integral;
 // TODO(rnarkk) check if I..I always yield valid characters
 /// A character class, regardless of its character type, is represented by a
 /// sequence of non-overlapping non-adjacent ranges of characters.
-#[derive_const(Clone, Debug, Default, PartialEq, PartialOrd, Ord)]
-#[derive(Copy, Eq)]
+#[derive_const(Clone, Default, PartialEq, PartialOrd, Ord)]
+#[derive(Copy, Eq, Hash)]
 pub struct Interval<I: ~const Integral>(pub I, pub I);
 
 #[unconst]
 impl<I: ~const Integral> Interval<I> {
-    pub const fn new(from: I, to: I) -> Self {
+    /// The interval from `from` to `to`, both included, or `None` if `to`
+    /// is below `from`. Such an interval, which the tuple constructor
+    /// accepts, contains nothing, so a class built with its bounds the
+    /// wrong way round would never match.
+    pub const fn new(from: I, to: I) -> Option<Self> {
         if from <= to {
-            Interval(from, to)
+            Some(Interval(from, to))
         } else {
-            Interval(to, from)
+            None
         }
     }
 
@@ -66,18 +73,22 @@ impl<I: ~const Integral> Interval<I> {
     /// If the intersection is empty, then this returns `None`.
     pub const fn and(self, other: Self) -> Option<Self> {
         match (max(self.0, other.0), min(self.1, other.1)) {
-            (from, to) if from <= to => Some(Self::new(from, to)),
+            (from, to) if from <= to => Some(Interval(from, to)),
             _ => None
         }
     }
     
     /// Union the given overlapping Interval into this Interval.
     ///
-    /// If the two Seqs aren't contiguous, then this returns `None`.
+    /// If the two Intervals aren't contiguous, then this returns `None`.
     pub const fn or(self, other: Self) -> Option<Self> {
-        match (max(self.0, other.0), min(self.1, other.1)) {
-            (from, to) if from <= to.succ() => Some(Self::new(from, to)),
-            _ => None
+        // Where they overlap, or the gap between them. `to` can only be
+        // `I::MAX` if they overlap, so its successor exists otherwise.
+        let (from, to) = (max(self.0, other.0), min(self.1, other.1));
+        if from <= to || from == to.succ() {
+            Some(Interval(min(self.0, other.0), max(self.1, other.1)))
+        } else {
+            None
         }
     }
     
@@ -114,10 +125,10 @@ impl<I: ~const Integral> Interval<I> {
         }
         let mut ret = (None, None);
         if self.0 < other.0 {
-            ret.0 = Some(Self::new(self.0, other.0.pred()));
+            ret.0 = Some(Interval(self.0, other.0.pred()));
         }
         if other.1 < self.1 {
-            let range = Self::new(other.1.succ(), self.1);
+            let range = Interval(other.1.succ(), self.1);
             if ret.0.is_none() {
                 ret.0 = Some(range);
             } else {
@@ -127,6 +138,25 @@ impl<I: ~const Integral> Interval<I> {
         ret
     }
 
+    /// Splits this Interval into the part below `pivot` and the part at or
+    /// above it, either being `None` if empty: the lower one when `pivot`
+    /// is at or below `self.0`, the upper one when it is above `self.1`.
+    /// Refining classes into disjoint ones comes down to splitting at the
+    /// bounds of the others.
+    pub const fn split_at(&self, pivot: I) -> (Option<Self>, Option<Self>) {
+        let lower = if self.0 < pivot {
+            Some(Interval(self.0, min(self.1, pivot.pred())))
+        } else {
+            None
+        };
+        let upper = if pivot <= self.1 {
+            Some(Interval(max(self.0, pivot), self.1))
+        } else {
+            None
+        };
+        (lower, upper)
+    }
+
     /// Negate this Interval.
     ///
     /// For all `a` where `a` is any element, if `a` is in this interval, then it will not be in this set after negation.
@@ -143,16 +173,39 @@ impl<I: ~const Integral> Interval<I> {
 
     /// i ∈ a
     pub fn has(&self, i: I) -> bool {
-        if self.0 <= i && i <= self.1 {
-            true
-        } else {
-            false
-        }
+        self.contains(i)
+    }
+
+    /// i ∈ a, usable in const context so that classes can be checked at
+    /// compile time.
+    pub const fn contains(&self, i: I) -> bool {
+        self.0 <= i && i <= self.1
+    }
+
+    /// a ∩ b ≠ ∅, that is, whether `and` returns `Some`.
+    pub const fn overlaps(&self, other: &Self) -> bool {
+        max(self.0, other.0) <= min(self.1, other.1)
     }
 
     pub const fn len(&self) -> usize {
         <I as Step>::steps_between(&self.0, &self.1).unwrap()
     }
+
+    /// Returns an iterator over the elements of this interval in order.
+    /// Stepping goes through `Step`, so for `char` it skips the surrogate
+    /// gap and never yields an invalid scalar value.
+    pub const fn iter(&self) -> RangeInclusive<I> {
+        self.0..=self.1
+    }
+
+    /// Returns the number of elements in this interval.
+    ///
+    /// This counts through `Step` rather than subtracting the bounds, so for
+    /// `char` the surrogate gap `U+D800..=U+DFFF` isn't counted when the
+    /// interval spans it.
+    pub const fn num_chars(&self) -> usize {
+        self.len() + 1
+    }
 }
 
 #[unconst]
@@ -165,6 +218,25 @@ impl<I: ~const Integral> const IntoIterator for Interval<I> {
     }
 }
 
+/// Ranges with more elements than this show their count in `Debug`.
+const DEBUG_COUNT_FROM: usize = 256;
+
+/// `[lo-hi]`, or just `lo` if it is the only element, followed by the number
+/// of elements for large ranges, as in `['\0'-'\u{10ffff}'; 1112064]`.
+impl<I: Integral> Debug for Interval<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 == self.1 {
+            return write!(f, "{:?}", self.0);
+        }
+        write!(f, "[{:?}-{:?}", self.0, self.1)?;
+        // A void interval has no count.
+        if self.0 < self.1 && self.num_chars() > DEBUG_COUNT_FROM {
+            write!(f, "; {}", self.num_chars())?;
+        }
+        write!(f, "]")
+    }
+}
+
 impl Interval<char> {
     /// Returns true if and only if this character class will either match
     /// nothing or only ASCII bytes. Stated differently, this returns false
@@ -172,4 +244,134 @@ impl Interval<char> {
     pub fn is_all_ascii(&self) -> bool {
         self.1 <= '\x7F'
     }
+
+    /// Returns the fewest intervals, in order, covering this interval and
+    /// the simple case folds of its characters, as in Unicode's
+    /// `CaseFolding.txt`. Folds next to each other or to this interval are
+    /// merged into one. Without the `unicode-case` feature, it is only this
+    /// interval.
+    pub fn case_fold(&self) -> Vec<Interval<char>> {
+        if self.1 < self.0 {
+            return Vec::new();
+        }
+        let mut class = ClassUnicode::new([
+            ClassUnicodeRange::new(self.0, self.1)
+        ]);
+        if class.try_case_fold_simple().is_err() {
+            return vec![*self];
+        }
+        class.iter()
+            .map(|range| Interval(range.start(), range.end()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repr::Repr;
+
+    #[test]
+    fn num_chars() {
+        assert_eq!(Interval('a', 'z').num_chars(), 26);
+        assert_eq!(Interval('a', 'a').num_chars(), 1);
+        assert_eq!(Interval('\u{D7FF}', '\u{E000}').num_chars(), 2);
+        assert_eq!(Interval::<char>::full().num_chars(), 1_112_064);
+    }
+
+    #[test]
+    fn iter() {
+        assert_eq!(Interval('a', 'a').iter().collect::<Vec<_>>(), vec!['a']);
+        assert_eq!(Interval('a', 'd').iter().collect::<String>(), "abcd");
+        assert_eq!(
+            Interval('\u{D7FE}', '\u{E001}').iter().collect::<Vec<_>>(),
+            vec!['\u{D7FE}', '\u{D7FF}', '\u{E000}', '\u{E001}']
+        );
+        let full = Interval::<char>::full();
+        assert_eq!(full.iter().count(), full.num_chars());
+    }
+
+    #[test]
+    fn new() {
+        assert_eq!(Interval::new('a', 'z'), Some(Interval('a', 'z')));
+        assert_eq!(Interval::new('a', 'a'), Some(Interval('a', 'a')));
+        assert_eq!(Interval::new('z', 'a'), None);
+        assert_eq!(Interval::from('a'..='z'), Interval('a', 'z'));
+        assert!(Repr::from('z'..='a').is_void());
+        assert!(!Repr::from('z'..='a').is_match("m"));
+    }
+
+    #[test]
+    fn contains_overlaps() {
+        const _: () = assert!(Interval('a', 'z').contains('m'));
+        const _: () = assert!(!Interval('a', 'z').contains('A'));
+        const _: () = assert!(Interval('a', 'm').overlaps(&Interval('m', 'z')));
+        const _: () = assert!(!Interval('a', 'f').overlaps(&Interval('g', 'z')));
+        const _: () = assert!(!Interval('g', 'z').overlaps(&Interval('a', 'f')));
+        assert!(Interval('a', 'z').overlaps(&Interval('c', 'd')));
+        assert!(!Interval('z', 'a').overlaps(&Interval('a', 'z')));
+        assert_eq!(Interval('a', 'f').overlaps(&Interval('d', 'z')),
+                   Interval('a', 'f').and(Interval('d', 'z')).is_some());
+    }
+
+    #[test]
+    fn split_at() {
+        let az = Interval('a', 'z');
+        assert_eq!(az.split_at('m'),
+                   (Some(Interval('a', 'l')), Some(Interval('m', 'z'))));
+        assert_eq!(az.split_at('a'), (None, Some(az)));
+        assert_eq!(az.split_at('z'),
+                   (Some(Interval('a', 'y')), Some(Interval('z', 'z'))));
+        assert_eq!(az.split_at('A'), (None, Some(az)));
+        assert_eq!(az.split_at('~'), (Some(az), None));
+        assert_eq!(Interval('\u{D000}', '\u{E100}').split_at('\u{E000}'),
+                   (Some(Interval('\u{D000}', '\u{D7FF}')),
+                    Some(Interval('\u{E000}', '\u{E100}'))));
+        for pivot in ['a', 'b', 'm', 'z'] {
+            if let (Some(lower), Some(upper)) = az.split_at(pivot) {
+                assert_eq!(lower.or(upper), Some(az));
+            }
+        }
+        assert_eq!(Interval('a', 'l').or(Interval('m', 'z')), Some(az));
+        assert_eq!(Interval('m', 'z').or(Interval('a', 'p')), Some(az));
+        assert_eq!(Interval('a', 'k').or(Interval('m', 'z')), None);
+        assert_eq!(Interval(0u8, 255).or(Interval(3, 255)),
+                   Some(Interval(0, 255)));
+        assert_eq!(Interval(0u8, 255).split_at(255),
+                   (Some(Interval(0, 254)), Some(Interval(255, 255))));
+    }
+
+    #[test]
+    fn debug() {
+        assert_eq!(format!("{:?}", Interval('a', 'z')), "['a'-'z']");
+        assert_eq!(format!("{:?}", Interval('a', 'a')), "'a'");
+        assert_eq!(format!("{:?}", Interval(0u8, 255)), "[0-255]");
+        assert_eq!(format!("{:?}", Interval::<char>::full()),
+                   "['\\0'-'\\u{10ffff}'; 1112064]");
+        assert_eq!(format!("{:?}", Repr::<char>::void()),
+                   "Interval(['\\u{10ffff}'-'\\0'])");
+        let prog = Repr::Interval(Interval('0', '9')).compile().unwrap();
+        assert!(format!("{:?}", prog).contains(" ['0'-'9']"), "{:?}", prog);
+    }
+
+    #[test]
+    fn case_fold() {
+        assert_eq!(Interval('a', 'z').case_fold(),
+                   [Interval('A', 'Z'), Interval('a', 'z')]);
+        // The folds of `a`..`w` run into `X`, and merge with it.
+        assert_eq!(Interval('X', 'z').case_fold(), [Interval('A', 'z')]);
+        assert_eq!(Interval('\u{3B1}', '\u{3B3}').case_fold(), [
+            Interval('\u{391}', '\u{393}'), Interval('\u{3B1}', '\u{3B3}')
+        ]);
+        assert_eq!(Interval('0', '9').case_fold(), [Interval('0', '9')]);
+        // `Σ`, `σ` and `ς` fold together, and `K` with the Kelvin sign.
+        assert_eq!(Interval('\u{3A3}', '\u{3A3}').case_fold(), [
+            Interval('\u{3A3}', '\u{3A3}'), Interval('\u{3C2}', '\u{3C3}')
+        ]);
+        assert_eq!(Interval('k', 'k').case_fold(), [
+            Interval('K', 'K'), Interval('k', 'k'),
+            Interval('\u{212A}', '\u{212A}')
+        ]);
+        assert!(Interval('z', 'a').case_fold().is_empty());
+    }
 }
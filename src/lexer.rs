@@ -0,0 +1,200 @@
+//! Anchored dispatch between token patterns, for lexers.
+//!
+//! The patterns are compiled into one program, as for a regex set, each
+//! ending in its own `Match(i)`. At a cursor, the program runs anchored
+//! there over the threads of all the patterns at once, keeping the longest
+//! match seen, so that the input is read once whatever the number of
+//! patterns.
+
+use alloc::vec::Vec;
+
+use crate::compile::Compiler;
+use crate::context::Context;
+use crate::error::CompileError;
+use crate::program::{Index, Inst, Program};
+use crate::repr::{Integral, Repr};
+
+/// Ordered token patterns, matched anchored at a cursor.
+///
+/// `next_token` returns the longest match among the patterns, and of those
+/// matching that far, the first in the order they were given. So with a
+/// keyword before the identifiers, `if` lexes as the keyword while `iffy`
+/// lexes as an identifier.
+#[derive(Clone)]
+pub struct Lexer<I: Integral, T> {
+    prog: Program<I>,
+    tokens: Vec<T>,
+}
+
+/// A thread: an instruction, and for a `One` instruction how many elements
+/// of its literal run have been matched.
+type Thread = (Index, usize);
+
+impl<T: Copy> Lexer<char, T> {
+    /// Compiles the patterns of `tokens`, highest priority first.
+    pub fn new(tokens: Vec<(T, Repr<char>)>) -> Result<Self, CompileError> {
+        let (tokens, reprs): (Vec<T>, Vec<Repr<char>>)
+            = tokens.into_iter().unzip();
+        let prog = if reprs.is_empty() {
+            Program::new()
+        } else {
+            Compiler::new().compile(&reprs)?
+        };
+        Ok(Lexer { prog, tokens })
+    }
+
+    /// Returns the token matching at `at`, as above, and the length of its
+    /// match, or `None` if no pattern matches there. A match may be empty.
+    pub fn next_token(&self, context: &Context<char>, at: usize)
+        -> Option<(T, usize)>
+    {
+        if self.prog.is_empty() {
+            return None;
+        }
+        let mut best = None;
+        let mut seen = vec![false; self.prog.len()];
+        let mut threads = Vec::new();
+        self.closure(context, at, &[self.prog.start], &mut seen, &mut threads,
+                     &mut best);
+        let mut end = at;
+        while !threads.is_empty() {
+            let Some(i) = context.next(end) else { break };
+            let mut gotos = Vec::new();
+            let mut next = Vec::new();
+            for &(ip, k) in threads.iter() {
+                match self.prog[ip] {
+                    Inst::One { goto, ref seq } if seq.get(k) == Some(i) => {
+                        if k + 1 == seq.len() {
+                            gotos.push(goto);
+                        } else {
+                            next.push((ip, k + 1));
+                        }
+                    }
+                    Inst::Interval { goto, interval } if interval.has(i) => {
+                        gotos.push(goto);
+                    }
+                    _ => {}
+                }
+            }
+            end += 1;
+            self.closure(context, end, &gotos, &mut seen, &mut next,
+                         &mut best);
+            next.sort();
+            next.dedup();
+            threads = next;
+        }
+        best.map(|(index, end)| (self.tokens[index], end - at))
+    }
+
+    /// Follows the epsilon transitions from `ips` at `at`, pushing the
+    /// threads that wait for an element onto `threads`. A match there
+    /// replaces `best` if it's the longest yet, or ties with it but comes
+    /// first.
+    fn closure(
+        &self,
+        context: &Context<char>,
+        at: usize,
+        ips: &[Index],
+        seen: &mut [bool],
+        threads: &mut Vec<Thread>,
+        best: &mut Option<(usize, usize)>,
+    ) {
+        for seen in seen.iter_mut() {
+            *seen = false;
+        }
+        let mut stack: Vec<Index> = ips.iter().rev().copied().collect();
+        while let Some(ip) = stack.pop() {
+            if seen[ip] {
+                continue;
+            }
+            seen[ip] = true;
+            match self.prog[ip] {
+                Inst::Match(index) => {
+                    if best.map_or(true, |(i, end)| at > end || index < i) {
+                        *best = Some((index, at));
+                    }
+                }
+                Inst::Split { goto1, goto2 } => {
+                    stack.push(goto2);
+                    stack.push(goto1);
+                }
+                Inst::Save { goto, .. } => stack.push(goto),
+                Inst::Zero { goto, ref zero } => {
+                    if context.is_empty_match(at, zero,
+                                              self.prog.line_terminator,
+                                              self.prog.crlf)
+                    {
+                        stack.push(goto);
+                    }
+                }
+                _ => threads.push((ip, 0)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interval::Interval;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Token {
+        If,
+        Ident,
+        Int,
+        Le,
+        Lt,
+        Space,
+    }
+
+    fn lexer() -> Lexer<char, Token> {
+        let alpha = Repr::Interval(Interval('a', 'z'));
+        let digit = Repr::Interval(Interval('0', '9'));
+        Lexer::new(vec![
+            (Token::If, Repr::literal("if")),
+            (Token::Ident, alpha.clone().mul(alpha.or(digit.clone()).exp())),
+            (Token::Int, digit.plus()),
+            (Token::Lt, Repr::one('<')),
+            (Token::Le, Repr::literal("<=")),
+            (Token::Space, Repr::one(' ').plus()),
+        ]).unwrap()
+    }
+
+    #[test]
+    fn next_token() {
+        let lexer = lexer();
+        let context = Context::from("if iffy <= x1 < 42");
+        let mut tokens = Vec::new();
+        let mut at = 0;
+        while let Some((token, len)) = lexer.next_token(&context, at) {
+            tokens.push((token, len));
+            at += len;
+        }
+        assert_eq!(at, context.len());
+        assert_eq!(tokens, [
+            (Token::If, 2), (Token::Space, 1), (Token::Ident, 4),
+            (Token::Space, 1), (Token::Le, 2), (Token::Space, 1),
+            (Token::Ident, 2), (Token::Space, 1), (Token::Lt, 1),
+            (Token::Space, 1), (Token::Int, 2),
+        ]);
+    }
+
+    #[test]
+    fn priority() {
+        // Both match `if` whole, so the first given wins.
+        let keyword_first = lexer();
+        assert_eq!(keyword_first.next_token(&Context::from("if"), 0),
+                   Some((Token::If, 2)));
+        let alpha = Repr::Interval(Interval('a', 'z'));
+        let ident_first = Lexer::new(vec![
+            (Token::Ident, alpha.plus()),
+            (Token::If, Repr::literal("if")),
+        ]).unwrap();
+        assert_eq!(ident_first.next_token(&Context::from("if"), 0),
+                   Some((Token::Ident, 2)));
+        assert_eq!(keyword_first.next_token(&Context::from("?"), 0), None);
+        assert_eq!(Lexer::<char, Token>::new(vec![]).unwrap()
+                       .next_token(&Context::from("if"), 0), None);
+    }
+}
@@ -19,8 +19,18 @@ use repr::{Repr, consts::DIGIT};
 let wh = WORD | '-';
 let re = (wh | '.') * 1.. & '@' & (wh * 1.. & '.') * 1.. & wh * 2..4;
 ```
+
+# Crate features
+
+`std`, on by default, enables matching through `Exec`: `Repr::is_match`,
+`find`, `captures`, `replace_all` and the like, `Options`, `ProgramPool` and
+the `Pattern` impl, all of which pool their caches behind `std::sync`.
+Without it the crate is `#![no_std]` and only needs `alloc`: reprs can still
+be built, parsed, simplified, matched by derivatives and compiled into a
+`Program`.
 */
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![feature(pattern)]
 #![feature(once_cell)]
 #![feature(const_trait_impl)]
@@ -46,24 +56,43 @@ let re = (wh | '.') * 1.. & '@' & (wh * 1.. & '.') * 1.. & wh * 2..4;
 // #![feature(specialization)]
 #![feature(step_trait)]
 
-extern crate alloc; 
+#[macro_use]
+extern crate alloc;
 
 mod backtrack;
+mod bytes;
 mod compile;
 mod context;
 mod conversions;
+mod dfa;
+mod display;
+mod error;
+#[cfg(feature = "std")]
 mod exec;
+mod explain;
+#[cfg(any(test, fuzzing))]
+#[doc(hidden)]
+pub mod harness;
 mod interval;
+mod lexer;
 mod operators;
+#[cfg(feature = "std")]
 mod options;
+mod parse;
 mod partition;
+#[cfg(feature = "std")]
 mod pattern;
 mod pikevm;
+#[cfg(feature = "std")]
 mod pool;
 mod program;
+#[cfg(feature = "std")]
+mod replace;
+mod searcher;
 mod seq;
 mod sparse;
 mod unicode;
+mod visit;
 mod wrappers;
 
 pub mod char;
@@ -73,11 +102,24 @@ pub mod macros;
 pub mod repr;
 
 pub use constants::perl::{DIGIT, WORD};
+pub use compile::Inst;
 pub use context::Context;
+pub use error::{
+    AstError, CompileError, MatchError, ParseError, ParseErrorKind,
+    ProgramError, ReplaceError
+};
 pub use interval::Interval;
-pub use partition::Partition;
+pub use lexer::Lexer;
+pub use partition::{Captures, Match, Partition};
+#[cfg(feature = "std")]
+pub use pool::ProgramPool;
+#[cfg(feature = "std")]
+pub use program::ProgramCell;
+pub use program::{Index, Program};
 pub use crate::repr::{Repr, Integral, Zero};
+pub use searcher::Searcher;
 pub use seq::Seq;
+pub use visit::Visitor;
 
 // #[test]
 // fn datetime() {
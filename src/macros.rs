@@ -42,6 +42,148 @@ macro_rules! xor {
     }
 }
 
+/// Literal pattern
+///
+/// A string literal lowers to `Repr::literal`, a single `One(Seq)` or
+/// `Repr::zero()` for the empty string. An inclusive range of char literals
+/// lowers to an `Interval`, and an inverted one is rejected at compile time
+/// rather than giving an empty interval.
+///
+/// ```
+/// use repr::{repr, Interval, Repr};
+///
+/// assert_eq!(
+///     repr!("abc"),
+///     Repr::one('a').mul(Repr::one('b')).mul(Repr::one('c'))
+/// );
+/// assert_eq!(repr!(""), Repr::zero());
+/// assert_eq!(repr!('a'..='z'), Repr::Interval(Interval('a', 'z')));
+/// ```
+///
+/// ```compile_fail
+/// use repr::repr;
+///
+/// let inverted = repr!('z'..='a');
+/// ```
+#[macro_export]
+macro_rules! repr {
+    ($from:literal ..= $to:literal) => {{
+        const _: () = assert!($from <= $to, "inverted range in `repr!`");
+        ::repr::Repr::Interval(::repr::Interval($from, $to))
+    }};
+    ($str:literal) => {
+        ::repr::Repr::literal($str)
+    };
+}
+
+/// Enum alphabet
+///
+/// Defines a field-less enum along with the `Integral` and `Step` impls that
+/// make it an alphabet, so that reprs can match sequences of it, such as
+/// the tokens of a lexer. Its elements are ordered as the variants are
+/// written, from `MIN`, the first, to `MAX`, the last, and any discriminants
+/// given must increase in that order, so that the derived `Ord` agrees.
+/// The macro derives `Clone`, `Copy`, `Debug`, `PartialEq`, `Eq`,
+/// `PartialOrd` and `Ord` itself; other attributes are kept.
+///
+/// `Step` being unstable, the crate using it needs `#![feature(step_trait)]`.
+///
+/// ```
+/// #![feature(step_trait)]
+/// use repr::{integral_enum, Context, Integral, Interval, Repr};
+///
+/// integral_enum! {
+///     pub enum Token { Num, Plus, Minus = 5, Times }
+/// }
+///
+/// assert_eq!((Token::MIN, Token::MAX), (Token::Num, Token::Times));
+/// assert_eq!(Token::Plus.succ(), Token::Minus);
+/// let op = Repr::Interval(Interval(Token::Plus, Token::Times));
+/// let expr = Repr::one(Token::Num).mul(op.mul(Repr::one(Token::Num)).exp());
+/// let tokens = [Token::Num, Token::Times, Token::Num, Token::Minus];
+/// assert!(expr.is_match_at(&Context::from_slice(&tokens[..3]), 0));
+/// assert_eq!(expr.find_in(&Context::from_slice(&tokens), 0, true),
+///            Some((0, 3)));
+/// ```
+#[macro_export]
+macro_rules! integral_enum {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident $(= $discriminant:expr)?),+ $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+        $vis enum $name {
+            $($variant $(= $discriminant)?),+
+        }
+
+        impl $name {
+            const __VARIANTS: &'static [Self] = &[$(Self::$variant),+];
+
+            fn __index(self) -> usize {
+                Self::__VARIANTS.iter().position(|v| *v == self).unwrap()
+            }
+        }
+
+        impl ::core::iter::Step for $name {
+            fn steps_between(start: &Self, end: &Self) -> Option<usize> {
+                end.__index().checked_sub(start.__index())
+            }
+
+            fn forward_checked(start: Self, n: usize) -> Option<Self> {
+                Self::__VARIANTS.get(start.__index().checked_add(n)?).copied()
+            }
+
+            fn backward_checked(start: Self, n: usize) -> Option<Self> {
+                Self::__VARIANTS.get(start.__index().checked_sub(n)?).copied()
+            }
+        }
+
+        impl ::repr::Integral for $name {
+            const MIN: Self = Self::__VARIANTS[0];
+            const MAX: Self = Self::__VARIANTS[Self::__VARIANTS.len() - 1];
+
+            fn succ(self) -> Self {
+                Self::__VARIANTS[self.__index() + 1]
+            }
+
+            fn pred(self) -> Self {
+                Self::__VARIANTS[self.__index() - 1]
+            }
+        }
+    };
+}
+
+/// Compiled pattern
+///
+/// Parses the pattern literal as `Repr::try_from` does and compiles it the
+/// first time the expansion is evaluated, into a `Program` kept in a static
+/// `ProgramCell` of its own. Every later evaluation, such as another call of
+/// the function it is in, returns that same `&'static Program<char>`
+/// without compiling again.
+///
+/// Panics on first use if the pattern doesn't parse or compile.
+///
+/// ```
+/// use repr::{program, Program};
+///
+/// fn year() -> &'static Program<char> {
+///     program!(r"\d{4}")
+/// }
+///
+/// assert!(std::ptr::eq(year(), year()));
+/// assert!(!year().is_empty());
+/// ```
+#[macro_export]
+macro_rules! program {
+    ($pattern:literal) => {{
+        static PROGRAM: ::repr::ProgramCell = ::repr::ProgramCell::new();
+        PROGRAM.get($pattern)
+    }};
+}
+
 // /// Interval
 // #[macro_export]
 // macro_rules! interval {
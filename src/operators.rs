@@ -1,4 +1,7 @@
-use core::ops::{BitOr, BitAnd, BitXor, Range, Mul, RangeFull, RangeFrom};
+use core::ops::{
+    BitOr, BitAnd, BitXor, Range, RangeInclusive, Mul, Not, RangeFull,
+    RangeFrom, Sub
+};
 
 use unconst::unconst;
 
@@ -48,6 +51,15 @@ impl<I: ~const Integral> const BitAnd<Range<I>> for Repr<I> {
     }
 }
 
+#[unconst]
+impl<I: ~const Integral> const BitAnd<RangeInclusive<I>> for Repr<I> {
+    type Output = Self;
+
+    fn bitand(self, rhs: RangeInclusive<I>) -> Self::Output {
+        self.and(Repr::Interval(rhs.into()))
+    }
+}
+
 #[unconst]
 impl<I: ~const Integral, T: Into<Self>> const BitAnd<[T; 1]> for Repr<I> {
     type Output = Self;
@@ -100,6 +112,15 @@ impl<I: ~const Integral> const BitOr<Range<I>> for Repr<I> {
     }
 }
 
+#[unconst]
+impl<I: ~const Integral> const BitOr<RangeInclusive<I>> for Repr<I> {
+    type Output = Self;
+
+    fn bitor(self, rhs: RangeInclusive<I>) -> Self {
+        self.or(Repr::Interval(rhs.into()))
+    }
+}
+
 #[unconst]
 impl<I: ~const Integral, T: Into<Self>> const BitOr<[T; 1]> for Repr<I> {
     type Output = Self;
@@ -109,6 +130,46 @@ impl<I: ~const Integral, T: Into<Self>> const BitOr<[T; 1]> for Repr<I> {
     }
 }
 
+#[unconst]
+/// Set difference, see `Repr::sub`. Like for integers, `-` binds tighter
+/// than `&` and `|` and looser than `*`:
+///
+/// ```
+/// use repr::{Repr, WORD, DIGIT};
+///
+/// // (WORD - DIGIT) | '_'
+/// let re = WORD - DIGIT | '_';
+/// assert!(re.is_match("_"));
+/// assert!(!re.is_match("7"));
+/// ```
+impl<I: ~const Integral> const Sub<Self> for Repr<I> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Repr::sub(self, rhs)
+    }
+}
+
+#[unconst]
+/// Complement, see `Repr::not`. It is taken over words, not characters, so
+/// `!DIGIT` also matches the empty word and every word of two or more
+/// characters, and a search finds it anywhere:
+///
+/// ```
+/// use repr::DIGIT;
+///
+/// assert!((!DIGIT).is_full_match("a"));
+/// assert!(!(!DIGIT).is_full_match("7"));
+/// assert!((!DIGIT).is_match("7"));
+/// ```
+impl<I: ~const Integral> const Not for Repr<I> {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Repr::not(self)
+    }
+}
+
 #[unconst]
 impl<I: ~const Integral> const Mul<RangeFull> for Repr<I> {
     type Output = Self;
@@ -1,5 +1,6 @@
 use unconst::unconst;
 
+use crate::error::CompileError;
 use crate::exec::{Exec, ExecBuilder};
 use crate::repr::{Repr, Integral};
 
@@ -30,6 +31,35 @@ pub struct Options<I: ~const Integral> {
     /// simultaneously, then each thread may use up to the number of bytes
     /// specified here.
     pub dfa_size_limit: usize,
+    /// Set the maximum number of capture groups.
+    ///
+    /// Each group costs two slots in every thread of the matching engines,
+    /// so for untrusted patterns this bounds that memory along with
+    /// `size_limit`. If the pattern has more groups, then a compilation
+    /// error is returned.
+    ///
+    /// By default, there is no limit.
+    pub max_captures: usize,
+    /// Set whether capture groups are compiled.
+    ///
+    /// When disabled, groups match as their contents do but report no
+    /// spans, which saves their slots in every thread of the matching
+    /// engines. Searches that only ask whether there is a match, or for the
+    /// span of the whole match, find the same results.
+    ///
+    /// By default, it is enabled.
+    pub captures: bool,
+    /// Set the most steps a matching engine may take in one run.
+    ///
+    /// A step is an instruction followed by the backtracker, or a thread
+    /// stepped or added by the PikeVM. Past it, a search is abandoned,
+    /// which `Exec::try_find_at` and `Exec::try_is_match_at` report as
+    /// `MatchError::StepLimitExceeded`, and the other searches as no match.
+    /// This bounds the time spent on untrusted patterns. The DFA, which
+    /// runs in linear time, isn't limited.
+    ///
+    /// By default, there is no limit.
+    pub step_limit: usize,
     /// Set the nesting limit for this parser.
     ///
     /// The nesting limit controls how deep the abstract syntax tree is allowed
@@ -88,6 +118,41 @@ pub struct Options<I: ~const Integral> {
     /// between expressions of the pattern, and `#` can be used to start a
     /// comment until the next new line.
     pub ignore_whitespace: bool,
+    /// Set whether matches are leftmost-longest (POSIX) instead of
+    /// leftmost-first.
+    ///
+    /// When enabled, among the matches starting at the leftmost position the
+    /// longest is reported, so `a|ab` finds `ab` in `ab` rather than `a`.
+    ///
+    /// By default, the first match in priority order is reported, where the
+    /// left operand of `Or` is preferred and `Exp` is greedy.
+    pub longest_match: bool,
+    /// Set whether the input is promised to be ASCII.
+    ///
+    /// When enabled, Unicode word boundaries are compiled as ASCII word
+    /// boundaries, which find the same matches in ASCII input but don't
+    /// consult the Unicode tables, making them cheaper on large inputs such
+    /// as logs. Input with non-ASCII word characters gets different results.
+    ///
+    /// By default, word boundaries are Unicode-aware.
+    pub ascii: bool,
+    /// Set the character ending lines.
+    ///
+    /// `^` and `$` in multi-line mode match after and before it, and `.`
+    /// built by `Repr::dot` doesn't match it. Input with `\r\n` line
+    /// endings can set `'\r'`, leaving the `'\n'` to start each line.
+    ///
+    /// By default, it is `'\n'`.
+    pub line_terminator: char,
+    /// Set whether lines end with `\r\n`, `\r` or `\n`.
+    ///
+    /// When enabled, `line_terminator` is ignored, `.` built by `Repr::dot`
+    /// matches neither `\r` nor `\n`, and `^` and `$` in multi-line mode
+    /// match at either, but never between the `\r` and the `\n` of a
+    /// `\r\n`, so that an empty line isn't found inside it.
+    ///
+    /// By default, it is disabled.
+    pub crlf: bool,
 }
 
 #[unconst]
@@ -101,11 +166,18 @@ impl<I: ~const Integral> Options<I> {
             repr,
             size_limit: 10 * (1 << 20),
             dfa_size_limit: 2 * (1 << 20),
+            max_captures: usize::MAX,
+            captures: true,
+            step_limit: usize::MAX,
             nest_limit: 250,
             multi_line: false,
             dot_matches_new_line: false,
             swap_greed: false,
             ignore_whitespace: false,
+            longest_match: false,
+            ascii: false,
+            line_terminator: '\n',
+            crlf: false,
         }
     }
 
@@ -114,7 +186,10 @@ impl<I: ~const Integral> Options<I> {
     /// Note that calling `as_str` on the resulting `Regex` will produce the
     /// pattern given to `new` verbatim. Notably, it will not incorporate any
     /// of the flags set on this builder.
-    pub fn build(self) -> Exec<I> {
+    ///
+    /// An error is returned if the pattern cannot be compiled with the
+    /// enabled crate features.
+    pub fn build(self) -> Result<Exec<I>, CompileError> {
         ExecBuilder::new(self).build()
     }
 }
@@ -0,0 +1,505 @@
+//! Parsing of a conventional regex syntax into `Repr<char>`.
+//!
+//! The supported syntax is literals, `.`, classes `[...]` and `[^...]`,
+//! repetitions `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}` with counts up to
+//! `REPETITION_LIMIT`, the lazy `*?`, alternation `|`, groups `(...)`,
+//! named capture groups `(?P<name>...)` or `(?<name>...)`, the anchors `^`
+//! and `$`, and the escapes `\d`, `\w`, `\s` (all ASCII), their negations
+//! `\D`, `\W`, `\S`, `\n`, `\r`, `\t`, the code points `\xHH` and
+//! `\x{H...}`, and any escaped ASCII punctuation. Groups nest at most
+//! `NEST_LIMIT` deep, and a counted repetition expands to at most
+//! `SIZE_LIMIT` nodes.
+
+use alloc::{string::String, vec::Vec};
+
+use crate::error::{ParseError, ParseErrorKind};
+use crate::interval::Interval;
+use crate::repr::{Integral, Repr, Zero};
+
+/// The largest count of a `{n}`, `{n,}` or `{n,m}`, as the repr repeats
+/// its operand that many times.
+pub const REPETITION_LIMIT: usize = 1000;
+
+/// How deep groups may be nested, as they are parsed by recursion.
+pub const NEST_LIMIT: usize = 250;
+
+/// The most nodes a counted repetition may expand to. Nested ones multiply,
+/// so that `((a{1000}){1000}){1000}` would otherwise take about 10^9.
+pub const SIZE_LIMIT: usize = 1 << 20;
+
+pub fn parse(pattern: &str) -> Result<Repr<char>, ParseError> {
+    let mut parser = Parser {
+        chars: pattern.char_indices().collect(),
+        pos: 0,
+        len: pattern.len(),
+        depth: 0,
+    };
+    let repr = parser.alt()?;
+    match parser.peek() {
+        None => Ok(repr),
+        // `alt` only stops early at a `)`.
+        Some(_) => Err(parser.error(ParseErrorKind::UnopenedGroup)),
+    }
+}
+
+struct Parser {
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    len: usize,
+    /// How many groups the parser is in.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|&(_, c)| c)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Byte offset of the next character, or the length of the pattern at
+    /// its end.
+    fn offset(&self) -> usize {
+        self.chars.get(self.pos).map_or(self.len, |&(offset, _)| offset)
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        ParseError { offset: self.offset(), kind }
+    }
+
+    fn alt(&mut self) -> Result<Repr<char>, ParseError> {
+        let mut repr = self.concat()?;
+        while self.eat('|') {
+            repr = repr.or(self.concat()?);
+        }
+        Ok(repr)
+    }
+
+    fn concat(&mut self) -> Result<Repr<char>, ParseError> {
+        let mut repr = None;
+        while !matches!(self.peek(), None | Some('|') | Some(')')) {
+            let next = self.repeat()?;
+            repr = Some(match repr {
+                None => next,
+                Some(repr) => Repr::mul(repr, next),
+            });
+        }
+        Ok(repr.unwrap_or_else(Repr::zero))
+    }
+
+    fn repeat(&mut self) -> Result<Repr<char>, ParseError> {
+        let mut repr = self.atom()?;
+        loop {
+            repr = match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    if self.eat('?') {
+                        repr.exp_lazy()
+                    } else {
+                        repr.exp()
+                    }
+                }
+                Some('+') => {
+                    self.pos += 1;
+                    repr.plus()
+                }
+                Some('?') => {
+                    self.pos += 1;
+                    repr.opt()
+                }
+                Some('{') => {
+                    let offset = self.offset();
+                    let (min, max) = self.range()?;
+                    let count = max.unwrap_or(min) + 1;
+                    if repr.node_count().saturating_mul(count) > SIZE_LIMIT {
+                        return Err(ParseError {
+                            offset,
+                            kind: ParseErrorKind::SizeLimitExceeded
+                        });
+                    }
+                    repr.repeat_range(min, max)
+                }
+                _ => return Ok(repr),
+            };
+        }
+    }
+
+    /// Parses `{n}`, `{n,}` or `{n,m}`.
+    fn range(&mut self) -> Result<(usize, Option<usize>), ParseError> {
+        let error = self.error(ParseErrorKind::InvalidRepetition);
+        self.pos += 1;
+        let min = self.number().ok_or_else(|| error.clone())?;
+        let max = if self.eat(',') { self.number() } else { Some(min) };
+        if !self.eat('}') || max.map_or(false, |max| max < min)
+            || max.unwrap_or(min) > REPETITION_LIMIT
+        {
+            return Err(error);
+        }
+        Ok((min, max))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let mut number: Option<usize> = None;
+        while let Some(digit) = self.peek().and_then(|c| c.to_digit(10)) {
+            self.pos += 1;
+            number = Some(
+                number.unwrap_or(0).checked_mul(10)?.checked_add(digit as usize)?
+            );
+        }
+        number
+    }
+
+    fn atom(&mut self) -> Result<Repr<char>, ParseError> {
+        let offset = self.offset();
+        match self.bump() {
+            Some('.') => Ok(Repr::dot()),
+            Some('^') => Ok(Repr::Zero(Zero::StartText)),
+            Some('$') => Ok(Repr::Zero(Zero::EndText)),
+            Some('(') => {
+                if self.depth == NEST_LIMIT {
+                    return Err(ParseError {
+                        offset,
+                        kind: ParseErrorKind::NestLimitExceeded
+                    });
+                }
+                let name = self.group_name(offset)?;
+                self.depth += 1;
+                let repr = self.alt();
+                self.depth -= 1;
+                let repr = repr?;
+                if !self.eat(')') {
+                    return Err(ParseError {
+                        offset,
+                        kind: ParseErrorKind::UnclosedGroup
+                    });
+                }
+                Ok(match name {
+                    Some(name) => repr.cap(&name),
+                    None => repr,
+                })
+            }
+            Some('[') => self.class(offset),
+            Some('\\') => Ok(union(self.escape(offset)?)),
+            Some('*' | '+' | '?' | '{') => Err(ParseError {
+                offset,
+                kind: ParseErrorKind::RepetitionMissing
+            }),
+            Some(c) => Ok(Repr::one(c)),
+            None => unreachable!("`concat` stops at the end of the pattern"),
+        }
+    }
+
+    /// Parses the `?P<name>` or `?<name>` after the `(` at `offset`, if
+    /// any. A name is an ASCII letter or `_` followed by ASCII letters,
+    /// digits and `_`.
+    fn group_name(&mut self, offset: usize)
+        -> Result<Option<String>, ParseError>
+    {
+        if !self.eat('?') {
+            return Ok(None);
+        }
+        self.eat('P');
+        let error = ParseError { offset, kind: ParseErrorKind::InvalidGroupName };
+        if !self.eat('<') {
+            return Err(error);
+        }
+        let mut name = String::new();
+        while let Some(c) = self.bump() {
+            match c {
+                '>' if !name.is_empty() => return Ok(Some(name)),
+                'a'..='z' | 'A'..='Z' | '_' => name.push(c),
+                '0'..='9' if !name.is_empty() => name.push(c),
+                _ => break,
+            }
+        }
+        Err(error)
+    }
+
+    /// Parses what follows the `\` at `offset`.
+    fn escape(&mut self, offset: usize)
+        -> Result<Vec<Interval<char>>, ParseError>
+    {
+        let class = match self.bump() {
+            Some('d') => digit(),
+            Some('w') => word(),
+            Some('s') => space(),
+            Some('D') => negate(digit()),
+            Some('W') => negate(word()),
+            Some('S') => negate(space()),
+            Some('n') => vec![Interval('\n', '\n')],
+            Some('r') => vec![Interval('\r', '\r')],
+            Some('t') => vec![Interval('\t', '\t')],
+            Some('x') => match self.hex() {
+                Some(c) => vec![Interval(c, c)],
+                None => return Err(ParseError {
+                    offset,
+                    kind: ParseErrorKind::InvalidEscape
+                }),
+            },
+            Some(c) if c.is_ascii_punctuation() => vec![Interval(c, c)],
+            _ => return Err(ParseError {
+                offset,
+                kind: ParseErrorKind::InvalidEscape
+            }),
+        };
+        Ok(class)
+    }
+
+    /// Parses the code point of a `\x` escape, two hex digits or any number
+    /// of them in braces.
+    fn hex(&mut self) -> Option<char> {
+        let digits: String = if self.eat('{') {
+            let mut digits = String::new();
+            loop {
+                match self.bump()? {
+                    '}' => break digits,
+                    c => digits.push(c),
+                }
+            }
+        } else {
+            [self.bump()?, self.bump()?].iter().collect()
+        };
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        char::from_u32(u32::from_str_radix(&digits, 16).ok()?)
+    }
+
+    /// Parses what follows the `[` at `offset`.
+    fn class(&mut self, offset: usize) -> Result<Repr<char>, ParseError> {
+        let negated = self.eat('^');
+        let mut intervals = Vec::new();
+        // A `]` right after the `[` (or `[^`) is a literal.
+        let mut first = true;
+        loop {
+            let at = self.offset();
+            let from = match self.bump() {
+                None => return Err(ParseError {
+                    offset,
+                    kind: ParseErrorKind::UnclosedClass
+                }),
+                Some(']') if !first => break,
+                Some('\\') => match self.escape(at)?[..] {
+                    [Interval(from, to)] if from == to => from,
+                    ref class => {
+                        intervals.extend_from_slice(class);
+                        first = false;
+                        continue;
+                    }
+                },
+                Some(c) => c,
+            };
+            first = false;
+            let to = match (self.peek(), self.chars.get(self.pos + 1)) {
+                (Some('-'), Some(&(_, next))) if next != ']' => {
+                    self.pos += 2;
+                    match next {
+                        '\\' => match self.escape(at)?[..] {
+                            [Interval(to, _)] => to,
+                            _ => return Err(ParseError {
+                                offset: at,
+                                kind: ParseErrorKind::InvalidRange
+                            }),
+                        },
+                        next => next,
+                    }
+                }
+                _ => from,
+            };
+            if to < from {
+                return Err(ParseError {
+                    offset: at,
+                    kind: ParseErrorKind::InvalidRange
+                });
+            }
+            intervals.push(Interval(from, to));
+        }
+        // Either way, the class is a few disjoint intervals, not a `Not`.
+        intervals = if negated { negate(intervals) } else { merge(intervals) };
+        if intervals.is_empty() {
+            return Err(ParseError { offset, kind: ParseErrorKind::EmptyClass });
+        }
+        Ok(union(intervals))
+    }
+}
+
+fn digit() -> Vec<Interval<char>> {
+    vec![Interval('0', '9')]
+}
+
+fn word() -> Vec<Interval<char>> {
+    vec![
+        Interval('0', '9'),
+        Interval('A', 'Z'),
+        Interval('_', '_'),
+        Interval('a', 'z'),
+    ]
+}
+
+fn space() -> Vec<Interval<char>> {
+    vec![Interval('\t', '\r'), Interval(' ', ' ')]
+}
+
+/// The intervals covering every `char` not in `intervals`.
+pub(crate) fn negate(mut intervals: Vec<Interval<char>>) -> Vec<Interval<char>> {
+    intervals.sort();
+    let mut negated = Vec::new();
+    // The least `char` not known to be covered yet, if any.
+    let mut next = Some(<char as Integral>::MIN);
+    for Interval(from, to) in intervals {
+        next = match next {
+            None => None,
+            Some(next) if to < next => Some(next),
+            Some(next) => {
+                if next < from {
+                    negated.push(Interval(next, from.pred()));
+                }
+                (to != <char as Integral>::MAX).then(|| to.succ())
+            }
+        };
+    }
+    if let Some(next) = next {
+        negated.push(Interval(next, <char as Integral>::MAX));
+    }
+    negated
+}
+
+/// Sorts `intervals`, merging those that overlap or are next to each other.
+pub(crate) fn merge(mut intervals: Vec<Interval<char>>)
+    -> Vec<Interval<char>>
+{
+    intervals.sort();
+    let mut merged: Vec<Interval<char>> = Vec::new();
+    for Interval(from, to) in intervals {
+        match merged.last_mut() {
+            Some(last) if last.1 == <char as Integral>::MAX
+                || from <= last.1.succ() => last.1 = last.1.max(to),
+            _ => merged.push(Interval(from, to)),
+        }
+    }
+    merged
+}
+
+pub(crate) fn union(intervals: Vec<Interval<char>>) -> Repr<char> {
+    Repr::any(intervals.into_iter().map(|Interval(from, to)| {
+        if from == to {
+            Repr::one(from)
+        } else {
+            Repr::Interval(Interval(from, to))
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(pattern: &str) -> Repr<char> {
+        Repr::try_from(pattern).unwrap()
+    }
+
+    fn error(pattern: &str) -> ParseError {
+        Repr::try_from(pattern).unwrap_err()
+    }
+
+    #[test]
+    fn constructors() {
+        let digit = Repr::Interval(Interval('0', '9'));
+        assert_eq!(parse("abc"), Repr::literal("abc"));
+        assert_eq!(parse("a|"), Repr::one('a').or(Repr::zero()));
+        assert_eq!(parse("."), Repr::dot());
+        assert_eq!(parse(r"\d+"), digit.clone().plus());
+        assert_eq!(parse(r"\d{2,}"), digit.clone().repeat_range(2, None));
+        assert_eq!(parse("(ab)?$"), Repr::literal("ab").opt()
+            .mul(Repr::Zero(Zero::EndText)));
+        assert_eq!(parse("[a-c_]"), Repr::Interval(Interval('a', 'c'))
+            .or(Repr::one('_')));
+        assert_eq!(parse(r"[\]]"), Repr::one(']'));
+        assert_eq!(parse(r"(?P<year>\d{4})-(?<month>\d\d)"),
+                   digit.clone().repeat(4).cap("year").mul(Repr::one('-'))
+                       .mul(digit.clone().mul(digit).cap("month")));
+    }
+
+    #[test]
+    fn matching() {
+        let date = parse(r"^\d{4}-\d{2}-\d{2}$");
+        assert!(date.is_match("2014-01-01"));
+        assert!(!date.is_match("2014-01-011"));
+
+        let email = parse(r"[\w.-]+@(\w+\.)+[a-z]{2,4}");
+        assert!(email.is_match("jane.doe@example.org"));
+        assert!(!email.is_match("jane.doe@example"));
+
+        let not_vowel = parse("^[^aeiou]*$");
+        assert!(not_vowel.is_match("rhythm"));
+        assert!(!not_vowel.is_match("rhyme"));
+    }
+
+    #[test]
+    fn classes() {
+        let max = <char as Integral>::MAX;
+        let not_digit = parse("[^0-9]");
+        assert_eq!(not_digit, Repr::Interval(Interval('\0', '/'))
+                              .or(Repr::Interval(Interval(':', max))));
+        assert!(not_digit.is_match("a"));
+        assert!(not_digit.is_match("\n"));
+        assert!(!not_digit.is_match("5"));
+        assert_eq!(parse("[a-cb-d]"), Repr::Interval(Interval('a', 'd')));
+        assert_eq!(parse("[^a-cb-d]"), parse("[^a-d]"));
+    }
+
+    #[test]
+    fn errors() {
+        use ParseErrorKind::*;
+
+        assert_eq!(error("(a"), ParseError { offset: 0, kind: UnclosedGroup });
+        assert_eq!(error("a)"), ParseError { offset: 1, kind: UnopenedGroup });
+        assert_eq!(error("é[a"), ParseError { offset: 2, kind: UnclosedClass });
+        assert_eq!(error("[z-a]"), ParseError { offset: 1, kind: InvalidRange });
+        assert_eq!(error("*a"), ParseError { offset: 0, kind: RepetitionMissing });
+        assert_eq!(error("a{3,2}"), ParseError { offset: 1, kind: InvalidRepetition });
+        assert_eq!(error("a{,2}"), ParseError { offset: 1, kind: InvalidRepetition });
+        assert_eq!(error("a{1001}"), ParseError { offset: 1, kind: InvalidRepetition });
+        assert_eq!(error("a{2,1001}"),
+                   ParseError { offset: 1, kind: InvalidRepetition });
+        assert_eq!(error("a{1001,}"),
+                   ParseError { offset: 1, kind: InvalidRepetition });
+        assert!(parse("a{1000}").is_ok());
+        assert!(parse("(a{100}){100}").is_ok());
+        assert_eq!(error("((a{1000}){1000}){1000}"),
+                   ParseError { offset: 10, kind: SizeLimitExceeded });
+        let deep = "(".repeat(NEST_LIMIT) + &")".repeat(NEST_LIMIT);
+        assert!(parse(&deep).is_ok());
+        assert_eq!(error(&"(".repeat(100_000)),
+                   ParseError { offset: NEST_LIMIT, kind: NestLimitExceeded });
+        assert_eq!(error(r"\q"), ParseError { offset: 0, kind: InvalidEscape });
+        assert_eq!(error(r"[^\s\S]"), ParseError { offset: 0, kind: EmptyClass });
+        assert_eq!(error("a(?P<1>b)"), ParseError { offset: 1, kind: InvalidGroupName });
+        assert_eq!(error("(?:a)"), ParseError { offset: 0, kind: InvalidGroupName });
+        assert_eq!(error("(?<a"), ParseError { offset: 0, kind: InvalidGroupName });
+    }
+
+    #[test]
+    fn hex() {
+        assert_eq!(parse(r"\x41\x{e9}"), Repr::literal("Aé"));
+        assert_eq!(parse(r"[\x00-\x{10FFFF}]"),
+                   Repr::Interval(Interval('\0', '\u{10FFFF}')));
+        for pattern in [r"\x4", r"\x4g", r"\x{}", r"\x{D800}", r"\x{41"] {
+            assert_eq!(error(pattern).kind, ParseErrorKind::InvalidEscape,
+                       "{}", pattern);
+        }
+    }
+}
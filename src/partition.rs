@@ -1,4 +1,7 @@
-use alloc::vec::IntoIter;
+use alloc::{
+    string::String,
+    vec::{IntoIter, Vec},
+};
 use core::{
     iter::{Enumerate, FusedIterator},
     slice::Iter
@@ -7,6 +10,10 @@ use core::{
 use unconst::unconst;
 
 use crate::context::Context;
+#[cfg(feature = "std")]
+use crate::error::MatchError;
+#[cfg(feature = "std")]
+use crate::exec::Exec;
 use crate::repr::{Repr, Integral};
 
 
@@ -16,21 +23,48 @@ use crate::repr::{Repr, Integral};
 /// The iterator yields a `Match` value. The iterator stops when no more
 /// matches can be found.
 ///
-/// `'r` is the lifetime of the compiled regular expression and `'c` is the
-/// lifetime of the matched string.
-#[derive_const(PartialEq)]
-#[derive(Debug, Eq)]
+/// `'c` is the lifetime of the matched context, which every search borrows
+/// rather than copies. The repr is compiled on the first call to `next`,
+/// which panics if it can't be, or by `try_new`, and the same `Exec`, along
+/// with its caches, runs every search after it.
+#[derive(Debug)]
 pub struct Partition<'c, I: ~const Integral> {
     context: &'c Context<I>,
     repr: Repr<I>,
-    last_end: usize,
-    last_match: Option<usize>,
+    cursor: Cursor,
+    #[cfg(feature = "std")]
+    exec: Option<Exec<I>>,
 }
 
 #[unconst]
 impl<'c, I: ~const Integral> Partition<'c, I> {
-    pub const fn new(context: &Context<I>, repr: Repr<I>) -> Self {
-        Partition { context, repr, last_end: 0, last_match: None }
+    /// Create an iterator over the matches of `repr` in `context`, compiling
+    /// `repr` on the first call to `next`.
+    ///
+    /// # Panics
+    ///
+    /// The first call to `next` panics if `repr` can't be compiled, see
+    /// `From<CompileError> for MatchError`. To handle that error instead,
+    /// use `try_new`.
+    pub const fn new(context: &'c Context<I>, repr: Repr<I>) -> Self {
+        Partition {
+            context,
+            repr,
+            cursor: Cursor::new(),
+            #[cfg(feature = "std")]
+            exec: None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `new`, but compiles `repr` right away, returning why if it can't
+    /// be, see `From<CompileError> for MatchError`, rather than panicking
+    /// in `next`.
+    pub fn try_new(context: &'c Context<I>, repr: Repr<I>)
+        -> Result<Self, MatchError>
+    {
+        let exec = Exec::new(repr.clone())?;
+        Ok(Partition { context, repr, cursor: Cursor::new(), exec: Some(exec) })
     }
 
     /// Return the context being searched.
@@ -44,18 +78,47 @@ impl<'c, I: ~const Integral> Partition<'c, I> {
     }
 }
 
+#[cfg(feature = "std")]
 #[unconst]
 impl<'c, I: ~const Integral> Iterator for Partition<'c, I> {
     type Item = (usize, usize);
 
     fn next(&mut self) -> Option<(usize, usize)> {
-        if self.last_end > self.context.len() {
-            return None;
+        let repr = &self.repr;
+        let exec = self.exec
+            .get_or_insert_with(|| Exec::new(repr.clone()).unwrap());
+        while self.cursor.last_end <= self.context.len() {
+            let m = exec.find_at(self.context, self.cursor.last_end)?;
+            if self.cursor.advance(m.start(), m.end()) {
+                return Some((m.start(), m.end()));
+            }
         }
-        let (start, end) = match self.repr.find_at(self.context, self.last_end) {
-            None => return None,
-            Some((start, end)) => (start, end),
-        };
+        None
+    }
+}
+
+#[unconst]
+/// The position a search for the next non-overlapping match starts from,
+/// shared by everything that walks through the matches of a context.
+#[derive_const(PartialEq)]
+#[derive(Clone, Copy, Debug, Eq)]
+pub struct Cursor {
+    /// Where the next search starts. Once past the end of the context,
+    /// there are no more matches.
+    pub last_end: usize,
+    last_match: Option<usize>,
+}
+
+#[unconst]
+impl Cursor {
+    pub const fn new() -> Self {
+        Cursor { last_end: 0, last_match: None }
+    }
+
+    /// Moves past the match from `start` to `end`, and returns false if it
+    /// is to be skipped, which is the case of an empty match immediately
+    /// following the previous match.
+    pub const fn advance(&mut self, start: usize, end: usize) -> bool {
         if start == end {
             // This is an empty match. To ensure we make progress, start
             // the next search at the smallest possible starting position
@@ -64,16 +127,17 @@ impl<'c, I: ~const Integral> Iterator for Partition<'c, I> {
             // Don't accept empty matches immediately following a match.
             // Just move on to the next match.
             if Some(end) == self.last_match {
-                return self.next();
+                return false;
             }
         } else {
             self.last_end = end;
         }
         self.last_match = Some(end);
-        Some((start, end))
+        true
     }
 }
 
+#[cfg(feature = "std")]
 #[unconst]
 impl<'c, I: ~const Integral> FusedIterator for Partition<'c, I> {}
 
@@ -97,6 +161,18 @@ impl<'c, I: ~const Integral> Match<'c, I> {
         Match { context, start, end }
     }
 
+    /// Returns the starting position of the match in the context.
+    #[inline]
+    pub const fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Returns the ending position of the match in the context.
+    #[inline]
+    pub const fn end(&self) -> usize {
+        self.end
+    }
+
     /// Returns the matched text.
     #[inline]
     pub const fn as_slice(&self) -> &'c [I] {
@@ -104,6 +180,51 @@ impl<'c, I: ~const Integral> Match<'c, I> {
     }
 }
 
+#[unconst]
+/// The spans of the capture groups of a single match. Group 0 is the whole
+/// match, and the named groups are numbered from 1 as `Repr::cap` describes.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Captures<'c, I: ~const Integral> {
+    context: &'c Context<I>,
+    /// The start and end of each group, in turn.
+    slots: Vec<Option<usize>>,
+    /// The names of the groups after the first.
+    names: Vec<String>,
+}
+
+#[unconst]
+impl<'c, I: ~const Integral> Captures<'c, I> {
+    pub const fn new(
+        context: &'c Context<I>,
+        slots: Vec<Option<usize>>,
+        names: Vec<String>,
+    ) -> Self {
+        Captures { context, slots, names }
+    }
+
+    /// Returns the match of group `i`, or `None` if there is no such group
+    /// or it didn't participate in the match.
+    pub const fn get(&self, i: usize) -> Option<Match<'c, I>> {
+        match (self.slots.get(2 * i)?, self.slots.get(2 * i + 1)?) {
+            (&Some(start), &Some(end))
+                => Some(Match::new(self.context, start, end)),
+            _ => None,
+        }
+    }
+
+    /// Returns the match of the group named `name`, or `None` if there is
+    /// no such group or it didn't participate in the match.
+    pub const fn name(&self, name: &str) -> Option<Match<'c, I>> {
+        let i = self.names.iter().position(|n| n == name)?;
+        self.get(i + 1)
+    }
+
+    /// Returns the number of groups, including the whole match.
+    pub const fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+}
+
 
 /// A set of matches returned by a regex set.
 #[derive(Clone, Debug)]
@@ -17,16 +17,20 @@ Therefore, the Pike VM is generally treated as the fallback when the other
 matching engines either aren't feasible to run or are insufficient.
 */
 
+use alloc::vec::Vec;
 use core::mem;
 
 use unconst::unconst;
 
 use crate::repr::Integral;
 use crate::context::Context;
-use crate::exec::ProgramCache;
-use crate::program::{Index, Program, Inst};
+use crate::program::ProgramCache;
+use crate::program::{Index, Program, ProgramCacheInner, Inst, Steps};
 use crate::sparse::SparseSet;
 
+/// A position in the input recorded by a thread, if any.
+pub type Slot = Option<usize>;
+
 /// An NFA simulation matching engine.
 #[derive(Debug)]
 pub struct Fsm<'r, I: Integral> {
@@ -36,24 +40,56 @@ pub struct Fsm<'r, I: Integral> {
     prog: &'r Program<I>,
     /// An explicit stack used for following epsilon transitions. (This is
     /// borrowed from the cache.)
-    stack: &'r mut Vec<Index>,
+    stack: &'r mut Vec<FollowEpsilon>,
     /// The context to search.
-    context: Context<I>,
+    context: &'r Context<I>,
+    /// Whether a match may only start at the position the search starts
+    /// at, either because the program says so or because the caller asked.
+    anchored: bool,
+    /// The states inside literal runs, see `Cache::runs`.
+    runs: &'r [(Index, usize)],
+    /// The steps taken, against `Program::step_limit`.
+    steps: &'r mut Steps,
 }
 
-type Thread = SparseSet<usize>;
-
 /// A cached allocation that can be reused on each execution.
 #[derive(Clone, Debug)]
 pub struct Cache {
-    /// A pair of ordered sets of opcodes (each opcode is an NFA state) for tracking NFA states.
-    clist: Thread,
-    nlist: Thread,
+    /// A pair of ordered sets of threads (NFA states and their slots) for
+    /// the current and the next position in the input.
+    clist: Threads,
+    nlist: Threads,
     /// An explicit stack used for following epsilon transitions.
-    /// A representation of an explicit stack frame when following epsilon
-    /// transitions. This is used to avoid recursion.
+    stack: Vec<FollowEpsilon>,
+    /// A `One` instruction matches a literal run of several elements, so a
+    /// thread may be partway through one. Such a thread is in a state of its
+    /// own, `(ip, k)` for `k` elements in, numbered after the instructions in
+    /// the order of this list. A thread at the start of an instruction is
+    /// simply in state `ip`.
+    runs: Vec<(Index, usize)>,
+}
+
+/// An explicit stack frame used for following epsilon transitions.
+#[derive(Clone, Debug)]
+enum FollowEpsilon {
     /// Follow transitions at the given instruction pointer.
-    stack: Vec<Index>,
+    IP(Index),
+    /// Restore the capture slot with the given position in the input.
+    Capture { slot: usize, pos: Slot },
+}
+
+/// An ordered set of NFA states along with the slots of the thread in each.
+///
+/// The slots of every thread live in one flat buffer, `slots_per_thread`
+/// entries per state, so spawning a thread copies into it and a dying
+/// thread is simply overwritten, instead of each thread owning a `Vec`. Since
+/// the buffer belongs to the `Cache`, which is taken from the pool of the
+/// `Exec`, it is also reused across executions.
+#[derive(Clone, Debug)]
+struct Threads {
+    set: SparseSet<usize>,
+    slots: Vec<Slot>,
+    slots_per_thread: usize,
 }
 
 #[unconst]
@@ -62,10 +98,36 @@ impl Cache {
     /// and captures.
     pub const fn new<I: Integral>(_prog: &Program<I>) -> Self {
         Cache {
-            clist: Thread::new(0),
-            nlist: Thread::new(0),
-            stack: Vec::new()
+            clist: Threads::new(),
+            nlist: Threads::new(),
+            stack: Vec::new(),
+            runs: Vec::new(),
+        }
+    }
+}
+
+impl Threads {
+    fn new() -> Self {
+        Threads { set: SparseSet::new(0), slots: vec![], slots_per_thread: 0 }
+    }
+
+    /// Makes room for `num_states` threads of `slots_per_thread` slots,
+    /// reallocating only when either changes.
+    fn resize(&mut self, num_states: usize, slots_per_thread: usize) {
+        if num_states == self.set.capacity()
+            && slots_per_thread == self.slots_per_thread
+        {
+            return;
         }
+        self.set = SparseSet::new(num_states);
+        self.slots_per_thread = slots_per_thread;
+        self.slots = vec![None; num_states * slots_per_thread];
+    }
+
+    /// The slots of the thread in state `state`.
+    fn slots(&mut self, state: usize) -> &mut [Slot] {
+        let i = state * self.slots_per_thread;
+        &mut self.slots[i..i + self.slots_per_thread]
     }
 }
 
@@ -74,47 +136,103 @@ impl<'r, I: ~const Integral> Fsm<'r, I> {
     /// Execute the NFA matching engine.
     ///
     /// If there's a match, `exec` returns `true` and populates the given
-    /// captures accordingly.
+    /// slots accordingly: the first is the start of the match and the
+    /// second its end. `slots` may be empty if positions aren't needed.
     pub fn exec(
         prog: &'r Program<I>,
         cache: &ProgramCache<I>,
         matches: &mut [bool],
+        slots: &mut [Slot],
         quit_after_match: bool,
-        context: Context<I>,
+        context: &'r Context<I>,
         start: usize,
         end: usize,
+    ) -> bool {
+        Self::exec_anchored(prog, cache, matches, slots, quit_after_match,
+                            context, start, end, prog.is_anchored_start)
+    }
+
+    /// Like `exec`, but if `anchored` is true, a match may only start at
+    /// `start` whether or not the program is anchored.
+    pub fn exec_anchored(
+        prog: &'r Program<I>,
+        cache: &ProgramCache<I>,
+        matches: &mut [bool],
+        slots: &mut [Slot],
+        quit_after_match: bool,
+        context: &'r Context<I>,
+        start: usize,
+        end: usize,
+        anchored: bool,
     ) -> bool {
         let mut cache = cache.borrow_mut();
-        let cache = &mut cache.pikevm;
-        cache.clist.resize(prog.len());
-        cache.nlist.resize(prog.len());
-        let at = context[start];
-        Fsm { prog, stack: &mut cache.stack, context }.exec_(
+        let ProgramCacheInner { pikevm: cache, steps, .. } = &mut *cache;
+        cache.runs.clear();
+        for (ip, inst) in prog.iter().enumerate() {
+            if let Inst::One { ref seq, .. } = *inst {
+                cache.runs.extend((1..seq.len()).map(|k| (ip, k)));
+            }
+        }
+        let num_states = prog.len() + cache.runs.len();
+        cache.clist.resize(num_states, slots.len());
+        cache.nlist.resize(num_states, slots.len());
+        let anchored = anchored || prog.is_anchored_start;
+        Fsm {
+            prog,
+            stack: &mut cache.stack,
+            context,
+            anchored,
+            runs: &cache.runs,
+            steps,
+        }.exec_(
             &mut cache.clist,
             &mut cache.nlist,
             matches,
+            slots,
             quit_after_match,
-            at,
+            start,
             end,
         )
     }
 
+    /// Returns the start and end positions of the leftmost-first match.
+    pub fn find(
+        prog: &'r Program<I>,
+        cache: &ProgramCache<I>,
+        context: &'r Context<I>,
+        start: usize,
+        end: usize,
+        anchored: bool,
+    ) -> Option<(usize, usize)> {
+        let mut slots = [None, None];
+        if Self::exec_anchored(prog, cache, &mut [], &mut slots, false,
+                               context, start, end, anchored)
+        {
+            Some((slots[0]?, slots[1]?))
+        } else {
+            None
+        }
+    }
+
     fn exec_(
         &mut self,
-        mut clist: &mut Thread,
-        mut nlist: &mut Thread,
+        mut clist: &mut Threads,
+        mut nlist: &mut Threads,
         matches: &mut [bool],
+        slots: &mut [Slot],
         quit_after_match: bool,
-        mut at: I,
+        mut at: usize,
         end: usize,
     ) -> bool {
+        let start = at;
         let mut matched = false;
         let mut all_matched = false;
-        clist.clear();
-        nlist.clear();
+        clist.set.clear();
+        nlist.set.clear();
+        self.steps.taken = 0;
         'LOOP: loop {
-            if clist.is_empty() {
-                // Three ways to bail out when our current set of threads is
+            if clist.set.is_empty() {
+                // Four ways to bail out when our current set of threads is
                 // empty.
                 //
                 // 1. We have a match---so we're done exploring any possible
@@ -124,11 +242,20 @@ impl<'r, I: ~const Integral> Fsm<'r, I> {
                 //
                 // 2. If the expression starts with a '^' we can terminate as
                 //    soon as the last thread dies.
-                if (matched && matches.len() <= 1) || all_matched {
+                if (matched && matches.len() <= 1)
+                    || all_matched
+                    || (at > start && self.anchored)
+                {
+                    break;
+                }
+
+                // 3. No match fits in what is left of the input, so no new
+                //    thread can lead to one.
+                if end - at < self.prog.min_len {
                     break;
                 }
 
-                // 3. If there's a literal prefix for the program, try to
+                // 4. If there's a literal prefix for the program, try to
                 //    jump ahead quickly. If it can't be found, then we can
                 //    bail out early.
                 if !self.prog.prefixes.is_empty() {
@@ -142,19 +269,34 @@ impl<'r, I: ~const Integral> Fsm<'r, I> {
             // This simulates a preceding '.*?' for every regex by adding
             // a state starting at the current position in the input for the
             // beginning of the program only if we don't already have a match.
-            if clist.is_empty()
-                || (!self.prog.is_anchored_start && !all_matched)
+            // A new thread starts out with `slots` as its slots, whose first
+            // is where the match would start.
+            if clist.set.is_empty()
+                || (!self.anchored && !all_matched
+                    && end.saturating_sub(at) >= self.prog.min_len)
             {
-                self.add(&mut clist, 0, at);
+                if let Some(start) = slots.first_mut() {
+                    *start = Some(at);
+                }
+                self.add(&mut clist, slots, 0, at);
             }
-            // The previous call to "add" actually inspects the position just
-            // before the current character. For stepping through the machine,
-            // we can to look at the current character, so we advance the
-            // input.
-            let at_next = self.context[at + 1];
-            for i in 0..clist.len() {
-                let ip = clist[i];
-                if self.step(&mut nlist, matches, ip, at, at_next) {
+            for i in 0..clist.set.len() {
+                let state = clist.set[i];
+                // Threads are ordered by where they started, so once there
+                // is a match, looking for the longest one only goes on with
+                // the threads that started along with it.
+                if self.prog.longest_match
+                    && matched
+                    && clist.slots(state).first() > slots.first()
+                {
+                    break;
+                }
+                if !self.steps.take(self.prog.step_limit) {
+                    break 'LOOP;
+                }
+                if self.step(&mut nlist, matches, slots, clist.slots(state),
+                             state, at)
+                {
                     matched = true;
                     all_matched = all_matched || matches.iter().all(|&b| b);
                     if quit_after_match {
@@ -162,7 +304,7 @@ impl<'r, I: ~const Integral> Fsm<'r, I> {
                         // position), then we can quit right now.
                         break 'LOOP;
                     }
-                    if self.prog.matches.len() == 1 {
+                    if self.prog.matches.len() == 1 && !self.prog.longest_match {
                         // We don't need to check the rest of the threads
                         // in this set because we've matched something
                         // ("leftmost-first"). However, we still need to check
@@ -175,14 +317,15 @@ impl<'r, I: ~const Integral> Fsm<'r, I> {
                     }
                 }
             }
-            if at.pos() >= end {
+            if at >= end {
                 break;
             }
-            at = at_next;
+            at += 1;
             mem::swap(clist, nlist);
-            nlist.clear();
+            nlist.set.clear();
         }
-        matched
+        // Past the limit, a match found so far may not be the one to report.
+        matched && self.steps.taken <= self.prog.step_limit
     }
 
     /// Step through the input, one token (byte or codepoint) at a time.
@@ -190,37 +333,57 @@ impl<'r, I: ~const Integral> Fsm<'r, I> {
     /// nlist is the set of states that will be processed on the next token
     /// in the input.
     ///
-    /// caps is the set of captures passed by the caller of the NFA. They are
+    /// slots is the set of slots passed by the caller of the NFA. They are
     /// written to only when a match state is visited.
     ///
-    /// thread_caps is the set of captures set for the current NFA state, ip.
+    /// thread_slots is the set of slots of the current NFA state.
     ///
-    /// at and at_next are the current and next positions in the input. at or
-    /// at_next may be EOF.
+    /// at is the current position in the input, which may be its end.
     fn step(
         &mut self,
-        nlist: &mut Thread,
+        nlist: &mut Threads,
         matches: &mut [bool],
-        ip: usize,
-        at: I,
-        at_next: I,
+        slots: &mut [Slot],
+        thread_slots: &mut [Slot],
+        state: usize,
+        at: usize,
     ) -> bool {
+        let (ip, k) = if state < self.prog.len() {
+            (state, 0)
+        } else {
+            self.runs[state - self.prog.len()]
+        };
         match self.prog[ip] {
             Inst::Match(match_slot) => {
                 if match_slot < matches.len() {
                     matches[match_slot] = true;
                 }
+                slots.copy_from_slice(thread_slots);
+                if let Some(end) = slots.get_mut(1) {
+                    *end = Some(at);
+                }
                 true
             }
-            Inst::One { goto, seq } => {
-                if seq == at.char() {
-                    self.add(nlist, goto, at_next);
+            Inst::One { goto, ref seq } => {
+                if self.context.next(at) != seq.get(k) {
+                    // Dies.
+                } else if k + 1 == seq.len() {
+                    self.add(nlist, thread_slots, goto, at + 1);
+                } else {
+                    // Still inside the run, so there are no epsilon
+                    // transitions to follow.
+                    let state = self.prog.len()
+                        + self.runs.partition_point(|&(i, _)| i < ip) + k;
+                    if !nlist.set.contains(state) {
+                        nlist.set.insert(state);
+                        nlist.slots(state).copy_from_slice(thread_slots);
+                    }
                 }
                 false
             }
             Inst::Interval { goto, interval } => {
-                if interval.has(at.char()) {
-                    self.add(nlist, goto, at_next);
+                if self.context.next(at).map_or(false, |i| interval.has(i)) {
+                    self.add(nlist, thread_slots, goto, at + 1);
                 }
                 false
             }
@@ -229,37 +392,76 @@ impl<'r, I: ~const Integral> Fsm<'r, I> {
     }
 
     /// Follows epsilon transitions and adds them for processing to nlist,
-    /// starting at and including ip.
-    fn add(&mut self, nlist: &mut Thread, ip: Index, at: I) {
-        self.stack.push(ip);
-        while let Some(ip) = self.stack.pop() {
-            self.add_step(nlist, ip, at);
+    /// starting at and including ip, each with a copy of `thread_slots` as
+    /// updated by the `Save` instructions on the way.
+    fn add(
+        &mut self,
+        nlist: &mut Threads,
+        thread_slots: &mut [Slot],
+        ip: Index,
+        at: usize,
+    ) {
+        self.stack.push(FollowEpsilon::IP(ip));
+        while let Some(frame) = self.stack.pop() {
+            match frame {
+                FollowEpsilon::IP(ip) => {
+                    self.add_step(nlist, thread_slots, ip, at);
+                }
+                FollowEpsilon::Capture { slot, pos } => {
+                    thread_slots[slot] = pos;
+                }
+            }
         }
     }
 
     /// A helper function for add that avoids excessive pushing to the stack.
-    fn add_step(&mut self, nlist: &mut Thread, mut ip: usize, at: I) {
+    fn add_step(
+        &mut self,
+        nlist: &mut Threads,
+        thread_slots: &mut [Slot],
+        mut ip: usize,
+        at: usize,
+    ) {
         // Instead of pushing and popping to the stack, we mutate ip as we
         // traverse the set of states. We only push to the stack when we
         // absolutely need recursion (restoring captures or following a
         // branch).
         loop {
             // Don't visit states we've already added.
-            if nlist.contains(ip) {
+            if nlist.set.contains(ip) {
                 return;
             }
-            nlist.insert(ip);
+            if !self.steps.take(self.prog.step_limit) {
+                return;
+            }
+            nlist.set.insert(ip);
             match self.prog[ip] {
                 Inst::Zero { goto, zero } => {
-                    if self.context.is_empty_match(at, zero) {
+                    if self.context.is_empty_match(
+                        at, &zero, self.prog.line_terminator, self.prog.crlf
+                    ) {
                         ip = goto;
                     }
                 }
                 Inst::Split { goto1, goto2 } => {
-                    self.stack.push(goto2);
+                    self.stack.push(FollowEpsilon::IP(goto2));
                     ip = goto1;
                 }
+                // Slots past those the caller asked for aren't tracked.
+                Inst::Save { goto, slot } if slot < thread_slots.len() => {
+                    // The slot is restored once the transitions from here
+                    // have been followed, so that the other branches of a
+                    // split see it as it was.
+                    self.stack.push(FollowEpsilon::Capture {
+                        slot,
+                        pos: thread_slots[slot],
+                    });
+                    thread_slots[slot] = Some(at);
+                    ip = goto;
+                }
+                Inst::Save { goto, .. } => ip = goto,
                 _ => {
+                    nlist.slots(ip).copy_from_slice(thread_slots);
                     return;
                 }
             }
@@ -59,12 +59,19 @@ Namely, at the time, I was looking to reduce dependencies. And for something
 like regex, maintenance can be simpler when we own the full dependency tree.
 */
 
+use alloc::{collections::BTreeMap, sync::Arc};
 use core::{
     fmt::{self, Debug},
     panic::{RefUnwindSafe, UnwindSafe},
+    slice,
     sync::atomic::{AtomicUsize, Ordering}
 };
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
+
+use crate::compile::Compiler;
+use crate::error::CompileError;
+use crate::program::Program;
+use crate::repr::{Integral, Repr};
 
 /// An atomic counter used to allocate thread IDs.
 static COUNTER: AtomicUsize = AtomicUsize::new(1);
@@ -281,6 +288,63 @@ impl<'a, T: Send> Drop for PoolGuard<'a, T> {
     }
 }
 
+/// The outcome of compiling a repr, set once by the first caller to ask.
+type Compiled<I> = Arc<OnceLock<Result<Arc<Program<I>>, CompileError>>>;
+
+/// A cache of programs compiled with the default options, keyed by the repr
+/// they are compiled from, for a server matching a fixed set of patterns
+/// from many threads. Clones share the cache.
+///
+/// Programs are never evicted, so the cache grows with the number of
+/// distinct reprs given to `get_or_compile`.
+#[derive(Clone)]
+pub struct ProgramPool<I: Integral> {
+    programs: Arc<Mutex<BTreeMap<Repr<I>, Compiled<I>>>>,
+}
+
+impl<I: Integral> ProgramPool<I> {
+    pub fn new() -> Self {
+        ProgramPool { programs: Arc::new(Mutex::new(BTreeMap::new())) }
+    }
+
+    /// Returns the program compiled from `repr`, compiling it on the first
+    /// call with this repr. Calls racing on the same repr wait for a single
+    /// compilation, while the map is only locked to look the repr up, so
+    /// that other reprs compile in the meantime. Errors are cached too.
+    pub fn get_or_compile(&self, repr: &Repr<I>)
+        -> Result<Arc<Program<I>>, CompileError>
+    {
+        let compiled = self.programs.lock().unwrap()
+            .entry(repr.clone())
+            .or_default()
+            .clone();
+        compiled.get_or_init(|| {
+            Compiler::new().compile(slice::from_ref(repr)).map(Arc::new)
+        }).clone()
+    }
+
+    /// Returns the number of reprs compiled or being compiled.
+    pub fn len(&self) -> usize {
+        self.programs.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<I: Integral> Default for ProgramPool<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I: Integral> Debug for ProgramPool<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProgramPool").field("len", &self.len()).finish()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // use core::panic::{RefUnwindSafe, UnwindSafe};
@@ -335,4 +399,28 @@ mod tests {
         // Pool's API.)
         assert_eq!(vec!['a', 'x'], *pool.get().value().borrow());
     }
+
+    #[test]
+    fn program_pool() {
+        use std::sync::Barrier;
+
+        let pool = ProgramPool::new();
+        let repr = Repr::literal("ab").or(Repr::one('c').exp());
+        let barrier = Arc::new(Barrier::new(8));
+        let threads: Vec<_> = (0..8).map(|_| {
+            let (pool, repr, barrier)
+                = (pool.clone(), repr.clone(), barrier.clone());
+            std::thread::spawn(move || {
+                barrier.wait();
+                pool.get_or_compile(&repr).unwrap()
+            })
+        }).collect();
+        let programs: Vec<_>
+            = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        assert!(programs.iter().all(|p| Arc::ptr_eq(p, &programs[0])));
+        assert_eq!(pool.len(), 1);
+        assert!(Arc::ptr_eq(&pool.get_or_compile(&repr).unwrap(), &programs[0]));
+        pool.get_or_compile(&Repr::one('d')).unwrap();
+        assert_eq!(pool.len(), 2);
+    }
 }
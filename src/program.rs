@@ -1,12 +1,24 @@
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::cell::RefCell;
 use core::fmt::{self, Debug};
 use core::mem;
 use core::ops::Deref;
+use core::panic::AssertUnwindSafe;
 use core::slice;
 
 use unconst::unconst;
 
+use crate::{backtrack, dfa, pikevm};
+pub use crate::compile::Inst;
 use crate::derivative::LiteralSearcher;
-use crate::repr::{Integral};
+use crate::error::ProgramError;
+use crate::repr::Integral;
+#[cfg(feature = "std")]
+use crate::repr::Repr;
 
 /// `Index` represents the index of an instruction in a regex program.
 pub type Index = usize;
@@ -31,10 +43,38 @@ pub struct Program<I: Integral> {
     pub is_anchored_start: bool,
     /// Whether the regex must match at the end of the input.
     pub is_anchored_end: bool,
+    /// Whether the regex must match at the start of a line, which
+    /// `is_anchored_start` implies.
+    pub is_line_anchored_start: bool,
+    /// Whether the program matches the reversed regex, i.e., it is meant to
+    /// be run over the input from its end towards its start.
+    pub is_reverse: bool,
+    /// The names of the capture groups, group `i` being named
+    /// `captures[i - 1]` as group 0 is the whole match.
+    pub captures: Vec<String>,
     /// Whether this program contains a Unicode word boundary instruction.
     pub has_unicode_word_boundary: bool,
+    /// Whether the engines report the longest match among those starting
+    /// at the leftmost position (POSIX), instead of the first one in the
+    /// priority order of `Split` (leftmost-first, as PCRE does).
+    pub longest_match: bool,
+    /// The character ending lines, before which `Zero::EndLine` and after
+    /// which `Zero::StartLine` match.
+    pub line_terminator: char,
+    /// Whether lines end with `\r\n`, `\r` or `\n` instead of
+    /// `line_terminator`, line anchors never matching inside a `\r\n`.
+    pub crlf: bool,
     /// A possibly empty machine for very quickly matching prefix literals.
     pub prefixes: LiteralSearcher<I>,
+    /// Elements that must appear somewhere in any input this program
+    /// matches.
+    pub required: Vec<I>,
+    /// The length of the shortest match, so that a search stops trying new
+    /// starting positions once fewer elements are left.
+    pub min_len: usize,
+    /// The most steps an engine run may take, see `Steps`, before giving up
+    /// as if there were no match. By default, there is no limit.
+    pub step_limit: usize,
     /// A limit on the size of the cache that the DFA is allowed to use while
     /// matching.
     ///
@@ -65,8 +105,17 @@ impl<I: ~const Integral> Program<I> {
             // byte_classes: vec![0; 256],
             is_anchored_start: false,
             is_anchored_end: false,
+            is_line_anchored_start: false,
+            is_reverse: false,
+            captures: vec![],
             has_unicode_word_boundary: false,
+            longest_match: false,
+            line_terminator: '\n',
+            crlf: false,
             prefixes: LiteralSearcher::empty(),
+            required: vec![],
+            min_len: 0,
+            step_limit: usize::MAX,
             dfa_size_limit: 2 * (1 << 20),
         }
     }
@@ -86,19 +135,98 @@ impl<I: ~const Integral> Program<I> {
         }
     }
 
+    /// Return the number of capture slots, two for each group including
+    /// the whole match.
+    pub fn slots(&self) -> usize {
+        2 * (self.captures.len() + 1)
+    }
+
+    /// Return the elements that must appear in any input this program
+    /// matches. An input lacking any of them can be rejected without running
+    /// an engine.
+    pub fn required_literals(&self) -> Vec<I> {
+        self.required.clone()
+    }
+
+    /// Checks that `start` and every `goto` are instructions, and that every
+    /// `Match` reports one of `matches`, so that a compiler bug shows up as
+    /// an error rather than as an out of bounds panic in the middle of a
+    /// search. The compiler checks each program in debug builds.
+    pub fn validate(&self) -> Result<(), ProgramError> {
+        if self.start >= self.len() {
+            return Err(ProgramError::StartOutOfRange(self.start));
+        }
+        for (ip, inst) in self.iter().enumerate() {
+            let gotos = match *inst {
+                Inst::Match(slot) if slot >= self.matches.len() => {
+                    return Err(ProgramError::MatchOutOfRange { ip, slot });
+                }
+                Inst::Match(_) => [None, None],
+                Inst::Split { goto1, goto2 } => [Some(goto1), Some(goto2)],
+                Inst::Zero { goto, .. } | Inst::One { goto, .. }
+                    | Inst::Interval { goto, .. } | Inst::Save { goto, .. }
+                    => [Some(goto), None],
+            };
+            if let Some(goto) = gotos.into_iter().flatten()
+                .find(|&goto| goto >= self.len())
+            {
+                return Err(ProgramError::GotoOutOfRange { ip, goto });
+            }
+        }
+        Ok(())
+    }
+
     /// Return the approximate heap usage of this instruction sequence in
     /// bytes.
     pub fn approximate_size(&self) -> usize {
-        // The only instruction that uses heap space is Ranges (for
-        // Unicode codepoint programs) to store non-overlapping codepoint
-        // ranges. To keep this operation constant time, we ignore them.
+        // Intervals are stored inline, so the only instruction owning heap
+        // space is One, for its literal run. This makes the operation
+        // linear in the number of instructions.
+        let seqs: usize = self.iter()
+            .map(|inst| match *inst {
+                Inst::One { ref seq, .. } => seq.len() * mem::size_of::<I>(),
+                _ => 0,
+            })
+            .sum();
+        let captures: usize = self.captures.iter()
+            .map(|name| mem::size_of::<String>() + name.len())
+            .sum();
         (self.len() * mem::size_of::<Inst<I>>())
+            + seqs
             + (self.matches.len() * mem::size_of::<Index>())
-            + (256 * mem::size_of::<u8>())
+            + (self.required.len() * mem::size_of::<I>())
+            + captures
             + self.prefixes.approximate_size()
     }
 }
 
+/// A `Program` compiled from a pattern the first time it is asked for, to
+/// keep in a static, as `program!` does.
+#[cfg(feature = "std")]
+pub struct ProgramCell(std::sync::OnceLock<Program<char>>);
+
+#[cfg(feature = "std")]
+impl ProgramCell {
+    pub const fn new() -> Self {
+        ProgramCell(std::sync::OnceLock::new())
+    }
+
+    /// Returns the program, parsing `pattern` as `Repr::try_from` does and
+    /// compiling it if this is the first call. Later calls ignore `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// If the pattern doesn't parse or compile.
+    pub fn get(&self, pattern: &str) -> &Program<char> {
+        self.0.get_or_init(|| {
+            Repr::try_from(pattern)
+                .expect("invalid pattern")
+                .compile()
+                .expect("pattern doesn't compile")
+        })
+    }
+}
+
 #[unconst]
 impl<I: ~const Integral> Deref for Program<I> {
     type Target = [Inst<I>];
@@ -121,7 +249,7 @@ impl<I: ~const Integral> Debug for Program<I> {
         }
 
         fn visible_byte(b: u8) -> String {
-            use std::ascii::escape_default;
+            use core::ascii::escape_default;
             let escaped = escape_default(b).collect::<Vec<u8>>();
             String::from_utf8_lossy(&escaped).into_owned()
         }
@@ -136,13 +264,17 @@ impl<I: ~const Integral> Debug for Program<I> {
                     let s = format!("{:?}", zero);
                     write!(f, "{:04} {}", pc, with_goto(pc, goto, s))?;
                 }
-                Inst::One { goto, seq } => {
+                Inst::One { goto, ref seq } => {
                     let s = format!("{:?}", seq);
                     write!(f, "{:04} {}", pc, with_goto(pc, goto, s))?;
                 }
                 Inst::Interval { goto, interval } => {
-                    let ranges = format!("{:?}-{:?}", interval.0, interval.1);
-                    write!(f, "{:04} {}", pc, with_goto(pc, goto, ranges))?;
+                    let s = format!("{:?}", interval);
+                    write!(f, "{:04} {}", pc, with_goto(pc, goto, s))?;
+                }
+                Inst::Save { goto, slot } => {
+                    let s = format!("Save({})", slot);
+                    write!(f, "{:04} {}", pc, with_goto(pc, goto, s))?;
                 }
             }
             if pc == self.start {
@@ -172,3 +304,117 @@ impl<I: Integral> Inst<I> {
         }
     }
 }
+
+/// `ProgramCache` maintains reusable allocations for each matching engine
+/// available to a particular program.
+///
+/// We declare this as unwind safe since it's a cache that's only used for
+/// performance purposes. If a panic occurs, it is (or should be) always safe
+/// to continue using the same regex object.
+pub type ProgramCache<I: Integral>
+    = AssertUnwindSafe<RefCell<ProgramCacheInner<I>>>;
+
+#[derive(Debug)]
+pub struct ProgramCacheInner<I: Integral> {
+    pub pikevm: pikevm::Cache,
+    pub backtrack: backtrack::Cache,
+    pub dfa: dfa::Cache<I>,
+    pub steps: Steps,
+}
+
+impl<I: Integral> ProgramCacheInner<I> {
+    pub fn new(prog: &Program<I>) -> Self {
+        ProgramCacheInner {
+            pikevm: pikevm::Cache::new(prog),
+            backtrack: backtrack::Cache::new(prog),
+            dfa: dfa::Cache::new(prog),
+            steps: Steps::default(),
+        }
+    }
+}
+
+/// The steps taken by an engine run against `Program::step_limit`: an
+/// instruction followed by the backtracker, or a thread stepped or added by
+/// the PikeVM. Each run starts counting from zero, while `exceeded` stays
+/// set until the caller clears it, so that it covers every run of a search.
+#[derive(Clone, Debug, Default)]
+pub struct Steps {
+    pub taken: usize,
+    pub exceeded: bool,
+}
+
+impl Steps {
+    /// Takes a step, and returns false if it goes over `limit`, in which
+    /// case the run is to stop.
+    pub fn take(&mut self, limit: usize) -> bool {
+        self.taken += 1;
+        if self.taken > limit {
+            self.exceeded = true;
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seq::Seq;
+
+    #[test]
+    fn approximate_size() {
+        let mut prog = Program::<char>::new();
+        prog.insts = vec![
+            Inst::One { goto: 1, seq: Seq::from("ab") },
+            Inst::Match(0),
+        ];
+        prog.matches = vec![1];
+        prog.required = vec!['a'];
+        prog.captures = vec!["x".to_string()];
+        assert_eq!(
+            prog.approximate_size(),
+            2 * mem::size_of::<Inst<char>>()
+                + 2 * mem::size_of::<char>()
+                + mem::size_of::<Index>()
+                + mem::size_of::<char>()
+                + mem::size_of::<String>() + 1
+                + prog.prefixes.approximate_size()
+        );
+    }
+
+    #[test]
+    fn validate() {
+        let valid = || {
+            let mut prog = Program::<char>::new();
+            prog.insts = vec![
+                Inst::Split { goto1: 1, goto2: 2 },
+                Inst::One { goto: 2, seq: Seq::from("ab") },
+                Inst::Match(0),
+            ];
+            prog.matches = vec![2];
+            prog
+        };
+        assert_eq!(valid().validate(), Ok(()));
+        assert_eq!(Program::<char>::new().validate(),
+                   Err(ProgramError::StartOutOfRange(0)));
+
+        let mut prog = valid();
+        prog.start = 3;
+        assert_eq!(prog.validate(), Err(ProgramError::StartOutOfRange(3)));
+        let mut prog = valid();
+        prog.insts[0] = Inst::Split { goto1: 1, goto2: 7 };
+        assert_eq!(prog.validate(),
+                   Err(ProgramError::GotoOutOfRange { ip: 0, goto: 7 }));
+        let mut prog = valid();
+        prog.insts[1] = Inst::One { goto: 3, seq: Seq::from("ab") };
+        assert_eq!(prog.validate(),
+                   Err(ProgramError::GotoOutOfRange { ip: 1, goto: 3 }));
+        let mut prog = valid();
+        prog.insts[2] = Inst::Match(1);
+        assert_eq!(prog.validate(),
+                   Err(ProgramError::MatchOutOfRange { ip: 2, slot: 1 }));
+
+        let repr = crate::repr::Repr::literal("ab").cap("x").or(crate::DIGIT);
+        assert_eq!(repr.compile().unwrap().validate(), Ok(()));
+    }
+}
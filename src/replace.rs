@@ -0,0 +1,47 @@
+//! Expansion of `$` references in replacement strings.
+
+use core::fmt::{self, Write};
+
+/// Writes `replacement` to `out`, expanding `$` references as it goes.
+///
+/// `$$` is a literal `$`. `$name` and `${name}` are replaced by `group(name)`,
+/// or by nothing if there is no such group. Without braces, the name is the
+/// longest run of `[_0-9A-Za-z]` after the `$`. A `$` that starts neither is
+/// written as is.
+pub fn expand<'t, W: Write>(
+    mut replacement: &str,
+    group: impl Fn(&str) -> Option<&'t str>,
+    out: &mut W,
+) -> fmt::Result {
+    while let Some(i) = replacement.find('$') {
+        out.write_str(&replacement[..i])?;
+        let after = &replacement[i + 1..];
+        if let Some(rest) = after.strip_prefix('$') {
+            out.write_char('$')?;
+            replacement = rest;
+            continue;
+        }
+        let (name, rest) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| c != '_' && !c.is_ascii_alphanumeric())
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+        if name.is_empty() {
+            out.write_char('$')?;
+            replacement = after;
+            continue;
+        }
+        if let Some(text) = group(name) {
+            out.write_str(text)?;
+        }
+        replacement = rest;
+    }
+    out.write_str(replacement)
+}
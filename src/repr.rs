@@ -1,18 +1,41 @@
-use alloc::boxed::Box;
+use alloc::{
+    boxed::Box,
+    collections::BTreeSet,
+    string::{String, ToString},
+    vec::Vec,
+};
 use core::{
+    cmp::{max, min},
     fmt::Debug,
     iter::Step,
     marker::Destruct,
+    mem::discriminant,
+    slice,
 };
 
 use unconst::unconst;
 
+use crate::compile::Compiler;
+use crate::context::Context;
+use crate::error::CompileError;
+#[cfg(feature = "std")]
+use crate::error::MatchError;
+#[cfg(feature = "std")]
+use crate::exec::Exec;
 use crate::interval::Interval;
+#[cfg(feature = "std")]
+use crate::options::Options;
+#[cfg(feature = "std")]
+use crate::partition::Captures;
+use crate::program::Program;
 use crate::seq::Seq;
 
 #[unconst]
+/// `Hash` and `Ord` are structural, so that reprs can key maps: `Ord` orders
+/// by variant, then by operands. Like for `Interval`, `<=` is therefore not
+/// `Repr::le`, which is language containment.
 #[derive_const(Clone, Debug)]
-#[derive(Eq, PartialEq)]
+#[derive(Eq, Hash, PartialEq, PartialOrd, Ord)]
 pub enum Repr<I: ~const Integral> {
     Zero(Zero),
     One(Seq<I>),
@@ -31,6 +54,13 @@ pub enum Repr<I: ~const Integral> {
     Add(Box<Repr<I>>, Box<Repr<I>>),
     /// a & b (additive conjunction/with)
     And(Box<Repr<I>>, Box<Repr<I>>),
+    /// A named capture group, matching what its operand matches. The
+    /// engines reporting positions record where it matched, see `Captures`.
+    Cap(Box<Repr<I>>, String),
+    /// `Exp` preferring fewer repetitions, as `*?` does: the same language,
+    /// but the engines reporting positions stop repeating as early as the
+    /// rest of the repr allows.
+    Lazy(Box<Repr<I>>),
     // Map(Box<Repr<I>>, Fn(Box<Repr<I>>), Fn(Box<Repr<I>>))
 }
 
@@ -44,11 +74,21 @@ impl<I: ~const Integral> Repr<I> {
         Self::One(Seq::one(i))
     }
 
+    /// The empty language, which matches nothing. It is the interval with
+    /// its bounds swapped, which `Interval::new` turns down.
+    pub const fn void() -> Self {
+        Self::Interval(Interval(I::MAX, I::MIN))
+    }
+
+    pub const fn is_void(&self) -> bool {
+        matches!(self, Self::Interval(Interval(from, to)) if from > to)
+    }
+
     
     pub const fn mul(self, other: Self) -> Self {
         match (self, other) {
             (Self::One(lhs), Self::One(rhs)) => Self::One(lhs.mul(rhs)),
-            (lhs, rhs) => Self::Or(box lhs, box rhs)
+            (lhs, rhs) => Self::Mul(box lhs, box rhs)
         }
     }
     
@@ -72,14 +112,440 @@ impl<I: ~const Integral> Repr<I> {
         Self::Exp(box self)
     }
 
+    /// Like `exp`, but lazy, see `Repr::Lazy`.
+    pub const fn exp_lazy(self) -> Self {
+        Self::Lazy(box self)
+    }
+
     pub const fn and(self, other: Self) -> Self {
         Self::And(box self, box other)
     }
+
+    /// What `self` matches that `other` doesn't, that is, `self & ¬other`.
+    pub const fn sub(self, other: Self) -> Self {
+        self.and(other.not())
+    }
+
+    /// The complement of the language of `self`, every word it doesn't
+    /// match, the empty word included.
+    pub const fn not(self) -> Self {
+        Self::Not(box self)
+    }
+
+    /// Captures what `self` matches under `name`. Groups are numbered from
+    /// 1 in the order their names first appear, left to right, group 0
+    /// being the whole match. Groups with the same name, such as the copies
+    /// `repeat` makes, share a number and report the last span matched.
+    pub const fn cap(self, name: &str) -> Self {
+        Self::Cap(box self, name.to_string())
+    }
     
+    /// Returns true if the language of `self` is contained in that of
+    /// `other`. This is conservative: false means containment couldn't be
+    /// shown, not that it doesn't hold.
     pub const fn le(&self, other: &Self) -> bool {
+        if self == other || self.is_void() {
+            return true;
+        }
+        match (self, other) {
+            // Neither do a group or laziness.
+            (Self::Lazy(repr), other) => repr.clone().exp().le(other),
+            (repr, Self::Lazy(other)) => repr.le(&other.clone().exp()),
+            (Self::Cap(repr, _), other) => repr.le(other),
+            (repr, Self::Cap(other, _)) => repr.le(other),
+            (Self::Or(lhs, rhs), other) => lhs.le(other) && rhs.le(other),
+            (repr, Self::And(lhs, rhs)) => repr.le(lhs) && repr.le(rhs),
+            (Self::And(lhs1, rhs1), Self::Or(lhs2, rhs2))
+                => lhs1.le(other) || rhs1.le(other)
+                    || self.le(lhs2) || self.le(rhs2),
+            (Self::And(lhs, rhs), other) => lhs.le(other) || rhs.le(other),
+            (repr, Self::Or(lhs, rhs)) => repr.le(lhs) || repr.le(rhs),
+            (Self::One(seq), Self::Interval(interval))
+                => seq.len() == 1 && interval.has(seq[0]),
+            (Self::Interval(lhs), Self::Interval(rhs)) => lhs.le(rhs),
+            (Self::Mul(lhs1, rhs1), Self::Mul(lhs2, rhs2))
+                => lhs1.le(lhs2) && rhs1.le(rhs2),
+            (Self::Zero(Zero::Any), Self::Exp(_)) => true,
+            (Self::Exp(lhs), Self::Exp(rhs)) => lhs.le(rhs),
+            (repr, Self::Exp(rhs)) => repr.le(rhs),
+            _ => false
+        }
+    }
+
+    /// The least upper bound of `self` and `other` in the order `le`: the
+    /// greater of the two if `le` relates them, or else their `Or`, the
+    /// lesser operand in `Ord` first.
+    ///
+    /// `self.le(&self.join(other))` always holds, and with `meet` this is
+    /// idempotent, commutative and absorptive (`x.join(x.meet(y))` is `x`)
+    /// up to reprs that `le` shows to contain each other. As `le` is
+    /// conservative, it isn't associative.
+    pub const fn join(self, other: Self) -> Self {
+        match (self.le(&other), other.le(&self)) {
+            (true, true) => min(self, other),
+            (true, false) => other,
+            (false, true) => self,
+            (false, false) => Self::Or(box min(self.clone(), other.clone()),
+                                       box max(self, other)),
+        }
+    }
+
+    /// The greatest lower bound of `self` and `other` in the order `le`,
+    /// like `join` but with `And`. `self.meet(other).le(&self)` always
+    /// holds, and the laws of `join` hold for it too.
+    pub const fn meet(self, other: Self) -> Self {
+        match (self.le(&other), other.le(&self)) {
+            (true, true) => min(self, other),
+            (true, false) => self,
+            (false, true) => other,
+            (false, false) => Self::And(box min(self.clone(), other.clone()),
+                                        box max(self, other)),
+        }
+    }
+
+    /// The union of the languages of `self` and `other`, as `self | other`
+    /// but normalised, so that composing unions again and again doesn't
+    /// grow the repr more than needed:
+    ///
+    /// - the branches of both are flattened, in order, and void ones dropped;
+    /// - each run of consecutive branches matching single elements, `One`s of
+    ///   one element and `Interval`s, is merged into as few intervals as
+    ///   cover it;
+    /// - a branch contained in an earlier one by `le` is dropped, see
+    ///   `dedup_or`.
+    ///
+    /// None of these change which match a leftmost-first search reports, as
+    /// the branches of a run all match one element, so it is the same as
+    /// for `self | other`. Groups are kept.
+    pub const fn union(self, other: Self) -> Self {
+        let mut branches = Vec::new();
+        let mut run = Vec::new();
+        for branch in self.branches().into_iter().chain(other.branches()) {
+            match branch.as_class() {
+                Some(interval) => run.push(interval),
+                None if branch.is_void() => {}
+                None => {
+                    branches.extend(merge_intervals(run.split_off(0))
+                                        .into_iter().map(Self::from_class));
+                    branches.push(branch);
+                }
+            }
+        }
+        branches.extend(merge_intervals(run).into_iter().map(Self::from_class));
+        Self::any(branches.into_iter()).dedup_or()
+    }
+
+    /// The intersection of the languages of `self` and `other`, as
+    /// `self & other` but normalised: void if either is, the intersected
+    /// interval if both match single elements, and otherwise their `meet`,
+    /// which is whichever of the two `le` shows to be contained in the
+    /// other. So `a.union(b).intersection(a)` is `a`.
+    pub const fn intersection(self, other: Self) -> Self {
+        if self.is_void() || other.is_void() {
+            return Self::void();
+        }
+        match (self.as_class(), other.as_class()) {
+            (Some(lhs), Some(rhs))
+                => lhs.and(rhs).map_or(Self::void(), Self::from_class),
+            _ => self.meet(other),
+        }
+    }
+
+    /// What `self` matches that `other` doesn't, as `sub` but normalised:
+    /// void if `le` shows `self` to be contained in `other`, `self` if
+    /// `other` is void, what is left of the interval if both match single
+    /// elements, and otherwise `self & ¬other`.
+    pub const fn difference(self, other: Self) -> Self {
+        if self.le(&other) {
+            return Self::void();
+        }
+        if other.is_void() {
+            return self;
+        }
+        match (self.as_class(), other.as_class()) {
+            (Some(lhs), Some(rhs)) => match lhs.sub(rhs) {
+                (Some(lower), Some(upper))
+                    => Self::from_class(lower).or(Self::from_class(upper)),
+                (Some(rest), None) => Self::from_class(rest),
+                _ => Self::void(),
+            },
+            _ => self.sub(other),
+        }
+    }
+
+    /// The interval of the elements this repr matches if it only matches
+    /// words of one element, as a `One` of one element or a non-void
+    /// `Interval`.
+    const fn as_class(&self) -> Option<Interval<I>> {
         match self {
-            // Self::Or(lhs, rhs) => other == lhs || other == rhs,
-            _ => unimplemented!()
+            Self::One(seq) if seq.len() == 1 => {
+                let i = seq.get(0)?;
+                Some(Interval(i, i))
+            }
+            Self::Interval(_) if self.is_void() => None,
+            Self::Interval(interval) => Some(*interval),
+            _ => None,
+        }
+    }
+
+    /// The inverse of `as_class`, a single element being a `One`.
+    const fn from_class(interval: Interval<I>) -> Self {
+        if interval.0 == interval.1 {
+            Self::one(interval.0)
+        } else {
+            Self::Interval(interval)
+        }
+    }
+
+    /// Drops every `Or` branch whose language is contained in that of an
+    /// earlier branch, since a leftmost-first search never reports a match
+    /// of it. A branch contained in a *later* one is kept, as dropping it
+    /// could change which match is reported. So is a branch with a group,
+    /// which `le` looks through, so that the groups stay the same.
+    pub const fn dedup_or(self) -> Self {
+        match self {
+            Self::Or(lhs, rhs) => {
+                let mut branches: Vec<Self> = Vec::new();
+                for branch in lhs.branches().into_iter().chain(rhs.branches()) {
+                    let branch = branch.dedup_or();
+                    if !branch.capture_names().is_empty()
+                        || !branches.iter().any(|kept| branch.le(kept))
+                    {
+                        branches.push(branch);
+                    }
+                }
+                Self::any(branches.into_iter())
+            }
+            Self::Mul(lhs, rhs)
+                => Self::Mul(box lhs.dedup_or(), box rhs.dedup_or()),
+            Self::Div(lhs, rhs)
+                => Self::Div(box lhs.dedup_or(), box rhs.dedup_or()),
+            Self::Exp(repr) => Self::Exp(box repr.dedup_or()),
+            Self::Lazy(repr) => Self::Lazy(box repr.dedup_or()),
+            Self::Not(repr) => Self::Not(box repr.dedup_or()),
+            Self::Add(lhs, rhs)
+                => Self::Add(box lhs.dedup_or(), box rhs.dedup_or()),
+            Self::And(lhs, rhs)
+                => Self::And(box lhs.dedup_or(), box rhs.dedup_or()),
+            Self::Cap(repr, name) => Self::Cap(box repr.dedup_or(), name),
+            repr => repr
+        }
+    }
+
+    /// Flattens nested `Or`s into their branches, in priority order.
+    pub(crate) const fn branches(self) -> Vec<Self> {
+        let mut branches = Vec::new();
+        let mut stack = vec![self];
+        while let Some(repr) = stack.pop() {
+            match repr {
+                Self::Or(lhs, rhs) => {
+                    stack.push(*rhs);
+                    stack.push(*lhs);
+                }
+                repr => branches.push(repr)
+            }
+        }
+        branches
+    }
+
+    /// Like `branches`, but borrowing them.
+    pub(crate) const fn branch_refs(&self) -> Vec<&Self> {
+        let mut branches = Vec::new();
+        let mut stack = vec![self];
+        while let Some(repr) = stack.pop() {
+            match repr {
+                Self::Or(lhs, rhs) => {
+                    stack.push(rhs);
+                    stack.push(lhs);
+                }
+                repr => branches.push(repr)
+            }
+        }
+        branches
+    }
+
+    /// The operands of this repr, if any.
+    pub(crate) const fn operands(&self) -> Vec<&Self> {
+        match self {
+            Self::Mul(lhs, rhs) | Self::Or(lhs, rhs) | Self::Div(lhs, rhs)
+            | Self::Add(lhs, rhs) | Self::And(lhs, rhs) => vec![lhs, rhs],
+            Self::Exp(repr) | Self::Not(repr) | Self::Lazy(repr)
+            | Self::Cap(repr, _) => vec![repr],
+            Self::Zero(_) | Self::One(_) | Self::Interval(_) => vec![],
+        }
+    }
+
+    /// Returns the number of nodes on the longest path from this repr down
+    /// to a leaf, so 1 for a leaf. Worth checking on reprs from untrusted
+    /// sources before compiling them, as most operations recurse over the
+    /// tree. This one doesn't, so any depth can be measured.
+    pub const fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut stack = vec![(self, 1)];
+        while let Some((repr, d)) = stack.pop() {
+            depth = max(depth, d);
+            stack.extend(repr.operands().into_iter().map(|repr| (repr, d + 1)));
+        }
+        depth
+    }
+
+    /// Returns the same repr with each chain of `Or`, `And`, `Add` or `Mul`
+    /// rebuilt as a balanced tree, so that a chain of `n` operands is about
+    /// `log2(n)` deep instead of `n`, as folding leaves it. The operands
+    /// keep their order, hence `Or` keeps its priority. A chain is
+    /// gathered without recursing, so any depth can be rebalanced.
+    pub const fn rebalance(self) -> Self {
+        let build: fn(Box<Self>, Box<Self>) -> Self = match self {
+            Self::Mul(..) => Self::Mul,
+            Self::Or(..) => Self::Or,
+            Self::Add(..) => Self::Add,
+            Self::And(..) => Self::And,
+            Self::Div(lhs, rhs)
+                => return Self::Div(box lhs.rebalance(), box rhs.rebalance()),
+            Self::Exp(repr) => return Self::Exp(box repr.rebalance()),
+            Self::Lazy(repr) => return Self::Lazy(box repr.rebalance()),
+            Self::Not(repr) => return Self::Not(box repr.rebalance()),
+            Self::Cap(repr, name)
+                => return Self::Cap(box repr.rebalance(), name),
+            repr => return repr,
+        };
+        let kind = discriminant(&self);
+        let mut operands = Vec::new();
+        let mut stack = vec![self];
+        while let Some(repr) = stack.pop() {
+            if discriminant(&repr) != kind {
+                operands.push(repr.rebalance());
+                continue;
+            }
+            match repr {
+                Self::Mul(lhs, rhs) | Self::Or(lhs, rhs) | Self::Add(lhs, rhs)
+                | Self::And(lhs, rhs) => {
+                    stack.push(*rhs);
+                    stack.push(*lhs);
+                }
+                _ => unreachable!(),
+            }
+        }
+        Self::balanced(build, operands)
+    }
+
+    /// The balanced tree of `build` over `operands`, in order.
+    const fn balanced(build: fn(Box<Self>, Box<Self>) -> Self,
+                      mut operands: Vec<Self>) -> Self {
+        if operands.len() == 1 {
+            return operands.pop().unwrap();
+        }
+        let rhs = operands.split_off(operands.len() / 2);
+        build(box Self::balanced(build, operands),
+              box Self::balanced(build, rhs))
+    }
+
+    /// Returns the number of nodes of this repr. Like `depth`, it doesn't
+    /// recurse.
+    pub const fn node_count(&self) -> usize {
+        let mut count = 0;
+        let mut stack = vec![self];
+        while let Some(repr) = stack.pop() {
+            count += 1;
+            stack.extend(repr.operands());
+        }
+        count
+    }
+
+    /// Returns reprs structurally simpler than this one, for shrinking a
+    /// failing case down to a small reproducer: the empty word, then each
+    /// operand in place of the whole, which drops a branch of an `Or` or a
+    /// copy made by `repeat` and unwraps an `Exp`, then a literal or an
+    /// interval cut down, then the same for each operand in place. The
+    /// order is deterministic and simplest first, so a shrinker can keep the
+    /// first candidate that still fails and start over from it.
+    pub fn shrink(&self) -> Vec<Self> {
+        let mut shrunk = Vec::new();
+        if *self != Self::zero() {
+            shrunk.push(Self::zero());
+        }
+        shrunk.extend(self.operands().into_iter().cloned());
+        match self {
+            Self::One(seq) if seq.len() > 1 => {
+                let last = seq.len() - 1;
+                shrunk.push(Self::One(Seq::new(seq[1..].iter().cloned())));
+                shrunk.push(Self::One(Seq::new(seq[..last].iter().cloned())));
+            }
+            Self::Interval(Interval(from, to)) if from < to
+                => shrunk.push(Self::one(*from)),
+            Self::Mul(lhs, rhs)
+                => shrink_binary(lhs, rhs, Self::Mul, &mut shrunk),
+            Self::Or(lhs, rhs)
+                => shrink_binary(lhs, rhs, Self::Or, &mut shrunk),
+            Self::Div(lhs, rhs)
+                => shrink_binary(lhs, rhs, Self::Div, &mut shrunk),
+            Self::Add(lhs, rhs)
+                => shrink_binary(lhs, rhs, Self::Add, &mut shrunk),
+            Self::And(lhs, rhs)
+                => shrink_binary(lhs, rhs, Self::And, &mut shrunk),
+            Self::Exp(repr) => shrunk.extend(
+                repr.shrink().into_iter().map(|repr| Self::Exp(box repr))),
+            Self::Not(repr) => shrunk.extend(
+                repr.shrink().into_iter().map(|repr| Self::Not(box repr))),
+            Self::Lazy(repr) => shrunk.extend(
+                repr.shrink().into_iter().map(|repr| Self::Lazy(box repr))),
+            Self::Cap(repr, name) => shrunk.extend(
+                repr.shrink().into_iter().map(|repr| repr.cap(name))),
+            _ => {}
+        }
+        shrunk.dedup();
+        shrunk
+    }
+
+    /// Returns the names of the capture groups, in the order of their
+    /// numbers, group 1 first.
+    pub const fn capture_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        let mut stack = vec![self];
+        while let Some(repr) = stack.pop() {
+            if let Self::Cap(_, name) = repr {
+                if !names.contains(name) {
+                    names.push(name.clone());
+                }
+            }
+            stack.extend(repr.operands().into_iter().rev());
+        }
+        names
+    }
+
+    /// Returns the names of the capture groups that a match may leave
+    /// unset, in the order of `capture_names`. A group is unset when it sits
+    /// in the branch of an `Or` not taken or under an `Exp` repeated zero
+    /// times, unless a group of the same name is set on every match, as in
+    /// `plus`. Under other operators than `Mul`, groups are taken to be
+    /// optional.
+    pub const fn optional_capture_names(&self) -> Vec<String> {
+        let mandatory = self.mandatory_capture_names();
+        self.capture_names().into_iter()
+            .filter(|name| !mandatory.contains(name.as_str()))
+            .collect()
+    }
+
+    /// The names of the capture groups that every match sets.
+    fn mandatory_capture_names(&self) -> BTreeSet<&str> {
+        match self {
+            Self::Mul(lhs, rhs) => {
+                let mut names = lhs.mandatory_capture_names();
+                names.extend(rhs.mandatory_capture_names());
+                names
+            }
+            Self::Or(lhs, rhs) => {
+                let rhs = rhs.mandatory_capture_names();
+                lhs.mandatory_capture_names().into_iter()
+                    .filter(|name| rhs.contains(name))
+                    .collect()
+            }
+            Self::Cap(repr, name) => {
+                let mut names = repr.mandatory_capture_names();
+                names.insert(name);
+                names
+            }
+            _ => BTreeSet::new(),
         }
     }
 
@@ -95,42 +561,293 @@ impl<I: ~const Integral> Repr<I> {
             _ => unimplemented!()
         }
     }
-    
+
+    /// Returns the same repr over another alphabet, each element of a `One`
+    /// and each bound of an `Interval` mapped through `f`, or `None` if `f`
+    /// returns `None` for any of them, as for a non-ASCII `char` mapped to
+    /// `u8`. Intervals are mapped by their bounds, so `f` must keep the order
+    /// and leave no gaps to give the same classes. Assertions carry over.
+    pub fn map_alphabet<J: Integral>(&self, f: &impl Fn(I) -> Option<J>)
+        -> Option<Repr<J>>
+    {
+        Some(match self {
+            Self::Zero(zero) => Repr::Zero(zero.clone()),
+            Self::One(seq) => {
+                let seq: Option<Vec<J>> = seq.iter().map(|&i| f(i)).collect();
+                Repr::One(Seq::new(seq?.into_iter()))
+            }
+            Self::Interval(_) if self.is_void() => Repr::void(),
+            Self::Interval(Interval(from, to))
+                => Repr::Interval(Interval(f(*from)?, f(*to)?)),
+            Self::Mul(lhs, rhs)
+                => Repr::Mul(box lhs.map_alphabet(f)?, box rhs.map_alphabet(f)?),
+            Self::Or(lhs, rhs)
+                => Repr::Or(box lhs.map_alphabet(f)?, box rhs.map_alphabet(f)?),
+            Self::Div(lhs, rhs)
+                => Repr::Div(box lhs.map_alphabet(f)?, box rhs.map_alphabet(f)?),
+            Self::Exp(repr) => Repr::Exp(box repr.map_alphabet(f)?),
+            Self::Not(repr) => Repr::Not(box repr.map_alphabet(f)?),
+            Self::Add(lhs, rhs)
+                => Repr::Add(box lhs.map_alphabet(f)?, box rhs.map_alphabet(f)?),
+            Self::And(lhs, rhs)
+                => Repr::And(box lhs.map_alphabet(f)?, box rhs.map_alphabet(f)?),
+            Self::Cap(repr, name)
+                => Repr::Cap(box repr.map_alphabet(f)?, name.clone()),
+            Self::Lazy(repr) => Repr::Lazy(box repr.map_alphabet(f)?),
+        })
+    }
+
+    /// Returns the repr matching the reversed words, see `try_rev`.
+    ///
+    /// # Panics
+    ///
+    /// If the repr contains a `Div`.
     pub const fn rev(self) -> Self {
+        match self.try_rev() {
+            Ok(repr) => repr,
+            Err(_) => panic!("the reversal of a `Div` is no repr"),
+        }
+    }
+
+    /// Returns the repr matching the reversed words. It works through an
+    /// explicit stack rather than recursing, so that it can't overflow the
+    /// call stack however deep the repr is.
+    ///
+    /// If the repr contains a `Div`, returns `CompileError::UnsupportedDiv`,
+    /// as the reversal of a left quotient is a right quotient, which no
+    /// `Repr` expresses.
+    pub const fn try_rev(self) -> Result<Self, CompileError> {
+        /// Either a repr to reverse, or how to rebuild a node from its
+        /// reversed operands, which are then the last ones on `done`.
+        enum Work<I: Integral> {
+            Rev(Repr<I>),
+            Build(fn(Box<Repr<I>>, Box<Repr<I>>) -> Repr<I>),
+            Exp,
+            Lazy,
+            Not,
+            Cap(String),
+        }
+        let mut work = vec![Work::Rev(self)];
+        let mut done: Vec<Self> = Vec::new();
+        while let Some(next) = work.pop() {
+            match next {
+                Work::Rev(repr) => match repr {
+                    Self::Zero(zero) => done.push(Self::Zero(zero.rev())),
+                    Self::One(i) => done.push(Self::One(i.rev())),
+                    Self::Interval(i) => done.push(Self::Interval(i)),
+                    // The operands are swapped, so `rhs` is reversed first.
+                    Self::Mul(lhs, rhs) => work.extend([
+                        Work::Build(Self::Mul), Work::Rev(*lhs), Work::Rev(*rhs)
+                    ]),
+                    Self::Or(lhs, rhs) => work.extend([
+                        Work::Build(Self::Or), Work::Rev(*rhs), Work::Rev(*lhs)
+                    ]),
+                    Self::Add(lhs, rhs) => work.extend([
+                        Work::Build(Self::Add), Work::Rev(*rhs), Work::Rev(*lhs)
+                    ]),
+                    Self::And(lhs, rhs) => work.extend([
+                        Work::Build(Self::And), Work::Rev(*rhs), Work::Rev(*lhs)
+                    ]),
+                    Self::Exp(repr) => work.extend([Work::Exp, Work::Rev(*repr)]),
+                    Self::Lazy(repr)
+                        => work.extend([Work::Lazy, Work::Rev(*repr)]),
+                    Self::Cap(repr, name)
+                        => work.extend([Work::Cap(name), Work::Rev(*repr)]),
+                    // The reversed words of the complement are the
+                    // complement of the reversed words.
+                    Self::Not(repr)
+                        => work.extend([Work::Not, Work::Rev(*repr)]),
+                    Self::Div(..) => return Err(CompileError::UnsupportedDiv),
+                },
+                Work::Build(build) => {
+                    let rhs = done.pop().unwrap();
+                    let lhs = done.pop().unwrap();
+                    done.push(build(box lhs, box rhs));
+                }
+                Work::Exp => {
+                    let repr = done.pop().unwrap();
+                    done.push(Self::Exp(box repr));
+                }
+                Work::Lazy => {
+                    let repr = done.pop().unwrap();
+                    done.push(Self::Lazy(box repr));
+                }
+                Work::Not => {
+                    let repr = done.pop().unwrap();
+                    done.push(Self::Not(box repr));
+                }
+                Work::Cap(name) => {
+                    let repr = done.pop().unwrap();
+                    done.push(Self::Cap(box repr, name));
+                }
+            }
+        }
+        Ok(done.pop().unwrap())
+    }
+
+    /// Returns the repr matching like this one, but only at the start of
+    /// the text, `Zero::StartText` being prepended.
+    pub const fn anchored_start(self) -> Self {
+        Self::Zero(Zero::StartText).mul(self)
+    }
+
+    /// Returns the repr matching like this one, but only at the end of the
+    /// text, `Zero::EndText` being appended.
+    pub const fn anchored_end(self) -> Self {
+        self.mul(Self::Zero(Zero::EndText))
+    }
+
+    /// Both `anchored_start` and `anchored_end`, so that matches span the
+    /// whole text.
+    pub const fn anchored(self) -> Self {
+        self.anchored_start().anchored_end()
+    }
+
+    /// Undoes `anchored`, or either of its halves: a `StartText` the repr
+    /// starts with and an `EndText` it ends with are removed. Anchors
+    /// elsewhere, such as in a branch of an `Or`, are kept.
+    pub const fn unanchored(self) -> Self {
+        self.unanchored_start().unanchored_end()
+    }
+
+    const fn unanchored_start(self) -> Self {
         match self {
-            Self::Zero(zero) => Self::Zero(zero),
-            Self::One(i) => Self::One(i.rev()),
-            Self::Interval(i) => Self::Interval(i),
-            Self::Mul(lhs, rhs) => Self::Mul(box rhs.rev(), box lhs.rev()),
-            Self::Or(lhs, rhs) => Self::Or(box lhs.rev(), box rhs.rev()),
-            // Self::Div(lhs, rhs) => ,
-            Self::Exp(repr) => Self::Exp(box repr.rev()),
-            // Self::Not => ,
-            Self::Add(lhs, rhs) => Self::Add(box lhs.rev(), box rhs.rev()),
-            Self::And(lhs, rhs) => Self::And(box lhs.rev(), box rhs.rev()),
-            _ => unimplemented!()
+            Self::Zero(Zero::StartText) => Self::zero(),
+            Self::Mul(lhs, rhs) => match *lhs {
+                Self::Zero(Zero::StartText) => *rhs,
+                lhs => Self::Mul(box lhs.unanchored_start(), rhs),
+            },
+            repr => repr,
+        }
+    }
+
+    const fn unanchored_end(self) -> Self {
+        match self {
+            Self::Zero(Zero::EndText) => Self::zero(),
+            Self::Mul(lhs, rhs) => match *rhs {
+                Self::Zero(Zero::EndText) => *lhs,
+                rhs => Self::Mul(lhs, box rhs.unanchored_end()),
+            },
+            repr => repr,
+        }
+    }
+
+    /// Returns the repr with every `Cap` replaced by the repr it captures,
+    /// so that a fragment with groups of its own can be embedded in another
+    /// repr without adding to its groups.
+    pub const fn without_captures(self) -> Self {
+        match self {
+            Self::Mul(lhs, rhs) => Self::Mul(box lhs.without_captures(),
+                                             box rhs.without_captures()),
+            Self::Or(lhs, rhs) => Self::Or(box lhs.without_captures(),
+                                           box rhs.without_captures()),
+            Self::Div(lhs, rhs) => Self::Div(box lhs.without_captures(),
+                                             box rhs.without_captures()),
+            Self::Exp(repr) => Self::Exp(box repr.without_captures()),
+            Self::Not(repr) => Self::Not(box repr.without_captures()),
+            Self::Add(lhs, rhs) => Self::Add(box lhs.without_captures(),
+                                             box rhs.without_captures()),
+            Self::And(lhs, rhs) => Self::And(box lhs.without_captures(),
+                                             box rhs.without_captures()),
+            Self::Cap(repr, _) => repr.without_captures(),
+            Self::Lazy(repr) => Self::Lazy(box repr.without_captures()),
+            repr => repr,
+        }
+    }
+
+    /// Mirrors the `(?m)` flag. When `yes`, every `StartText`/`EndText`
+    /// becomes `StartLine`/`EndLine` so that `^`/`$` match at line breaks;
+    /// otherwise line anchors go back to matching only at the text edges.
+    pub const fn multi_line(self, yes: bool) -> Self {
+        match self {
+            Self::Zero(zero) => Self::Zero(zero.multi_line(yes)),
+            Self::Mul(lhs, rhs)
+                => Self::Mul(box lhs.multi_line(yes), box rhs.multi_line(yes)),
+            Self::Or(lhs, rhs)
+                => Self::Or(box lhs.multi_line(yes), box rhs.multi_line(yes)),
+            Self::Div(lhs, rhs)
+                => Self::Div(box lhs.multi_line(yes), box rhs.multi_line(yes)),
+            Self::Exp(repr) => Self::Exp(box repr.multi_line(yes)),
+            Self::Lazy(repr) => Self::Lazy(box repr.multi_line(yes)),
+            Self::Not(repr) => Self::Not(box repr.multi_line(yes)),
+            Self::Add(lhs, rhs)
+                => Self::Add(box lhs.multi_line(yes), box rhs.multi_line(yes)),
+            Self::And(lhs, rhs)
+                => Self::And(box lhs.multi_line(yes), box rhs.multi_line(yes)),
+            Self::Cap(repr, name) => Self::Cap(box repr.multi_line(yes), name),
+            repr => repr
         }
     }
 
+    /// The `Mul` of `reprs` from left to right, or its unit, the empty
+    /// word, if there are none.
     pub const fn prod<M: ~const Iterator<Item = Self>>(reprs: M) -> Self {
-        reprs.reduce(|acc, e| Repr::Mul(box acc, box e)).unwrap()
+        reprs.reduce(|acc, e| Repr::Mul(box acc, box e))
+            .unwrap_or(Self::zero())
     }
 
+    /// The `Or` of `reprs` from left to right, or its unit, the empty
+    /// language, if there are none.
     pub const fn any<M: ~const Iterator<Item = Self>>(reprs: M) -> Self {
-        reprs.reduce(|acc, e| Repr::Or(box acc, box e)).unwrap()
+        reprs.reduce(|acc, e| Repr::Or(box acc, box e))
+            .unwrap_or(Self::void())
     }
 
+    /// The `Add` of `reprs` from left to right, or its unit, the empty
+    /// word, if there are none.
     pub const fn sum<M: ~const Iterator<Item = Self>>(reprs: M) -> Self {
-        reprs.reduce(|acc, e| Repr::Add(box acc, box e)).unwrap()
+        reprs.reduce(|acc, e| Repr::Add(box acc, box e))
+            .unwrap_or(Self::zero())
     }
 
+    /// The `And` of `reprs` from left to right, or its unit, every word, if
+    /// there are none.
     pub const fn all<M: ~const Iterator<Item = Self>>(reprs: M) -> Self {
-        reprs.reduce(|acc, e| Repr::And(box acc, box e)).unwrap()
+        reprs.reduce(|acc, e| Repr::And(box acc, box e))
+            .unwrap_or(Self::Exp(box Self::Interval(Interval::full())))
+    }
+
+    /// What any of `reprs` matches, see `any`.
+    pub const fn union_many(reprs: &[Self]) -> Self {
+        Self::any(reprs.iter().cloned())
+    }
+
+    /// What all of `reprs` match, see `all`.
+    pub const fn intersect_many(reprs: &[Self]) -> Self {
+        Self::all(reprs.iter().cloned())
     }
 
     pub const fn repeat(self, count: usize) -> Self {
         Self::prod(vec![self; count].into_iter())
     }
+
+    /// `self+`, one or more repetitions.
+    pub const fn plus(self) -> Self {
+        self.clone().mul(self.exp())
+    }
+
+    /// `self?`, preferring to match `self` over matching nothing.
+    pub const fn opt(self) -> Self {
+        self.or(Self::zero())
+    }
+
+    /// `self{min,max}`, or `self{min,}` if `max` is `None`.
+    pub const fn repeat_range(self, min: usize, max: Option<usize>) -> Self {
+        let rest = match max {
+            None => self.clone().exp(),
+            Some(max) => {
+                assert!(min <= max);
+                (min..max).fold(Self::zero(), |rest, _| {
+                    self.clone().mul(rest).opt()
+                })
+            }
+        };
+        if min == 0 {
+            rest
+        } else {
+            self.repeat(min).mul(rest)
+        }
+    }
 }
 
 #[unconst]
@@ -146,6 +863,10 @@ impl<I: ~const Integral> Repr<I> {
     pub const fn is_anchored_start(&self) -> bool {
         match self {
             Self::Zero(Zero::StartText) => true,
+            Self::Mul(lhs, _) => lhs.is_anchored_start(),
+            Self::Or(lhs, rhs)
+                => lhs.is_anchored_start() && rhs.is_anchored_start(),
+            Self::Cap(repr, _) => repr.is_anchored_start(),
             _ => false
         }
     }
@@ -153,12 +874,25 @@ impl<I: ~const Integral> Repr<I> {
     pub const fn is_anchored_end(&self) -> bool {
         match self {
             Self::Zero(Zero::EndText) => true,
+            Self::Mul(_, rhs) => rhs.is_anchored_end(),
+            Self::Or(lhs, rhs)
+                => lhs.is_anchored_end() && rhs.is_anchored_end(),
+            Self::Cap(repr, _) => repr.is_anchored_end(),
             _ => false
         }
     }
 
+    /// Returns true if every match starts at the start of a line, the start
+    /// of the text included.
     pub const fn is_line_anchored_start(&self) -> bool {
-        unimplemented!()
+        match self {
+            Self::Zero(Zero::StartLine | Zero::StartText) => true,
+            Self::Mul(lhs, _) => lhs.is_line_anchored_start(),
+            Self::Or(lhs, rhs)
+                => lhs.is_line_anchored_start() && rhs.is_line_anchored_start(),
+            Self::Cap(repr, _) => repr.is_line_anchored_start(),
+            _ => false
+        }
     }
 
     pub const fn is_line_anchored_end(&self) -> bool {
@@ -185,14 +919,62 @@ impl<I: ~const Integral> Repr<I> {
     pub const fn is_match_empty(&self) -> bool {
         match self {
             Self::Zero(_) => true,
+            Self::One(seq) => seq.is_empty(),
+            Self::Mul(lhs, rhs) => lhs.is_match_empty() && rhs.is_match_empty(),
             Self::Or(lhs, rhs) => lhs.is_match_empty() || rhs.is_match_empty(),
-            Self::Exp(_) => true,
+            Self::Exp(_) | Self::Lazy(_) => true,
+            Self::Cap(repr, _) => repr.is_match_empty(),
             _ => false
         }
     }
 
+    /// Returns the repr matching the non-empty words `self` matches, as far
+    /// as `is_match_empty` tells. `Exp` compiles the repetition of that
+    /// instead, since `r*` and the repetition of the non-empty part of `r`
+    /// match the same words, but the latter can't go around the loop without
+    /// reading anything, which the engines would otherwise try at each
+    /// position.
+    pub const fn non_empty(&self) -> Self {
+        if !self.is_match_empty() {
+            return self.clone();
+        }
+        match self {
+            Self::Zero(_) | Self::One(_) => Self::void(),
+            // `ab` minus the empty word is `a'b | ab'`, `a'` being the
+            // non-empty part of `a`, which keeps the assertions of `a`.
+            Self::Mul(lhs, rhs) => {
+                let first = lhs.non_empty().mul((**rhs).clone());
+                let second = (**lhs).clone().mul(rhs.non_empty());
+                Self::or_non_void(first, second)
+            }
+            Self::Or(lhs, rhs)
+                => Self::or_non_void(lhs.non_empty(), rhs.non_empty()),
+            Self::Exp(repr) => repr.non_empty().mul(self.clone()),
+            Self::Lazy(repr) => repr.non_empty().mul(self.clone()),
+            Self::Cap(repr, name) => repr.non_empty().cap(name),
+            _ => self.clone(),
+        }
+    }
+
+    /// `lhs | rhs`, leaving out a side that is void.
+    const fn or_non_void(lhs: Self, rhs: Self) -> Self {
+        if lhs.is_void() {
+            rhs
+        } else if rhs.is_void() {
+            lhs
+        } else {
+            lhs.or(rhs)
+        }
+    }
+
+    /// Returns true if this repr matches a single fixed word and nothing
+    /// else: a `One`, or a product of them, without any assertion.
     pub const fn is_literal(&self) -> bool {
-        unimplemented!()
+        match self {
+            Self::One(_) => true,
+            Self::Mul(lhs, rhs) => lhs.is_literal() && rhs.is_literal(),
+            _ => false
+        }
     }
 
     pub const fn is_alternation_literal(&self) -> bool {
@@ -203,6 +985,159 @@ impl<I: ~const Integral> Repr<I> {
             _ => false
         }
     }
+
+    /// Returns the interval whose language is exactly this repr's, if it is
+    /// an `Or` tree of single elements and intervals covering a contiguous
+    /// range, so that it compiles to one `Interval` instruction instead of
+    /// a chain of splits. Void intervals among them are left out.
+    pub const fn try_simplify_to_interval(&self) -> Option<Interval<I>> {
+        let mut intervals = Vec::new();
+        for branch in self.branch_refs() {
+            match branch {
+                Self::One(seq) if seq.len() == 1 => {
+                    let i = seq.get(0)?;
+                    intervals.push(Interval(i, i));
+                }
+                Self::Interval(Interval(from, to)) if to < from => {}
+                Self::Interval(interval) => intervals.push(*interval),
+                _ => return None,
+            }
+        }
+        intervals.sort();
+        let mut intervals = intervals.into_iter();
+        let mut merged = intervals.next()?;
+        for Interval(from, to) in intervals {
+            if merged.1 != I::MAX && from > merged.1.succ() {
+                return None;
+            }
+            merged.1 = max(merged.1, to);
+        }
+        Some(merged)
+    }
+
+    /// Returns the length of the shortest input this repr can match, so any
+    /// shorter input can be rejected without matching.
+    pub const fn min_len(&self) -> usize {
+        match self {
+            Self::Zero(_) => 0,
+            Self::One(seq) => seq.len(),
+            Self::Interval(_) => 1,
+            Self::Mul(lhs, rhs) | Self::Add(lhs, rhs)
+                => lhs.min_len() + rhs.min_len(),
+            Self::And(lhs, rhs) => max(lhs.min_len(), rhs.min_len()),
+            // Walked rather than recursed into, see `required_literals`.
+            Self::Or(..) => self.branch_refs().into_iter()
+                .map(Self::min_len)
+                .min()
+                .unwrap(),
+            Self::Div(_, _) | Self::Exp(_) | Self::Lazy(_) | Self::Not(_)
+                => 0,
+            Self::Cap(repr, _) => repr.min_len(),
+        }
+    }
+
+    /// Returns the length of the longest input this repr can match, or
+    /// `None` if it is unbounded.
+    pub const fn max_len(&self) -> Option<usize> {
+        match self {
+            Self::Zero(_) => Some(0),
+            Self::One(seq) => Some(seq.len()),
+            Self::Interval(_) => Some(1),
+            Self::Mul(lhs, rhs) | Self::Add(lhs, rhs)
+                => lhs.max_len()?.checked_add(rhs.max_len()?),
+            // Both sides match the same text, so the shorter bound holds.
+            Self::And(lhs, rhs) => match (lhs.max_len(), rhs.max_len()) {
+                (Some(lhs), Some(rhs)) => Some(min(lhs, rhs)),
+                (len, None) | (None, len) => len,
+            },
+            Self::Or(lhs, rhs) => Some(max(lhs.max_len()?, rhs.max_len()?)),
+            Self::Div(_, _) | Self::Exp(_) | Self::Lazy(_) | Self::Not(_)
+                => None,
+            Self::Cap(repr, _) => repr.max_len(),
+        }
+    }
+
+    /// Returns true if this repr matches finitely many words, so that they
+    /// can be enumerated. It is false as soon as an `Exp` is reachable
+    /// other than under an `And` with a finite side, and also for `Not`,
+    /// `Div` and `Add`, which it doesn't look into.
+    pub const fn is_finite(&self) -> bool {
+        match self {
+            Self::Zero(_) | Self::One(_) | Self::Interval(_) => true,
+            Self::Mul(lhs, rhs) | Self::Or(lhs, rhs)
+                => lhs.is_finite() && rhs.is_finite(),
+            Self::And(lhs, rhs) => lhs.is_finite() || rhs.is_finite(),
+            Self::Cap(repr, _) => repr.is_finite(),
+            Self::Div(_, _) | Self::Exp(_) | Self::Lazy(_) | Self::Not(_)
+            | Self::Add(_, _) => false,
+        }
+    }
+
+    /// Returns the number of words this repr matches if it `is_finite`,
+    /// or `None` if not or if the number doesn't fit. Branches of `Or` are
+    /// added up and factors of `Mul` multiplied, so a word matched in
+    /// several ways is counted once for each, and assertions are taken to
+    /// hold: the count is exact for unambiguous reprs, and a bound for the
+    /// others.
+    pub const fn language_size(&self) -> Option<u128> {
+        match self {
+            Self::Zero(_) | Self::One(_) => Some(1),
+            Self::Interval(_) if self.is_void() => Some(0),
+            Self::Interval(interval) => Some(interval.num_chars() as u128),
+            Self::Mul(lhs, rhs)
+                => lhs.language_size()?.checked_mul(rhs.language_size()?),
+            Self::Or(lhs, rhs)
+                => lhs.language_size()?.checked_add(rhs.language_size()?),
+            Self::And(lhs, rhs) => match (lhs.language_size(),
+                                          rhs.language_size()) {
+                (Some(lhs), Some(rhs)) => Some(min(lhs, rhs)),
+                (Some(size), None) | (None, Some(size)) => Some(size),
+                (None, None) => None,
+            },
+            Self::Cap(repr, _) => repr.language_size(),
+            Self::Div(_, _) | Self::Exp(_) | Self::Lazy(_) | Self::Not(_)
+            | Self::Add(_, _) => None,
+        }
+    }
+
+    /// Returns the elements that appear in every input this repr matches,
+    /// without duplicates. `Mul` and `And` require the union of both sides,
+    /// `Or` only what is required on both branches.
+    pub const fn required_literals(&self) -> Vec<I> {
+        match self {
+            Self::One(seq) => {
+                let mut literals = Vec::new();
+                for i in seq.iter() {
+                    if !literals.contains(i) {
+                        literals.push(*i);
+                    }
+                }
+                literals
+            }
+            Self::Interval(Interval(from, to)) if from == to => vec![*from],
+            Self::Mul(lhs, rhs) | Self::And(lhs, rhs) => {
+                let mut literals = lhs.required_literals();
+                for i in rhs.required_literals() {
+                    if !literals.contains(&i) {
+                        literals.push(i);
+                    }
+                }
+                literals
+            }
+            Self::Or(..) => {
+                // Long `Or` chains are common, so they are walked rather than
+                // recursed into.
+                self.branch_refs().into_iter()
+                    .map(Self::required_literals)
+                    .reduce(|acc, required| {
+                        acc.into_iter().filter(|i| required.contains(i)).collect()
+                    })
+                    .unwrap()
+            }
+            Self::Cap(repr, _) => repr.required_literals(),
+            _ => Vec::new()
+        }
+    }
 }
 
 /// This trait is my attempt at reducing code duplication and to standardize
@@ -217,6 +1152,163 @@ impl<I: ~const Integral> Repr<I> {
 /// isn't possible in the current public API.
 #[unconst]
 impl<I: ~const Integral> Repr<I> {
+    #[cfg(feature = "std")]
+    /// Returns true if there is a match in `context` starting the search at
+    /// `start`.
+    ///
+    /// # Panics
+    ///
+    /// If the repr can't be compiled, see `try_is_match_at`.
+    pub const fn is_match_at(&self, context: &Context<I>, start: usize)
+        -> bool
+    {
+        // Spans are not reported, so groups needn't be compiled.
+        let options = Options { captures: false, ..Options::new(self.clone()) };
+        options.build().unwrap().is_match_at(context, start)
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns the start and end positions of the leftmost-first match in
+    /// `context` starting the search at `start`. If `anchored` is true, the
+    /// match must start exactly at `start`.
+    ///
+    /// # Panics
+    ///
+    /// If the repr can't be compiled, see `try_find_in`.
+    pub const fn find_in(&self, context: &Context<I>, start: usize,
+                         anchored: bool)
+        -> Option<(usize, usize)>
+    {
+        let exec = self.exec();
+        let m = if anchored {
+            exec.find_anchored_at(context, start)
+        } else {
+            exec.find_at(context, start)
+        };
+        m.map(|m| (m.start(), m.end()))
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `is_match_at`, but rather than panicking if the repr can't be
+    /// compiled, returns why, see `From<CompileError> for MatchError`, and
+    /// `MatchError::StepLimitExceeded` if an engine goes over the step
+    /// limit.
+    pub fn try_is_match_at(&self, context: &Context<I>, start: usize)
+        -> Result<bool, MatchError>
+    {
+        let options = Options { captures: false, ..Options::new(self.clone()) };
+        options.build()?.try_is_match_at(context, start)
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `find_in`, with errors reported as for `try_is_match_at`.
+    pub fn try_find_in(&self, context: &Context<I>, start: usize,
+                       anchored: bool)
+        -> Result<Option<(usize, usize)>, MatchError>
+    {
+        let exec = Exec::new(self.clone())?;
+        let m = if anchored {
+            exec.try_find_anchored_at(context, start)?
+        } else {
+            exec.try_find_at(context, start)?
+        };
+        Ok(m.map(|m| (m.start(), m.end())))
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns the spans of the groups of the leftmost-first match in
+    /// `context`, or `None` if there is no match.
+    pub const fn captures<'c>(&self, context: &'c Context<I>)
+        -> Option<Captures<'c, I>>
+    {
+        self.exec().captures_at(context, 0)
+    }
+
+    /// Compiles this repr into the program the engines run, with the
+    /// default options, for inspecting it or running it with another
+    /// engine. See `Options` to compile with others.
+    ///
+    /// ```
+    /// use repr::Repr;
+    ///
+    /// // `One("ab")` and `Match`.
+    /// let prog = Repr::literal("ab").compile().unwrap();
+    /// assert_eq!(prog.len(), 2);
+    /// assert!(!prog.is_anchored_start);
+    /// ```
+    pub fn compile(&self) -> Result<Program<I>, CompileError> {
+        Compiler::new().compile(slice::from_ref(self))
+    }
+
+    /// Like `compile`, but the program only matches at the start of the
+    /// input, as if the repr began with `Zero::StartText`.
+    ///
+    /// ```
+    /// use repr::Repr;
+    ///
+    /// let prog = Repr::literal("ab").compile_anchored().unwrap();
+    /// assert!(prog.is_anchored_start);
+    /// ```
+    pub fn compile_anchored(&self) -> Result<Program<I>, CompileError> {
+        Self::Zero(Zero::StartText).mul(self.clone()).compile()
+    }
+
+    /// Like `compile`, but capture groups are compiled as their contents,
+    /// without `Save` instructions, for when only whether there is a match,
+    /// or where the whole of it is, matters.
+    ///
+    /// ```
+    /// use repr::Repr;
+    ///
+    /// let repr = Repr::literal("ab").cap("x");
+    /// assert_eq!(repr.compile().unwrap().len(), 4);
+    /// assert_eq!(repr.compile_nocap().unwrap().len(), 2);
+    /// ```
+    pub fn compile_nocap(&self) -> Result<Program<I>, CompileError> {
+        Compiler::new().captures(false).compile(slice::from_ref(self))
+    }
+
+    #[cfg(feature = "std")]
+    /// Compiles this repr with the default options.
+    ///
+    /// # Panics
+    ///
+    /// If the repr can't be compiled, see `CompileError`.
+    const fn exec(&self) -> Exec<I> {
+        Exec::new(self.clone()).unwrap()
+    }
+}
+
+/// Sorts `intervals` and merges those that overlap or are adjacent.
+fn merge_intervals<I: Integral>(mut intervals: Vec<Interval<I>>)
+    -> Vec<Interval<I>>
+{
+    intervals.sort();
+    let mut merged: Vec<Interval<I>> = Vec::new();
+    for interval in intervals {
+        match merged.last_mut() {
+            Some(last) if last.1 == I::MAX || interval.0 <= last.1.succ()
+                => last.1 = max(last.1, interval.1),
+            _ => merged.push(interval),
+        }
+    }
+    merged
+}
+
+/// Pushes `op` of each shrunk `lhs` with `rhs`, then of `lhs` with each
+/// shrunk `rhs`, see `Repr::shrink`.
+fn shrink_binary<I: Integral>(
+    lhs: &Repr<I>,
+    rhs: &Repr<I>,
+    op: fn(Box<Repr<I>>, Box<Repr<I>>) -> Repr<I>,
+    shrunk: &mut Vec<Repr<I>>,
+) {
+    for lhs in lhs.shrink() {
+        shrunk.push(op(box lhs, box rhs.clone()));
+    }
+    for rhs in rhs.shrink() {
+        shrunk.push(op(box lhs.clone(), box rhs));
+    }
 }
 
 /// - `Copy` + `Clone`: possibility of `!` exponentiation
@@ -249,7 +1341,8 @@ pub trait Integral: Copy + ~const Clone
 /// A matching word boundary assertion is always zero-length.
 #[unconst]
 #[derive_const(Default)]
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Zero {
     #[default]
     Any,
@@ -286,3 +1379,34 @@ pub enum Zero {
     /// Match an ASCII-only negation of a word boundary.
     NotWordBoundaryAscii,
 }
+
+#[unconst]
+impl Zero {
+    /// The assertion that holds at the mirrored position once the input is
+    /// reversed, so that `Repr::rev` keeps anchors meaningful.
+    pub const fn rev(self) -> Self {
+        match self {
+            Zero::StartLine => Zero::EndLine,
+            Zero::EndLine => Zero::StartLine,
+            Zero::StartText => Zero::EndText,
+            Zero::EndText => Zero::StartText,
+            zero => zero
+        }
+    }
+
+    /// See `Repr::multi_line`.
+    pub const fn multi_line(self, yes: bool) -> Self {
+        match (self, yes) {
+            (Zero::StartText, true) => Zero::StartLine,
+            (Zero::EndText, true) => Zero::EndLine,
+            (Zero::StartLine, false) => Zero::StartText,
+            (Zero::EndLine, false) => Zero::EndText,
+            (zero, _) => zero
+        }
+    }
+}
+
+pub mod ast;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,165 @@
+//! A plain-data mirror of `Repr<char>`, to carry patterns across an FFI
+//! boundary or a wire format.
+//!
+//! `Ast` has a variant for each of `Repr`, with the operands held in a
+//! `Vec` rather than a `Box`, literals as `String`s and intervals as their
+//! bounds, so that with the `serde` feature it derives `Serialize` and
+//! `Deserialize` without depending on how `Repr` is laid out. Converting
+//! back checks the number of operands of each node.
+
+use alloc::{
+    string::String,
+    vec,
+    vec::Vec,
+};
+
+use crate::error::AstError;
+use crate::interval::Interval;
+use crate::repr::{Repr, Zero};
+use crate::seq::Seq;
+
+/// See the module documentation. `Mul`, `Or`, `Div`, `Add` and `And` have
+/// two operands, the others one.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Ast {
+    Zero(Zero),
+    One(String),
+    Interval(char, char),
+    Mul(Vec<Ast>),
+    Or(Vec<Ast>),
+    Div(Vec<Ast>),
+    Exp(Vec<Ast>),
+    Not(Vec<Ast>),
+    Add(Vec<Ast>),
+    And(Vec<Ast>),
+    Cap(Vec<Ast>, String),
+    Lazy(Vec<Ast>),
+}
+
+impl From<&Repr<char>> for Ast {
+    fn from(repr: &Repr<char>) -> Self {
+        let pair = |lhs: &Repr<char>, rhs: &Repr<char>| {
+            vec![Ast::from(lhs), Ast::from(rhs)]
+        };
+        match repr {
+            Repr::Zero(zero) => Ast::Zero(zero.clone()),
+            Repr::One(seq) => Ast::One(seq.iter().collect()),
+            Repr::Interval(Interval(from, to)) => Ast::Interval(*from, *to),
+            Repr::Mul(lhs, rhs) => Ast::Mul(pair(lhs, rhs)),
+            Repr::Or(lhs, rhs) => Ast::Or(pair(lhs, rhs)),
+            Repr::Div(lhs, rhs) => Ast::Div(pair(lhs, rhs)),
+            Repr::Exp(repr) => Ast::Exp(vec![Ast::from(&**repr)]),
+            Repr::Not(repr) => Ast::Not(vec![Ast::from(&**repr)]),
+            Repr::Add(lhs, rhs) => Ast::Add(pair(lhs, rhs)),
+            Repr::And(lhs, rhs) => Ast::And(pair(lhs, rhs)),
+            Repr::Cap(repr, name)
+                => Ast::Cap(vec![Ast::from(&**repr)], name.clone()),
+            Repr::Lazy(repr) => Ast::Lazy(vec![Ast::from(&**repr)]),
+        }
+    }
+}
+
+impl TryFrom<Ast> for Repr<char> {
+    type Error = AstError;
+
+    fn try_from(ast: Ast) -> Result<Self, AstError> {
+        Ok(match ast {
+            Ast::Zero(zero) => Repr::Zero(zero),
+            Ast::One(s) => Repr::One(Seq::from(s.as_str())),
+            Ast::Interval(from, to) if to < from
+                => return Err(AstError::InvalidInterval(from, to)),
+            Ast::Interval(from, to) => Repr::Interval(Interval(from, to)),
+            Ast::Mul(operands) => {
+                let [lhs, rhs] = operands_of("Mul", operands)?;
+                Repr::Mul(box lhs, box rhs)
+            }
+            Ast::Or(operands) => {
+                let [lhs, rhs] = operands_of("Or", operands)?;
+                Repr::Or(box lhs, box rhs)
+            }
+            Ast::Div(operands) => {
+                let [lhs, rhs] = operands_of("Div", operands)?;
+                Repr::Div(box lhs, box rhs)
+            }
+            Ast::Exp(operands) => {
+                let [repr] = operands_of("Exp", operands)?;
+                Repr::Exp(box repr)
+            }
+            Ast::Not(operands) => {
+                let [repr] = operands_of("Not", operands)?;
+                Repr::Not(box repr)
+            }
+            Ast::Add(operands) => {
+                let [lhs, rhs] = operands_of("Add", operands)?;
+                Repr::Add(box lhs, box rhs)
+            }
+            Ast::And(operands) => {
+                let [lhs, rhs] = operands_of("And", operands)?;
+                Repr::And(box lhs, box rhs)
+            }
+            Ast::Cap(operands, name) => {
+                let [repr] = operands_of("Cap", operands)?;
+                Repr::Cap(box repr, name)
+            }
+            Ast::Lazy(operands) => {
+                let [repr] = operands_of("Lazy", operands)?;
+                Repr::Lazy(box repr)
+            }
+        })
+    }
+}
+
+/// Converts the `N` operands of a `variant` node.
+fn operands_of<const N: usize>(variant: &'static str, operands: Vec<Ast>)
+    -> Result<[Repr<char>; N], AstError>
+{
+    let found = operands.len();
+    let reprs = operands.into_iter()
+        .map(Repr::try_from)
+        .collect::<Result<Vec<_>, _>>()?;
+    reprs.try_into().map_err(|_| AstError::Arity {
+        variant, expected: N, found
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let a = Repr::one('a');
+        let digit = Repr::Interval(Interval('0', '9'));
+        let repr = Repr::Zero(Zero::StartText)
+            .mul(Repr::literal("héllo").or(digit.clone().exp()))
+            .mul(Repr::Div(box Repr::literal("ab"), box a.clone()))
+            .mul(Repr::Not(box a.clone()))
+            .mul(Repr::Add(box a.clone(), box Repr::zero()))
+            .mul(digit.clone().and(Repr::one('5')))
+            .mul(digit.exp_lazy().cap("n"));
+        let ast = Ast::from(&repr);
+        assert_eq!(Repr::try_from(ast.clone()), Ok(repr.clone()));
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&ast).unwrap();
+            let ast: Ast = serde_json::from_str(&json).unwrap();
+            assert_eq!(Repr::try_from(ast), Ok(repr));
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let a = Ast::One(String::from("a"));
+        assert_eq!(Repr::try_from(Ast::Mul(vec![a.clone()])),
+                   Err(AstError::Arity {
+                       variant: "Mul", expected: 2, found: 1
+                   }));
+        assert_eq!(Repr::try_from(Ast::Exp(vec![a.clone(), a])),
+                   Err(AstError::Arity {
+                       variant: "Exp", expected: 1, found: 2
+                   }));
+        assert_eq!(Repr::try_from(Ast::Interval('z', 'a')),
+                   Err(AstError::InvalidInterval('z', 'a')));
+    }
+}
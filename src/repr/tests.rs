@@ -0,0 +1,1122 @@
+use std::collections::{BTreeMap, hash_map::DefaultHasher};
+use std::hash::{Hash, Hasher};
+use std::{cell::RefCell, panic::AssertUnwindSafe};
+
+use crate::backtrack::Bounded;
+use crate::compile::Compiler;
+use crate::context::Context;
+use crate::error::{CompileError, MatchError, ReplaceError};
+use crate::exec::Exec;
+use crate::interval::Interval;
+use crate::partition::{Match, Partition};
+use crate::program::{Inst, ProgramCacheInner};
+use crate::repr::{Repr, Zero};
+use crate::seq::Seq;
+
+fn context(s: &str) -> Context<char> {
+    Context::from(s)
+}
+
+fn is_match(repr: &Repr<char>, s: &str) -> bool {
+    Exec::new(repr.clone()).unwrap().is_match(&context(s))
+}
+
+#[test]
+fn multi_line() {
+    let repr = Repr::one('a').mul(Repr::Zero(Zero::EndText));
+    assert!(!is_match(&repr, "a\nb"));
+
+    let repr = repr.multi_line(true);
+    assert_eq!(repr, Repr::one('a').mul(Repr::Zero(Zero::EndLine)));
+    assert!(is_match(&repr, "a\nb"));
+
+    let repr = repr.multi_line(false);
+    assert!(!is_match(&repr, "a\nb"));
+    assert!(is_match(&repr, "b\na"));
+}
+
+#[test]
+fn find_reports_byte_offsets() {
+    let context = context("naïve");
+    assert_eq!(context.len(), 5);
+    assert_eq!(context.byte_offset(3), 4);
+    assert_eq!(context.byte_offset(5), 6);
+
+    let repr = Repr::one('v').mul(Repr::one('e'));
+    assert_eq!(repr.find_in(&context, 0, false), Some((3, 5)));
+    assert_eq!(repr.find("naïve"), Some((4, 6)));
+}
+
+#[test]
+fn min_max_len() {
+    let digit = Repr::Interval(Interval('0', '9'));
+    let date = digit.clone().repeat(4)
+        .mul(Repr::one('-'))
+        .mul(digit.clone().repeat(2))
+        .mul(Repr::one('-'))
+        .mul(digit.clone().repeat(2));
+    assert_eq!(date.min_len(), 10);
+    assert_eq!(date.max_len(), Some(10));
+
+    let repr = Repr::one('a').or(digit.clone().repeat(3))
+        .mul(digit.clone().exp());
+    assert_eq!(repr.min_len(), 1);
+    assert_eq!(repr.max_len(), None);
+
+    let both = digit.clone().repeat(2).and(digit.clone().mul(digit.clone()));
+    assert_eq!(both.max_len(), Some(2));
+    let both = digit.clone().exp().and(digit.clone().repeat(3));
+    assert_eq!(both.max_len(), Some(3));
+    assert_eq!(digit.clone().exp().and(digit.exp()).max_len(), None);
+}
+
+#[test]
+fn add_is_shuffle() {
+    let anchored = |repr: Repr<char>| Repr::Zero(Zero::StartText)
+        .mul(repr)
+        .mul(Repr::Zero(Zero::EndText));
+
+    let repr = anchored(Repr::zero().add(Repr::one('a')));
+    assert!(is_match(&repr, "a"));
+    assert!(!is_match(&repr, ""));
+
+    let repr = anchored(Repr::One(Seq::from("ab")).add(Repr::one('c')));
+    for s in ["abc", "acb", "cab"] {
+        assert!(is_match(&repr, s), "{}", s);
+    }
+    for s in ["bac", "cba", "ab", "abcc"] {
+        assert!(!is_match(&repr, s), "{}", s);
+    }
+
+    let repr = Repr::one('a').exp().add(Repr::one('b'));
+    assert_eq!(Exec::new(repr).unwrap_err(), CompileError::UnsupportedAdd);
+}
+
+#[test]
+fn div_is_left_quotient() {
+    let anchored = |repr: Repr<char>| Repr::Zero(Zero::StartText)
+        .mul(repr)
+        .mul(Repr::Zero(Zero::EndText));
+
+    let target = Repr::One(Seq::from("abc")).or(Repr::One(Seq::from("abd")));
+    let repr = anchored(Repr::One(Seq::from("ab")).div(target));
+    assert!(is_match(&repr, "c"));
+    assert!(is_match(&repr, "d"));
+    assert!(!is_match(&repr, "abc"));
+    assert!(!is_match(&repr, ""));
+
+    let target = Repr::one('a').exp().mul(Repr::one('b'));
+    let repr = anchored(Repr::one('a').div(target));
+    assert!(is_match(&repr, "b"));
+    assert!(is_match(&repr, "aab"));
+    assert!(!is_match(&repr, ""));
+
+    let repr = Repr::one('x').div(Repr::one('a'));
+    assert!(!is_match(&repr, ""));
+    assert!(!is_match(&repr, "a"));
+
+    let repr = Repr::one('a').exp().div(Repr::one('a'));
+    assert_eq!(Exec::new(repr).unwrap_err(), CompileError::UnsupportedDiv);
+}
+
+#[test]
+fn literal() {
+    assert_eq!(Repr::literal(""), Repr::zero());
+    assert_eq!(Repr::literal("ab"), Repr::one('a').mul(Repr::one('b')));
+
+    let repr = Repr::literal("abc");
+    assert!(repr.is_match("xabcy"));
+    assert!(repr.is_full_match("abc"));
+    assert!(!repr.is_full_match("xabcy"));
+    assert!(!repr.is_match("ab"));
+}
+
+#[test]
+fn from_chars() {
+    let chars = ['x', 'a', 'b', 'c', 'y'];
+    let context = Context::from_chars(&chars);
+    assert_eq!(context, Context::from_slice(&chars));
+    assert_eq!(Repr::literal("abc").find_in(&context, 0, false), Some((1, 4)));
+    assert_eq!(context.byte_offset(4), 4);
+}
+
+#[test]
+fn dedup_or() {
+    let lower = Repr::Interval(Interval('a', 'z'));
+    assert!(Repr::one('b').le(&lower));
+    assert!(!lower.le(&Repr::one('b')));
+    assert!(Repr::one('b').le(&Repr::one('a').or(lower.clone())));
+
+    assert_eq!(lower.clone().or(Repr::one('b')).dedup_or(), lower);
+    assert_eq!(
+        lower.clone().or(Repr::one('1')).or(lower.clone()).dedup_or(),
+        lower.clone().or(Repr::one('1'))
+    );
+
+    // An earlier branch contained in a later one has priority, so it stays.
+    let repr = Repr::one('b').or(lower.clone().exp());
+    assert!(Repr::one('b').le(&lower.exp()));
+    assert_eq!(repr.clone().dedup_or(), repr);
+    assert_eq!(repr.find_in(&context("bcd"), 0, false), Some((0, 1)));
+
+    // Nor is a group dropped, even if it never matches.
+    let repr = lower.clone().or(Repr::one('b').cap("b"));
+    assert_eq!(repr.clone().dedup_or(), repr);
+    assert_eq!(lower.clone().union(Repr::one('b').cap("b")), repr);
+    assert_eq!(repr.dedup_or().capture_names(), ["b"]);
+}
+
+#[test]
+fn replace_all_write_agrees_with_replace_all() {
+    let repr = Repr::Interval(Interval('0', '9'))
+        .mul(Repr::Interval(Interval('0', '9')).exp());
+    let text = "1 apple, 23 pears and ü 456";
+    let cases = [
+        ("<$0>", "<1> apple, <23> pears and ü <456>"),
+        ("${0}s", "1s apple, 23s pears and ü 456s"),
+        ("$$", "$ apple, $ pears and ü $"),
+        ("$$0", "$0 apple, $0 pears and ü $0"),
+        ("$name", " apple,  pears and ü "),
+        ("$", "$ apple, $ pears and ü $"),
+        ("${0", "${0 apple, ${0 pears and ü ${0"),
+    ];
+    for (replacement, expected) in cases {
+        let mut out = String::new();
+        repr.replace_all_write(text, replacement, &mut out).unwrap();
+        assert_eq!(out, repr.replace_all(text, replacement), "{}", replacement);
+        assert_eq!(out, expected, "{}", replacement);
+    }
+
+    let div = repr.clone().div(Repr::literal("12"));
+    let mut out = String::new();
+    let error = MatchError::UnsupportedOperator("Div");
+    assert_eq!(div.replace_all_write(text, "", &mut out),
+               Err(ReplaceError::Match(error)));
+    assert_eq!(out, "");
+
+    /// Fails once more than `self.0` bytes were written.
+    struct Short(usize);
+    impl core::fmt::Write for Short {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            self.0 = self.0.checked_sub(s.len()).ok_or(core::fmt::Error)?;
+            Ok(())
+        }
+    }
+    assert_eq!(repr.replace_all_write(text, "<$0>", &mut Short(8)),
+               Err(ReplaceError::Write(core::fmt::Error)));
+}
+
+#[test]
+fn hash_and_ord() {
+    fn hash(repr: &Repr<char>) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        repr.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let lhs = Repr::literal("ab").or(Repr::Interval(Interval('0', '9')).exp());
+    let rhs = Repr::literal("ab").or(Repr::Interval(Interval('0', '9')).exp());
+    assert_eq!(hash(&lhs), hash(&rhs));
+    assert_ne!(hash(&lhs), hash(&Repr::literal("ab")));
+    assert_ne!(hash(&lhs), hash(&lhs.clone().rev()));
+
+    let mut map = BTreeMap::new();
+    map.insert(lhs.clone(), 1);
+    map.insert(Repr::zero(), 0);
+    assert_eq!(map.get(&rhs), Some(&1));
+    assert_eq!(map.keys().next(), Some(&Repr::zero()));
+}
+
+#[test]
+fn derivative() {
+    fn matches(repr: &Repr<char>, s: &str) -> bool {
+        s.chars().fold(repr.clone(), |repr, c| repr.derivative(c)).nullable()
+    }
+
+    let digit = Repr::Interval(Interval('0', '9'));
+    let repr = Repr::literal("ab").or(digit.clone().plus());
+    assert_eq!(Repr::literal("ab").derivative('a'), Repr::one('b'));
+    assert!(Repr::literal("ab").derivative('b').is_void());
+    for (s, expected) in [("ab", true), ("a", false), ("123", true), ("", false),
+                          ("ab1", false)] {
+        assert_eq!(matches(&repr, s), expected, "{}", s);
+    }
+
+    let not = Repr::Not(box Repr::literal("ab"));
+    assert!(matches(&not, ""));
+    assert!(matches(&not, "abc"));
+    assert!(!matches(&not, "ab"));
+
+    let and = digit.clone().exp().and(Repr::one('1').mul(digit.clone()));
+    assert!(matches(&and, "12"));
+    assert!(!matches(&and, "21"));
+    assert!(!matches(&and, "1"));
+}
+
+/// Every string over `a`, `b` and `1` of length at most 4.
+fn words() -> Vec<String> {
+    let mut words = vec![String::new()];
+    for i in 0.. {
+        if words[i].len() == 4 {
+            break;
+        }
+        for c in ['a', 'b', '1'] {
+            words.push(format!("{}{}", words[i], c));
+        }
+    }
+    words
+}
+
+#[test]
+fn derivative_agrees_with_backtrack() {
+    let digit = Repr::Interval(Interval('0', '9'));
+    let ab = Repr::one('a').or(Repr::one('b'));
+    let reprs = [
+        ab.clone().exp().mul(Repr::one('1')),
+        Repr::literal("ab").or(ab.clone().mul(digit.clone())).exp(),
+        ab.clone().exp().mul(ab.clone().exp()),
+    ];
+    for repr in reprs {
+        for word in words() {
+            assert_eq!(repr.is_match_derivative(&word), repr.is_full_match(&word),
+                       "{:?} {}", repr, word);
+        }
+    }
+
+    // Reprs with `Not`, `And` or a `Div` by more than a literal can't be
+    // compiled, so compare them with equivalent ones that can.
+    let pairs = [
+        (Repr::Not(box Repr::Not(box ab.clone().exp())), ab.clone().exp()),
+        (ab.clone().exp().and(Repr::one('a').mul(ab.clone().exp())),
+         Repr::one('a').mul(ab.clone().exp())),
+        (ab.clone().plus().and(Repr::Not(box ab.clone().mul(ab.clone()).plus())),
+         ab.clone().mul(ab.clone()).exp().mul(ab.clone())),
+        (ab.clone().exp().div(ab.clone().exp().mul(Repr::one('1'))),
+         ab.clone().exp().mul(Repr::one('1'))),
+        (ab.clone().mul(Repr::one('b')).div(Repr::literal("ab1")
+             .or(Repr::literal("bb")).or(Repr::literal("b1"))),
+         Repr::one('1').or(Repr::zero())),
+    ];
+    for (repr, equivalent) in pairs {
+        for word in words() {
+            assert_eq!(repr.is_match_derivative(&word),
+                       equivalent.is_full_match(&word),
+                       "{:?} {}", repr, word);
+        }
+    }
+}
+
+#[test]
+fn complement() {
+    let repr = Repr::literal("abc").complement();
+    for s in ["", "ab", "abcd", "abd", "x", "😀"] {
+        assert!(repr.is_match_derivative(s), "{}", s);
+        assert!(repr.is_full_match(s), "{}", s);
+    }
+    assert!(!repr.is_match_derivative("abc"));
+    assert!(!repr.is_full_match("abc"));
+
+    let ab = Repr::one('a').or(Repr::one('b'));
+    let repr = ab.exp().mul(Repr::one('1'));
+    let complement = repr.clone().complement();
+    let twice = complement.clone().complement();
+    for word in words() {
+        let expected = repr.is_match_derivative(&word);
+        assert_eq!(complement.is_match_derivative(&word), !expected, "{}", word);
+        assert_eq!(twice.is_match_derivative(&word), expected, "{}", word);
+    }
+}
+
+#[test]
+fn find_at_anchored() {
+    let repr = Repr::literal("ab");
+    let text = "xxabxab";
+    assert_eq!(repr.find_at(text, 1, false), Some((2, 4)));
+    assert_eq!(repr.find_at(text, 1, true), None);
+    assert_eq!(repr.find_at(text, 2, true), Some((2, 4)));
+    assert_eq!(repr.find_at(text, 4, false), Some((5, 7)));
+    assert_eq!(repr.find_at(text, 4, true), None);
+
+    let repr = Repr::one('b').exp();
+    assert_eq!(repr.find_at("ébb", 2, true), Some((2, 4)));
+    assert_eq!(repr.find_at("aéb", 0, true), Some((0, 0)));
+}
+
+#[test]
+fn tokenize_with_find_anchored_at() {
+    let token = Repr::Interval(Interval('a', 'z')).plus()
+        .or(Repr::Interval(Interval('0', '9')).plus())
+        .or(Repr::one(' ').or(Repr::one('é')));
+    let text = "let é 42 in x1";
+    // Compiled and decoded once for the whole text.
+    let exec = Exec::new(token.clone()).unwrap();
+    let context = context(text);
+    let mut at = 0;
+    let mut tokens = Vec::new();
+    while let Some(m) = exec.find_anchored_at(&context, at) {
+        let (start, end)
+            = (context.byte_offset(m.start()), context.byte_offset(m.end()));
+        assert_eq!(token.find_at(text, start, true), Some((start, end)));
+        tokens.push(&text[start..end]);
+        at = m.end();
+    }
+    assert_eq!(at, context.len());
+    assert_eq!(tokens, ["let", " ", "é", " ", "42", " ", "in", " ", "x", "1"]);
+}
+
+#[test]
+fn literal_run() {
+    let mut seq = Seq::from_slice(&['a', 'b']);
+    seq.push('c');
+    assert_eq!(seq, Seq::from_slice(&['a', 'b', 'c']));
+    let repr = Repr::One(seq);
+
+    let prog = Compiler::new().compile(&[repr.clone()]).unwrap();
+    let runs = prog.iter().filter(|inst| matches!(inst, Inst::One { .. }));
+    assert_eq!(runs.count(), 1);
+
+    assert!(is_match(&repr, "xxabcx"));
+    assert!(is_match(&repr, "ababc"));
+    assert!(!is_match(&repr, "abxc"));
+    assert!(!is_match(&repr, "ab"));
+    assert_eq!(repr.find_in(&context("aababc"), 0, false), Some((3, 6)));
+    let repr = repr.or(Repr::one('a').mul(Repr::one('b'))).exp();
+    assert!(repr.is_full_match("ababcab"));
+    assert!(!repr.is_full_match("abca"));
+}
+
+#[test]
+fn context_edges() {
+    let empty = context("");
+    assert_eq!((empty.prev(0), empty.next(0)), (None, None));
+    let ab = context("ab");
+    assert_eq!((ab.prev(0), ab.next(0)), (None, Some('a')));
+    assert_eq!((ab.prev(2), ab.next(2)), (Some('b'), None));
+
+    for (zero, at_start, at_end) in [
+        (Zero::StartLine, true, false),
+        (Zero::EndLine, false, true),
+        (Zero::StartText, true, false),
+        (Zero::EndText, false, true),
+        (Zero::WordBoundaryAscii, true, true),
+        (Zero::NotWordBoundaryAscii, false, false),
+        (Zero::Any, true, true),
+    ] {
+        assert_eq!(ab.is_empty_match(0, &zero, '\n', false), at_start,
+                   "{:?}", zero);
+        assert_eq!(ab.is_empty_match(2, &zero, '\n', false), at_end,
+                   "{:?}", zero);
+        assert_eq!(empty.is_empty_match(0, &zero, '\n', false),
+                   zero != Zero::WordBoundaryAscii, "{:?}", zero);
+    }
+
+    let word = Repr::Zero(Zero::WordBoundaryAscii)
+        .mul(Repr::one('a'))
+        .mul(Repr::Zero(Zero::WordBoundaryAscii));
+    assert!(is_match(&word, "a"));
+    assert!(is_match(&word, "b a"));
+    assert!(!is_match(&word, "ab"));
+    assert!(is_match(&Repr::Zero(Zero::StartLine), ""));
+    assert!(is_match(&Repr::one('b').mul(Repr::Zero(Zero::EndLine)), "a\nb"));
+}
+
+#[test]
+fn lattice() {
+    let (a, b) = (Repr::one('a'), Repr::one('b'));
+    let reprs = [
+        Repr::zero(),
+        a.clone(),
+        b.clone(),
+        Repr::Interval(Interval('a', 'b')),
+        a.clone().exp(),
+        Repr::literal("ab"),
+        a.clone().or(b.clone()),
+        b.clone().mul(a.clone().exp()),
+    ];
+    for x in reprs.iter() {
+        assert_eq!(x.clone().join(x.clone()), *x);
+        assert_eq!(x.clone().meet(x.clone()), *x);
+        for y in reprs.iter() {
+            let join = x.clone().join(y.clone());
+            let meet = x.clone().meet(y.clone());
+            assert!(x.le(&join) && y.le(&join), "{:?} {:?}", x, y);
+            assert!(meet.le(x) && meet.le(y), "{:?} {:?}", x, y);
+            assert_eq!(join, y.clone().join(x.clone()));
+            assert_eq!(meet, x.clone().meet(y.clone()));
+            assert_eq!(meet, y.clone().meet(x.clone()));
+            assert_eq!(x.clone().join(meet.clone()), *x, "{:?} {:?}", x, y);
+            assert_eq!(x.clone().meet(join.clone()), *x, "{:?} {:?}", x, y);
+            for word in words() {
+                let (x, y) = (x.is_match_derivative(&word),
+                              y.is_match_derivative(&word));
+                assert_eq!(join.is_match_derivative(&word), x || y);
+                assert_eq!(meet.is_match_derivative(&word), x && y);
+            }
+        }
+    }
+}
+
+#[test]
+fn dot_grapheme() {
+    let grapheme = Repr::dot_grapheme();
+    assert_eq!(Repr::dot().find("e\u{301}"), Some((0, 1)));
+    assert_eq!(grapheme.find("e\u{301}"), Some((0, 3)));
+    for cluster in ["e\u{301}", "a", "\r\n", "\u{1100}\u{1161}\u{11A8}", "\u{AC00}",
+                    "\u{1F1EB}\u{1F1F7}", "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}",
+                    "\u{915}\u{94D}"] {
+        assert!(grapheme.is_full_match(cluster), "{:?}", cluster);
+    }
+    assert!(!grapheme.is_full_match("ab"));
+    assert!(!grapheme.is_full_match("\u{1F1EB}\u{1F1F7}\u{1F1EB}"));
+    assert!(!grapheme.is_match("\n"));
+    assert!(grapheme.clone().mul(grapheme).is_full_match("e\u{301}\u{1F1EB}\u{1F1F7}"));
+}
+
+#[test]
+fn count_matches() {
+    let date = Repr::try_from(r"\d{4}-\d{2}-\d{2}").unwrap();
+    assert_eq!(date.count_matches("2012-03-14, 2013-01-01 and 2014-07-05"), 3);
+    assert_eq!(date.count_matches("no dates"), 0);
+    for (repr, text) in [
+        (Repr::one('a').exp(), "baaab"),
+        (Repr::zero(), "abc"),
+        (Repr::literal("ab").or(Repr::zero()), "abxab"),
+    ] {
+        let context = context(text);
+        assert_eq!(repr.count_matches(text),
+                   Partition::new(&context, repr.clone()).count(),
+                   "{:?} {:?}", repr, text);
+    }
+}
+
+#[test]
+fn captures() {
+    let digit = Repr::Interval(Interval('0', '9'));
+    let date = digit.clone().repeat(4).cap("year")
+        .mul(Repr::one('-'))
+        .mul(digit.clone().repeat(2).cap("month"))
+        .mul(Repr::one('-').mul(digit.clone().repeat(2).cap("day")).opt());
+    let context = context("on 2012-03, 2013-01-01");
+    let caps = date.captures(&context).unwrap();
+    assert_eq!(caps.len(), 4);
+    let span = |m: Option<Match<char>>| m.map(|m| (m.start(), m.end()));
+    assert_eq!(span(caps.get(0)), Some((3, 10)));
+    assert_eq!(span(caps.name("year")), Some((3, 7)));
+    assert_eq!(span(caps.get(1)), Some((3, 7)));
+    assert_eq!(span(caps.name("month")), Some((8, 10)));
+    assert_eq!(caps.name("day"), None);
+    assert_eq!(caps.name("hour"), None);
+    assert_eq!(caps.get(4), None);
+
+    // The copies of a repeated group share it, and it reports the last.
+    let pairs = Repr::literal("ab").or(Repr::literal("cd")).cap("pair").plus();
+    let context = self::context("xabcdab");
+    let caps = pairs.captures(&context).unwrap();
+    assert_eq!(span(caps.get(0)), Some((1, 7)));
+    assert_eq!(span(caps.name("pair")), Some((5, 7)));
+
+    let date = Repr::try_from(r"(?P<y>\d{4})-(?P<m>\d\d)-(?P<d>\d\d)").unwrap();
+    assert_eq!(
+        date.replace_all("2012-03-14, 2013-01-01 and 2014-07-05", "$m/$d/$y"),
+        "03/14/2012, 01/01/2013 and 07/05/2014"
+    );
+}
+
+#[test]
+fn deep_or() {
+    let mut repr = Repr::one('a');
+    for _ in 0..100_000 {
+        repr = Repr::one('b').or(repr);
+    }
+    assert_eq!(repr.depth(), 100_001);
+    assert_eq!(repr.node_count(), 200_001);
+    assert_eq!(repr.required_literals(), Vec::<char>::new());
+    assert_eq!(repr.min_len(), 1);
+    let prog = Compiler::new().size_limit(1 << 30)
+        .compile(std::slice::from_ref(&repr))
+        .unwrap();
+    assert_eq!(prog.matches.len(), 1);
+    let mut repr = repr.rev();
+    assert_eq!(repr.depth(), 100_001);
+    // Dropping recurses as well, so take the chain apart first.
+    while let Repr::Or(_, rhs) = repr {
+        repr = *rhs;
+    }
+    assert_eq!(repr, Repr::one('a'));
+}
+
+#[test]
+fn depth() {
+    let a = Repr::one('a');
+    assert_eq!(a.depth(), 1);
+    assert_eq!(a.node_count(), 1);
+    let repr = a.clone().mul(a.clone().or(Repr::dot()).exp()).cap("x");
+    assert_eq!(repr.depth(), 6);
+    assert_eq!(repr.node_count(), 9);
+    assert_eq!(repr.clone().rev(), a.clone().or(Repr::dot()).exp().mul(a).cap("x"));
+    assert_eq!(a.clone().div(a.clone()).mul(a).try_rev(),
+               Err(CompileError::UnsupportedDiv));
+}
+
+#[test]
+fn sub() {
+    let repr = Repr::from('a'..='z') - Repr::one('e');
+    assert!(!repr.is_match("e"));
+    assert!(repr.is_match("f"));
+    assert!(repr.is_match("ef"));
+    let repr = Repr::from('a'..='z') - Repr::one('e') | '_';
+    assert!(repr.is_match("_"));
+    assert!(!repr.is_match("e"));
+}
+
+#[test]
+fn not() {
+    let repr = !Repr::from('0'..='9');
+    assert!(repr.is_full_match("a"));
+    assert!(repr.is_full_match(""));
+    assert!(repr.is_full_match("77"));
+    assert!(!repr.is_full_match("7"));
+    assert_eq!(!Repr::one('a'), Repr::one('a').not());
+}
+
+#[test]
+fn optional_capture_names() {
+    let a = Repr::one('a');
+    let b = Repr::one('b');
+    let repr = a.clone().cap("x")
+        .mul(b.clone().cap("y").opt())
+        .mul(a.clone().cap("z").or(b.clone().cap("z")))
+        .mul(a.clone().cap("w").exp())
+        .mul(b.clone().cap("v").plus());
+    assert_eq!(repr.capture_names(), ["x", "y", "z", "w", "v"]);
+    assert_eq!(repr.optional_capture_names(), ["y", "w"]);
+    assert_eq!(a.clone().cap("x").or(b).optional_capture_names(), ["x"]);
+    assert!(a.optional_capture_names().is_empty());
+}
+
+#[test]
+fn after_prefix() {
+    let digit = Repr::Interval(Interval('0', '9'));
+    let year = digit.clone().repeat(4);
+    let rest = year.after_prefix("20");
+    for word in words() {
+        assert_eq!(rest.is_match_derivative(&word),
+                   digit.clone().repeat(2).is_match_derivative(&word), "{}", word);
+    }
+    assert!(!rest.nullable());
+    assert!(rest.after_prefix("14").nullable());
+    assert!(year.after_prefix("2a").is_void());
+    assert_eq!(year.after_prefix(""), year.clone().simplify());
+}
+
+#[test]
+fn many() {
+    let a = Repr::one('a');
+    let b = Repr::one('b');
+    assert!(Repr::<char>::union_many(&[]).is_void());
+    assert!(!Repr::<char>::union_many(&[]).is_match(""));
+    assert_eq!(Repr::<char>::intersect_many(&[]),
+               Repr::Exp(box Repr::Interval(Interval::full())));
+    assert!(Repr::<char>::intersect_many(&[]).is_match_derivative("ab"));
+    assert_eq!(Repr::prod(core::iter::empty()), Repr::<char>::zero());
+    assert_eq!(Repr::sum(core::iter::empty()), Repr::<char>::zero());
+    assert_eq!(a.clone().repeat(0), Repr::zero());
+
+    assert_eq!(Repr::union_many(&[a.clone()]), a);
+    assert_eq!(Repr::intersect_many(&[a.clone()]), a);
+    assert_eq!(Repr::union_many(&[a.clone(), b.clone()]), a.clone().or(b.clone()));
+    assert_eq!(Repr::intersect_many(&[a.clone(), b.clone()]), a.and(b));
+}
+
+#[test]
+fn equivalent() {
+    let a = Repr::one('a');
+    let b = Repr::one('b');
+    assert!(a.clone().or(b.clone()).equivalent(&b.clone().or(a.clone())));
+    assert!(a.clone().exp().exp().equivalent(&a.clone().exp()));
+    assert!(a.clone().mul(a.clone().exp())
+        .equivalent(&a.clone().exp().mul(a.clone())));
+    assert!(a.clone().or(b.clone()).exp()
+        .equivalent(&a.clone().exp().mul(b.clone().mul(a.clone().exp())).exp()
+            .mul(a.clone().exp())));
+    assert!(Repr::literal("ab").cap("x").equivalent(&Repr::literal("ab")));
+    assert!(!a.clone().exp().equivalent(&a.clone().plus()));
+    assert!(!a.clone().equivalent(&b.clone()));
+    // Through complement and intersection.
+    assert!(a.clone().not().not().equivalent(&a));
+    assert!(a.clone().or(b.clone()).and(a.clone().exp())
+        .equivalent(&a.clone()));
+    assert!(a.clone().sub(a.clone()).equivalent(&Repr::void()));
+    assert!(!a.clone().not().equivalent(&b.clone().not()));
+    // Agrees with matching word by word.
+    let lhs = a.clone().or(b.clone()).exp().mul(b.clone());
+    let rhs = a.clone().exp().mul(b.clone()).plus();
+    assert!(lhs.equivalent(&rhs));
+    for word in words() {
+        assert_eq!(lhs.is_match_derivative(&word),
+                   rhs.is_match_derivative(&word), "{}", word);
+    }
+}
+
+#[test]
+fn push_str() {
+    let text = "héllo wörld 12\nbaz 345 ☃6";
+    let repr = Repr::Interval(Interval('0', '9')).plus()
+        .or(Repr::Interval(Interval('a', 'ö')).plus());
+    let expected: Vec<_>
+        = Partition::new(&context(text), repr.clone()).collect();
+    for chunk in [1, 2, 3, 5, 8] {
+        let mut context = context("");
+        let mut rest = text;
+        while !rest.is_empty() {
+            let mut at = chunk.min(rest.len());
+            while !rest.is_char_boundary(at) {
+                at += 1;
+            }
+            context.push_str(&rest[..at]);
+            rest = &rest[at..];
+        }
+        assert_eq!(context, Context::from(text), "{}", chunk);
+        let found: Vec<_> = Partition::new(&context, repr.clone()).collect();
+        assert_eq!(found, expected, "{}", chunk);
+    }
+
+    // `EndText` moves with the end.
+    let end = Repr::literal("ab").mul(Repr::Zero(Zero::EndText));
+    let mut context = context("ab");
+    assert!(end.is_match_at(&context, 0));
+    context.push_str("c");
+    assert!(!end.is_match_at(&context, 0));
+    assert_eq!(context.byte_offset(3), 3);
+
+    let mut chars = Context::from_chars(&['a', 'é']);
+    chars.push_str("☃");
+    assert_eq!(chars.byte_offset(3), 3);
+}
+
+#[test]
+fn shrink() {
+    let repr = Repr::literal("ab").or(Repr::one('c'))
+        .mul(Repr::one('b').exp())
+        .cap("x");
+    let shrunk = repr.shrink();
+    assert_eq!(shrunk, repr.shrink());
+    assert_eq!(shrunk[0], Repr::zero());
+    assert!(shrunk.iter().all(|candidate| *candidate != repr
+        && candidate.node_count() <= repr.node_count()));
+    assert!(Repr::<char>::zero().shrink().is_empty());
+    assert_eq!(Repr::literal("abc").shrink(),
+               [Repr::zero(), Repr::literal("bc"), Repr::literal("ab")]);
+
+    // Keeping the first candidate that still fails.
+    let fails = |repr: &Repr<char>| repr.is_match_derivative("b");
+    let mut repr = repr;
+    while let Some(next) = repr.shrink().into_iter().find(fails) {
+        repr = next;
+    }
+    assert_eq!(repr, Repr::one('b'));
+}
+
+#[test]
+fn exp_of_nullable() {
+    let (a, b) = (Repr::one('a'), Repr::one('b'));
+    // `(a?)*` loops over `a` alone, like `a*`.
+    let repr = a.clone().opt().exp();
+    assert_eq!(repr.non_empty(), a.clone().mul(repr.clone()));
+    assert_eq!(repr.compile().unwrap().len(),
+               a.clone().exp().compile().unwrap().len());
+    let text = "a".repeat(100_000);
+    assert_eq!(repr.find(&text), Some((0, 100_000)));
+    assert_eq!(repr.clone().mul(b.clone()).find("aab"), Some((0, 3)));
+
+    let repr = a.clone().opt().mul(b.clone().opt()).exp();
+    assert_eq!(repr.find("abbab"), Some((0, 5)));
+    assert_eq!(repr.find("c"), Some((0, 0)));
+    assert_eq!(Repr::zero().exp().find("a"), Some((0, 0)));
+    let lazy = Repr::<char>::zero().cap("x").exp_lazy();
+    assert_eq!(lazy.find("a"), Some((0, 0)));
+}
+
+#[test]
+fn from_char_set() {
+    let repr = Repr::from_char_set(&['x', 'c', 'a', 'b', 'a']);
+    assert_eq!(repr, Repr::Interval(Interval('a', 'c')).or(Repr::one('x')));
+    for (s, expected) in [("a", true), ("c", true), ("x", true), ("d", false),
+                          ("w", false)] {
+        assert_eq!(repr.is_full_match(s), expected, "{}", s);
+    }
+    assert_eq!(Repr::from_char_set(&['\u{D7FF}', '\u{E000}']),
+               Repr::Interval(Interval('\u{D7FF}', '\u{E000}')));
+    assert!(Repr::from_char_set(&[]).is_void());
+}
+
+#[test]
+fn as_literal() {
+    let abc = Repr::literal("abc");
+    assert!(abc.is_literal());
+    assert_eq!(abc.as_literal().as_deref(), Some("abc"));
+    let split = Repr::one('a').mul(Repr::literal("bé"));
+    assert_eq!(split.as_literal().as_deref(), Some("abé"));
+    assert!(!crate::DIGIT.is_literal());
+    assert_eq!(crate::DIGIT.as_literal(), None);
+    let anchored = Repr::one('a').mul(Repr::Zero(Zero::EndText));
+    assert_eq!(anchored.as_literal(), None);
+
+    // `find` takes the fast path, with byte offsets as before.
+    assert_eq!(split.find("xyabé"), Some((2, 6)));
+    assert_eq!(split.find_at("abé abé", 1, false), Some((5, 9)));
+    assert_eq!(split.find_at("abé abé", 1, true), None);
+    assert_eq!(split.find_at("abé abé", 5, true), Some((5, 9)));
+}
+
+#[test]
+fn keyword_prefixes() {
+    use crate::derivative::{LiteralSearcher, Literals};
+
+    let keywords = [
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn",
+        "else", "enum", "extern", "false", "fn", "for", "if", "impl", "in",
+        "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+        "self", "static", "struct", "super", "trait", "true", "type",
+        "unsafe", "use", "where", "while", "abstract", "become", "box", "do",
+        "final", "macro", "override", "priv", "typeof", "unsized", "virtual",
+        "yield", "try",
+    ];
+    assert_eq!(keywords.len(), 50);
+    let repr = Repr::any(keywords.iter().map(|kw| Repr::literal(kw)));
+    let mut lits = Literals::empty();
+    lits.limit_size = 1000;
+    assert!(lits.union_prefixes(&repr));
+    let searcher = LiteralSearcher::prefixes(lits);
+    assert_eq!(searcher.len(), 50);
+    for kw in keywords {
+        let text = context(&format!("0 {} 1", kw));
+        let (start, end) = searcher.find(&text).unwrap();
+        assert_eq!(start, 2, "{}", kw);
+        // Of the keywords starting there, the one ending first is found.
+        let found: String = text[start..end].iter().collect();
+        assert!(kw.starts_with(&found) && keywords.contains(&&*found),
+                "{} {}", kw, found);
+    }
+    // `sync` isn't one, but `as` before it is found first.
+    assert_eq!(searcher.find(&context("xsyncasync")), Some((5, 7)));
+    assert_eq!(searcher.find(&context("0 1 2")), None);
+}
+
+#[test]
+fn map_alphabet() {
+    let ascii = |c: char| u8::try_from(c).ok();
+    let word = Repr::Zero(Zero::StartText)
+        .mul(Repr::literal("ab").or(Repr::Interval(Interval('0', '9')).plus()))
+        .cap("w");
+    let bytes = word.map_alphabet(&ascii).unwrap();
+    assert_eq!(bytes, Repr::Zero(Zero::StartText)
+        .mul(Repr::One(Seq::from_slice(b"ab"))
+                 .or(Repr::Interval(Interval(b'0', b'9')).plus()))
+        .cap("w"));
+    assert_eq!(bytes.map_alphabet(&|b| Some(char::from(b))), Some(word));
+    assert_eq!(Repr::literal("hé").map_alphabet(&ascii), None);
+    assert_eq!(Repr::Interval(Interval('a', 'é')).map_alphabet(&ascii), None);
+}
+
+#[test]
+fn compile_nocap() {
+    let digit = Repr::Interval(Interval('0', '9'));
+    let date = digit.clone().repeat(4).cap("y")
+        .mul(Repr::one('-')).mul(digit.clone().repeat(2).cap("m"))
+        .mul(Repr::one('-')).mul(digit.repeat(2).cap("d"))
+        .cap("date");
+    let nocap = date.compile_nocap().unwrap();
+    assert!(nocap.len() < date.compile().unwrap().len());
+    assert!(nocap.captures.is_empty());
+    assert!(!nocap.iter().any(|inst| matches!(inst, Inst::Save { .. })));
+    for text in ["2024-01-31", "on 1999-12-01.", "2024-1-31", "", "12-34-5678"] {
+        assert_eq!(date.is_match(text), date.find(text).is_some(), "{}", text);
+        assert_eq!(date.is_match(text), is_match(&date, text), "{}", text);
+    }
+}
+
+#[test]
+fn matches() {
+    let digit = Repr::Interval(Interval('0', '9'));
+    let date = digit.clone().repeat(4).mul(Repr::one('-'))
+        .mul(digit.clone().repeat(2)).mul(Repr::one('-'))
+        .mul(digit.repeat(2));
+    let text = "2012-03-14, 2013-01-01 and 2014-07-05";
+    assert_eq!(date.matches(text).collect::<Vec<_>>(),
+               ["2012-03-14", "2013-01-01", "2014-07-05"]);
+    // Slices fall on char boundaries, and empty matches don't repeat.
+    let e = Repr::one('é').exp();
+    assert_eq!(e.matches("aéé☃").collect::<Vec<_>>(), ["", "éé", ""]);
+    assert_eq!(e.matches("").collect::<Vec<_>>(), [""]);
+}
+
+#[test]
+fn min_len_skip() {
+    let word = Repr::literal("hello");
+    assert_eq!(word.compile().unwrap().min_len, 5);
+    assert_eq!(word.find("say hello"), Some((4, 9)));
+    assert_eq!(word.find("hell"), None);
+    assert_eq!(word.find("hello hell"), Some((0, 5)));
+    assert!(!is_match(&word, "abc hell"));
+    // No step is taken from where what is left is shorter than that: one
+    // at each of the first four positions, where `hello` fails at once.
+    let prog = word.compile().unwrap();
+    let cache = AssertUnwindSafe(RefCell::new(ProgramCacheInner::new(&prog)));
+    assert!(!Bounded::exec(&prog, &cache, &mut [false], &context("abc hell"),
+                           0, 8));
+    assert_eq!(cache.borrow().steps.taken, 4);
+    // Both sides of `And` match the same text, so the longer bound holds.
+    let alpha = Repr::Interval(Interval('a', 'z'));
+    let both = alpha.clone().repeat(3).and(alpha.clone().mul(alpha.exp()));
+    assert_eq!(both.min_len(), 3);
+    assert_eq!(both.find("ab abc"), Some((3, 6)));
+}
+
+#[test]
+fn split() {
+    let comma = Repr::one(',');
+    assert_eq!(comma.split("a,b,,c").collect::<Vec<_>>(), ["a", "b", "", "c"]);
+    assert_eq!(comma.split("").collect::<Vec<_>>(), [""]);
+    assert_eq!(comma.split(",").collect::<Vec<_>>(), ["", ""]);
+    assert_eq!(comma.split_words("foo,bar").collect::<Vec<_>>(),
+               ["foo", "bar"]);
+    // Between quotes, there is no word boundary around the comma.
+    assert_eq!(comma.split_words(r#""a","b""#).collect::<Vec<_>>(),
+               [r#""a","b""#]);
+    assert_eq!(comma.split_words(r#"x,y,"z""#).collect::<Vec<_>>(),
+               ["x", r#"y,"z""#]);
+}
+
+#[test]
+fn try_simplify_to_interval() {
+    let abc = Repr::one('a').or(Repr::one('c')).or(Repr::one('b'));
+    assert_eq!(abc.try_simplify_to_interval(), Some(Interval('a', 'c')));
+    let digits = Repr::Interval(Interval('5', '9'))
+        .or(Repr::one('4'))
+        .or(Repr::Interval(Interval('0', '6')));
+    assert_eq!(digits.try_simplify_to_interval(), Some(Interval('0', '9')));
+    let gapped = Repr::one('a').or(Repr::one('c'));
+    assert_eq!(gapped.try_simplify_to_interval(), None);
+    let word = Repr::one('a').or(Repr::literal("bc"));
+    assert_eq!(word.try_simplify_to_interval(), None);
+    // The chain compiles to a single interval, and matches the same.
+    let prog = abc.compile().unwrap();
+    assert!(!prog.iter().any(|inst| matches!(inst, Inst::Split { .. })));
+    assert_eq!(abc.find("xbz"), Some((1, 2)));
+    assert_eq!(gapped.find("xbz"), None);
+}
+
+#[test]
+fn anchored() {
+    let year = crate::DIGIT.repeat(4);
+    let anchored = year.clone().anchored();
+    assert!(anchored.is_anchored_start() && anchored.is_anchored_end());
+    assert!(year.clone().anchored_start().is_anchored_start());
+    assert!(!year.clone().anchored_start().is_anchored_end());
+    assert!(year.clone().anchored_end().is_anchored_end());
+    assert_eq!(anchored.find("2024"), Some((0, 4)));
+    assert_eq!(anchored.find("x2024"), None);
+    assert_eq!(anchored.find("20245"), None);
+    assert_eq!(year.clone().anchored_start().find("20245"), Some((0, 4)));
+    assert_eq!(anchored.clone().unanchored(), year);
+    assert_eq!(anchored.unanchored().find("x2024"), Some((1, 5)));
+}
+
+#[test]
+fn language_size() {
+    let digit = crate::DIGIT;
+    assert_eq!(digit.clone().repeat(2).language_size(), Some(100));
+    assert!(digit.clone().repeat(2).is_finite());
+    assert!(!Repr::Exp(box digit.clone()).is_finite());
+    assert_eq!(Repr::Exp(box digit.clone()).language_size(), None);
+    let ab = Repr::literal("a").or(Repr::literal("b")).cap("x");
+    assert_eq!(ab.clone().mul(digit.clone()).language_size(), Some(20));
+    // An `And` with a finite side is finite, whatever the other side.
+    let and = ab.clone().and(Repr::dot().exp());
+    assert!(and.is_finite());
+    assert_eq!(and.language_size(), Some(2));
+    // Finite, but too many to count.
+    let long = Repr::dot().repeat(10);
+    assert!(long.is_finite());
+    assert_eq!(long.language_size(), None);
+}
+
+#[test]
+fn enumerate() {
+    let words = |repr: &Repr<char>| repr.enumerate(100).unwrap()
+        .collect::<Vec<_>>();
+    let ab = Repr::one('a').or(Repr::one('b'));
+    assert_eq!(words(&ab.clone().mul(Repr::one('c'))), ["ac", "bc"]);
+    // `&` is the intersection, which has nothing here.
+    assert!(words(&(ab.clone() & 'c')).is_empty());
+    assert_eq!(words(&ab.clone().or(Repr::one('a'))), ["a", "b"]);
+    let opt = Repr::zero().or(Repr::one('x'));
+    assert_eq!(words(&opt.clone().mul(opt)), ["", "x", "xx"]);
+    let and = Repr::Interval(Interval('a', 'e')).and(Repr::dot().exp());
+    assert_eq!(words(&and).concat(), "abcde");
+    assert!(crate::DIGIT.repeat(2).enumerate(99).is_none());
+    assert!(Repr::one('a').exp().enumerate(100).is_none());
+}
+
+#[test]
+fn match_prefix_len() {
+    let year = crate::DIGIT.repeat(4);
+    assert_eq!(year.match_prefix_len("12x4"), 2);
+    assert_eq!(year.match_prefix_len("1234"), 4);
+    assert_eq!(year.match_prefix_len("123"), 3);
+    assert_eq!(year.match_prefix_len("12345"), 4);
+    assert_eq!(year.match_prefix_len("x"), 0);
+    let either = Repr::literal("abc").or(Repr::literal("abd"));
+    assert_eq!(either.match_prefix_len("abe"), 2);
+    assert_eq!(either.match_prefix_len("abdé"), 3);
+}
+
+#[test]
+fn rebalance() {
+    let branches = |first: &str, last: &str| Repr::any(
+        std::iter::once(Repr::literal(first))
+            .chain((0..998).map(|i| Repr::literal(&format!("x{}", i))))
+            .chain(std::iter::once(Repr::literal(last)))
+    );
+    let or = branches("a", "ab");
+    assert_eq!(or.depth(), 1000);
+    let balanced = or.clone().rebalance();
+    assert!(balanced.depth() <= 12, "{}", balanced.depth());
+    assert_eq!(balanced.branch_refs(), or.branch_refs());
+    // The first branch is still preferred.
+    assert_eq!(balanced.find("ab"), Some((0, 1)));
+    assert_eq!(branches("ab", "a").rebalance().find("ab"), Some((0, 2)));
+
+    // Chains under other operators are rebalanced too, but not merged
+    // with chains of another operator.
+    let mul = Repr::prod((0..64).map(|_| Repr::dot())).cap("x");
+    let balanced = mul.clone().rebalance();
+    assert!(balanced.depth() < 10);
+    assert_eq!(balanced.min_len(), 64);
+    let mixed = Repr::one('a').or(Repr::dot().mul(Repr::one('c')));
+    assert_eq!(mixed.clone().rebalance(), mixed);
+}
+
+#[test]
+fn replace_nested_groups() {
+    let nested = Repr::try_from("(?P<outer>a(?P<inner>b)c)").unwrap();
+    assert_eq!(nested.replace_all("xabcx", "[$outer|$inner|$1|$2]"),
+               "x[abc|b|abc|b]x");
+
+    let date = Repr::try_from(r"(?P<ym>(?P<y>\d{4})(?P<m>\d\d))").unwrap();
+    let text = "201203";
+    assert_eq!(date.replace_all(text, "$ym"), "201203");
+    // Unbraced, the name runs as far as it can, so `$ymonth` is a group
+    // that doesn't exist.
+    assert_eq!(date.replace_all(text, "$ymonth"), "");
+    assert_eq!(date.replace_all(text, "${y}month"), "2012month");
+    assert_eq!(date.replace_all(text, "${y}${m}"), "201203");
+    assert_eq!(date.replace_all(text, "${m}/${y}"), "03/2012");
+}
+
+#[test]
+fn without_captures() {
+    let date = Repr::try_from(r"(?P<y>\d{4})-(?P<m>\d\d)-(?P<d>\d\d)").unwrap();
+    assert_eq!(date.compile().unwrap().slots(), 8);
+    let stamp = date.clone().without_captures().cap("date")
+        .mul(Repr::one('T'))
+        .mul(Repr::try_from(r"(?P<h>\d\d):(?P<m>\d\d)").unwrap());
+    // `date`, `h` and `m` are left, `m` being the minutes.
+    let prog = stamp.compile().unwrap();
+    assert_eq!(prog.slots(), 8);
+    let context = context("2012-03-14T09:30");
+    let caps = stamp.captures(&context).unwrap();
+    assert_eq!(caps.name("m").map(|m| (m.start(), m.end())), Some((14, 16)));
+    assert_eq!(caps.name("y"), None);
+    assert_eq!(date.without_captures().find("x2012-03-14"), Some((1, 11)));
+}
+
+#[test]
+fn contains() {
+    let reprs = [
+        Repr::literal("needle"),
+        Repr::literal(""),
+        Repr::literal("ne").mul(Repr::dot().plus()).mul(Repr::one('e')),
+        Repr::Interval(Interval('0', '9')).plus().cap("n"),
+        Repr::Zero(Zero::StartText).mul(Repr::literal("hay")),
+        Repr::literal("a").or(Repr::literal("é"))
+            .mul(Repr::Zero(Zero::EndText)),
+    ];
+    let texts = [
+        "", "needle", "haystack with a needle", "hay", "no 42 here", "café",
+        "nee", "a\n",
+    ];
+    for repr in reprs.iter() {
+        for text in texts {
+            assert_eq!(repr.contains(text), repr.is_match(text),
+                       "{:?} {:?}", repr, text);
+            assert_eq!(repr.contains(text), repr.find(text).is_some());
+        }
+    }
+}
+
+#[test]
+fn set_operations() {
+    let a = Repr::literal("abc").or(Repr::Interval(Interval('0', '9')).plus());
+    let b = Repr::literal("xyz");
+    assert_eq!(a.clone().union(b.clone()).intersection(a.clone()), a);
+    assert_eq!(a.clone().union(b.clone()).union(a.clone()),
+               a.clone().or(b.clone()));
+    assert_eq!(a.clone().union(Repr::void()), a);
+
+    // Runs of single elements are merged, in any order.
+    let lower = Repr::Interval(Interval('a', 'z'));
+    let union = Repr::one('b').union(Repr::Interval(Interval('c', 'z')))
+        .union(Repr::one('a'));
+    assert_eq!(union, lower);
+    // But not across another branch, which would change priorities.
+    let split = Repr::one('a').union(b.clone()).union(Repr::one('b'));
+    assert_eq!(split, Repr::one('a').or(b.clone()).or(Repr::one('b')));
+
+    let upper = Repr::Interval(Interval('A', 'Z'));
+    assert_eq!(lower.clone().intersection(Repr::Interval(Interval('x', '~'))),
+               Repr::Interval(Interval('x', 'z')));
+    assert!(lower.clone().intersection(upper.clone()).is_void());
+    assert_eq!(lower.clone().difference(Repr::Interval(Interval('b', 'y'))),
+               Repr::one('a').or(Repr::one('z')));
+    assert!(Repr::one('q').difference(lower.clone()).is_void());
+    assert_eq!(lower.clone().difference(upper.clone()), lower);
+    assert_eq!(a.clone().difference(b.clone()), a.clone().sub(b));
+}
+
+#[test]
+fn match_errors() {
+    let lower = Repr::Interval(Interval('a', 'z'));
+    // Under a `Mul`, so that it isn't taken for a set of patterns.
+    let add = Repr::one('<')
+        .mul(lower.clone().plus().add(Repr::one('1').plus()));
+    assert_eq!(add.compile().err(), Some(CompileError::UnsupportedAdd));
+    assert_eq!(add.try_is_match("<ab1"),
+               Err(MatchError::UnsupportedOperator("Add")));
+    assert_eq!(add.try_find("<ab1"),
+               Err(MatchError::UnsupportedOperator("Add")));
+    let div = lower.clone().plus().div(Repr::literal("ab"));
+    let error = MatchError::UnsupportedOperator("Div");
+    assert_eq!(div.try_find("ab"), Err(error.clone()));
+    assert_eq!(div.try_is_match("ab"), Err(error.clone()));
+    assert_eq!(div.try_count_matches("ab"), Err(error));
+    assert!(div.try_matches("ab").is_err());
+    assert!(div.try_split("ab").is_err());
+    assert!(Partition::try_new(&context("ab"), div).is_err());
+    // A `Div` at the end isn't reversed, but run forward.
+    let div = Repr::literal("ab").div(Repr::literal("abc")).anchored_end();
+    assert_eq!(div.try_find("xc"), Ok(Some((1, 2))));
+    assert_eq!(div.try_count_matches("xc"), Ok(1));
+
+    let word = lower.plus().cap("word");
+    assert_eq!(word.try_is_match("¿qué?"), Ok(true));
+    assert_eq!(word.try_find("¿qué?"), Ok(Some((2, 6))));
+    assert_eq!(word.try_find_in(&context("12 ab"), 0, true), Ok(None));
+    assert_eq!(MatchError::from(CompileError::TooLarge(10)),
+               MatchError::SizeLimitExceeded(10));
+
+    let not = Repr::literal("ab").not();
+    assert_eq!(not.clone().rev(), Repr::literal("ba").not());
+    assert_eq!(not.clone().rev().rev(), not);
+}
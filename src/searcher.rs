@@ -0,0 +1,301 @@
+//! Push-based search, for input that arrives one character at a time.
+//!
+//! The searcher runs the compiled program like the PikeVM does, over the
+//! threads of all the possible matches at once in priority order, but the
+//! caller drives it, so that it never needs the whole input. An assertion
+//! at a position depends on the character after it, so a position is only
+//! stepped through once that character, or the end of the input, is known.
+//! The characters from the start of the current search on are kept, as once
+//! a match is found, the search for the next one starts over from its end.
+
+use alloc::{
+    collections::{BTreeSet, VecDeque},
+    vec::Vec,
+};
+use core::{mem, slice};
+
+use crate::compile::Compiler;
+use crate::error::CompileError;
+use crate::partition::Cursor;
+use crate::program::{Index, Inst, Program};
+use crate::repr::Repr;
+use crate::unicode::is_empty_match_between;
+
+/// Finds the non-overlapping leftmost-first matches of a repr in input fed
+/// to it one character at a time, at the same character positions as
+/// `Exec::find_iter` over the whole input.
+///
+/// A match is returned once no more input could change it, so at the
+/// earliest with the character after it, which an assertion at its end may
+/// look at: a greedy `\d+` only ends on a character that isn't a digit, or
+/// at the end of the input, which `finish` tells. A character may complete
+/// more than one match, in which case the later ones are returned by the
+/// next calls to `feed` or `finish`, in order.
+#[derive(Clone)]
+pub struct Searcher {
+    prog: Program<char>,
+    cursor: Cursor,
+    /// The characters fed from `origin` on, which starts right before the
+    /// current search, for the assertions there.
+    chars: Vec<char>,
+    origin: usize,
+    /// The position the threads are at.
+    at: usize,
+    /// The threads entering `at`, highest priority first, each with where
+    /// its match starts.
+    threads: Vec<(Thread, usize)>,
+    /// The match of the current search with the highest priority so far.
+    best: Option<(usize, usize)>,
+    /// Which instructions a thread entered at `at`, kept between the steps
+    /// so as not to allocate it for every character.
+    seen: Vec<bool>,
+    /// The matches found but not returned yet.
+    found: VecDeque<(usize, usize)>,
+}
+
+/// A thread entering an instruction, or partway through the literal run of
+/// a `One` instruction, with how many of its elements have been matched.
+#[derive(Clone, Copy)]
+enum Thread {
+    Enter(Index),
+    Run(Index, usize),
+}
+
+impl Searcher {
+    /// Compiles `repr` for searching, without its groups, as only the
+    /// spans of the matches are reported.
+    pub fn new(repr: &Repr<char>) -> Result<Self, CompileError> {
+        let prog = Compiler::new().captures(false)
+            .compile(slice::from_ref(repr))?;
+        let seen = vec![false; prog.len()];
+        Ok(Searcher {
+            prog,
+            cursor: Cursor::new(),
+            chars: Vec::new(),
+            origin: 0,
+            at: 0,
+            threads: Vec::new(),
+            best: None,
+            seen,
+            found: VecDeque::new(),
+        })
+    }
+
+    /// Feeds the next character of the input, and returns the start and
+    /// end positions of the next match, counted in characters fed, if
+    /// there is one that no more input could change.
+    pub fn feed(&mut self, c: char) -> Option<(usize, usize)> {
+        self.chars.push(c);
+        self.run(false);
+        self.found.pop_front()
+    }
+
+    /// Tells that the input ended, and returns the next match not returned
+    /// yet. Call it until it returns `None` to get all of them. Nothing
+    /// should be fed after it.
+    pub fn finish(&mut self) -> Option<(usize, usize)> {
+        self.run(true);
+        self.found.pop_front()
+    }
+
+    /// Steps through the positions whose next character is known, the end
+    /// of the input being known if `end`.
+    fn run(&mut self, end: bool) {
+        while self.at <= self.origin + self.chars.len() {
+            let next = self.chars.get(self.at - self.origin).copied();
+            if next.is_none() && !end {
+                return;
+            }
+            self.step(next);
+            if next.is_none() || self.threads.is_empty() {
+                match self.best.take() {
+                    Some(m) => self.restart(m),
+                    // There is no match from where the search started.
+                    None if next.is_none() => self.at += 1,
+                    // No thread is alive, so no match starts before `at`.
+                    None => self.drop_before(self.at),
+                }
+            }
+        }
+    }
+
+    /// Follows the threads entering `self.at` to the instructions reading
+    /// an element, and moves those reading `next` to the next position.
+    fn step(&mut self, next: Option<char>) {
+        let at = self.at;
+        let prev = at.checked_sub(1)
+            .filter(|&prev| prev >= self.origin)
+            .map(|prev| self.chars[prev - self.origin]);
+        let mut entering = mem::take(&mut self.threads);
+        if self.best.is_none() && (!self.prog.is_anchored_start || at == 0) {
+            entering.push((Thread::Enter(self.prog.start), at));
+        }
+        self.seen.fill(false);
+        let mut runs = BTreeSet::new();
+        let mut reading = Vec::new();
+        'threads: for (thread, start) in entering {
+            let mut stack = match thread {
+                Thread::Run(ip, k) => {
+                    if runs.insert((ip, k)) {
+                        reading.push((ip, k, start));
+                    }
+                    continue;
+                }
+                Thread::Enter(ip) => vec![ip],
+            };
+            while let Some(ip) = stack.pop() {
+                if self.seen[ip] {
+                    continue;
+                }
+                self.seen[ip] = true;
+                match self.prog[ip] {
+                    // The threads after this one have a lower priority.
+                    Inst::Match(_) => {
+                        self.best = Some((start, at));
+                        break 'threads;
+                    }
+                    Inst::Split { goto1, goto2 } => {
+                        stack.push(goto2);
+                        stack.push(goto1);
+                    }
+                    Inst::Save { goto, .. } => stack.push(goto),
+                    Inst::Zero { goto, ref zero } => {
+                        if is_empty_match_between(prev, next, zero,
+                                                  self.prog.line_terminator,
+                                                  self.prog.crlf)
+                        {
+                            stack.push(goto);
+                        }
+                    }
+                    _ => reading.push((ip, 0, start)),
+                }
+            }
+        }
+        let Some(c) = next else { return };
+        for (ip, k, start) in reading {
+            match self.prog[ip] {
+                Inst::One { goto, ref seq } if seq.get(k) == Some(c) => {
+                    let thread = if k + 1 == seq.len() {
+                        Thread::Enter(goto)
+                    } else {
+                        Thread::Run(ip, k + 1)
+                    };
+                    self.threads.push((thread, start));
+                }
+                Inst::Interval { goto, interval } if interval.has(c) => {
+                    self.threads.push((Thread::Enter(goto), start));
+                }
+                _ => {}
+            }
+        }
+        self.at += 1;
+    }
+
+    /// Reports the match from `start` to `end` unless `Cursor` skips it,
+    /// and starts the search for the next one where the cursor says.
+    fn restart(&mut self, (start, end): (usize, usize)) {
+        if self.cursor.advance(start, end) {
+            self.found.push_back((start, end));
+        }
+        self.threads.clear();
+        self.at = self.cursor.last_end;
+        self.drop_before(self.at);
+    }
+
+    /// Drops the characters before `at` but the one right before, which
+    /// the assertions at `at` look at.
+    fn drop_before(&mut self, at: usize) {
+        let origin = at.saturating_sub(1);
+        if origin > self.origin {
+            self.chars.drain(..origin - self.origin);
+            self.origin = origin;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Context;
+    use crate::interval::Interval;
+    use crate::partition::Partition;
+    use crate::repr::Zero;
+
+    fn feed_all(repr: &Repr<char>, text: &str) -> Vec<(usize, usize)> {
+        let mut searcher = Searcher::new(repr).unwrap();
+        let mut found: Vec<_>
+            = text.chars().filter_map(|c| searcher.feed(c)).collect();
+        while let Some(m) = searcher.finish() {
+            found.push(m);
+        }
+        found
+    }
+
+    fn find_iter(repr: &Repr<char>, text: &str) -> Vec<(usize, usize)> {
+        Partition::new(&Context::from(text), repr.clone()).collect()
+    }
+
+    #[test]
+    fn date() {
+        let date = Repr::try_from(r"\d{4}-\d{2}-\d{2}").unwrap();
+        let text = "2012-03-14, 2013-01-01 and 2014-07-05";
+        let mut searcher = Searcher::new(&date).unwrap();
+        let mut found = Vec::new();
+        for (i, c) in text.chars().enumerate() {
+            if let Some(m) = searcher.feed(c) {
+                // Returned with the character after it.
+                assert_eq!(m.1, i);
+                found.push(m);
+            }
+        }
+        assert_eq!(found, [(0, 10), (12, 22)]);
+        found.push(searcher.finish().unwrap());
+        assert_eq!(searcher.finish(), None);
+        assert_eq!(found, [(0, 10), (12, 22), (27, 37)]);
+        assert_eq!(found, find_iter(&date, text));
+    }
+
+    #[test]
+    fn agrees_with_find_iter() {
+        let digit = Repr::Interval(Interval('0', '9'));
+        let word = Repr::Zero(Zero::WordBoundary);
+        let reprs = [
+            digit.clone().plus(),
+            digit.clone().exp(),
+            Repr::literal("ab").or(Repr::literal("abcd")),
+            Repr::literal("abcd").or(Repr::literal("ab")),
+            word.clone().mul(Repr::Interval(Interval('a', 'z')).plus())
+                .mul(word),
+            Repr::Zero(Zero::StartLine).mul(digit.clone())
+                .mul(Repr::dot().exp_lazy()).mul(Repr::Zero(Zero::EndLine)),
+            Repr::Zero(Zero::StartText).mul(Repr::literal("ab")),
+            Repr::literal("b").mul(Repr::Zero(Zero::EndText)),
+            Repr::dot().mul(Repr::literal("é")).cap("x"),
+        ];
+        let texts = [
+            "", "abcd ab abc", "12 x 345\n6 é\nabcdab", "ababab", "aébé b",
+            "1\n22\n", "zabcd9",
+        ];
+        for repr in reprs.iter() {
+            for text in texts {
+                assert_eq!(feed_all(repr, text), find_iter(repr, text),
+                           "{:?} {:?}", repr, text);
+            }
+        }
+    }
+
+    #[test]
+    fn drops_dead_input() {
+        let repr = Repr::literal("ab");
+        let mut searcher = Searcher::new(&repr).unwrap();
+        for c in "x".repeat(1000).chars() {
+            assert_eq!(searcher.feed(c), None);
+            assert!(searcher.chars.len() <= 2);
+        }
+        for c in "aab".chars() {
+            assert_eq!(searcher.feed(c), None);
+        }
+        assert_eq!(searcher.finish(), Some((1001, 1003)));
+        assert_eq!(searcher.finish(), None);
+    }
+}
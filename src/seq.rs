@@ -1,5 +1,6 @@
 use alloc::vec::Vec;
 use core::ops::Deref;
+use core::slice;
 
 use unconst::unconst;
 
@@ -7,7 +8,7 @@ use crate::repr::Integral;
 
 #[unconst]
 #[derive_const(Clone, Debug, PartialEq, PartialOrd, Ord)]
-#[derive(Eq)]
+#[derive(Eq, Hash)]
 pub struct Seq<I: ~const Integral>(Vec<I>);
 
 #[unconst]
@@ -24,14 +25,73 @@ impl<I: ~const Integral> Seq<I> {
         Seq(vec![i])
     }
 
+    /// A copy of `is` as a sequence, matched as a single literal run once
+    /// compiled.
+    pub const fn from_slice(is: &[I]) -> Self {
+        Seq(is.to_vec())
+    }
+
+    /// The number of elements.
+    pub const fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The element at `index`, or `None` if it's out of bounds.
+    pub const fn get(&self, index: usize) -> Option<I> {
+        if index < self.0.len() {
+            Some(self.0[index])
+        } else {
+            None
+        }
+    }
+
+    /// Iterates over the elements in order.
+    pub fn iter(&self) -> slice::Iter<'_, I> {
+        self.0.iter()
+    }
+
+    /// Appends `i` at the end.
+    pub const fn push(&mut self, i: I) {
+        self.0.push(i);
+    }
+
     pub const fn mul(self, other: Self) -> Self {
         self.0.extend(other);
         self
     }
 
+    /// Reverses the order of the elements, each of which is kept whole: for
+    /// `char` that's a scalar value, so no surrogate pair or UTF-8 sequence
+    /// is ever split.
     pub const fn rev(self) -> Self {
         Seq(self.0.into_iter().rev().collect())
     }
+
+    /// Returns every interleaving of `self` and `other`, that is, every
+    /// sequence of the elements of both that keeps the order within each,
+    /// without duplicates.
+    pub const fn shuffle(&self, other: &Self) -> Vec<Self> {
+        let (i, j) = match (self.first(), other.first()) {
+            (None, _) => return vec![other.clone()],
+            (_, None) => return vec![self.clone()],
+            (Some(i), Some(j)) => (*i, *j),
+        };
+        let mut seqs = Vec::new();
+        let lhs = Seq(self.0[1..].to_vec()).shuffle(other);
+        let rhs = self.shuffle(&Seq(other.0[1..].to_vec()));
+        for seq in lhs.into_iter().map(|seq| Seq::one(i).mul(seq))
+            .chain(rhs.into_iter().map(|seq| Seq::one(j).mul(seq)))
+        {
+            if !seqs.contains(&seq) {
+                seqs.push(seq);
+            }
+        }
+        seqs
+    }
 }
 
 #[unconst]
@@ -58,3 +118,30 @@ impl<I: ~const Integral> const IntoIterator for Seq<I> {
         self.0.into_iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rev() {
+        let seq = Seq::from_slice(&['a', 'b', 'c']);
+        assert_eq!(seq.clone().rev(), Seq::from_slice(&['c', 'b', 'a']));
+        assert_eq!(seq.clone().rev().rev(), seq);
+        assert_eq!(Seq::one('a').rev(), Seq::one('a'));
+        assert_eq!(Seq::<char>::empty().rev(), Seq::empty());
+        let astral = Seq::from_slice(&['a', '\u{1F600}', 'é']);
+        assert_eq!(astral.rev(), Seq::from_slice(&['é', '\u{1F600}', 'a']));
+    }
+
+    #[test]
+    fn accessors() {
+        let seq = Seq::one('a').mul(Seq::one('b'));
+        assert_eq!(seq.len(), 2);
+        assert!(!seq.is_empty());
+        assert!(Seq::<char>::empty().is_empty());
+        assert_eq!(seq.get(1), Some('b'));
+        assert_eq!(seq.get(2), None);
+        assert_eq!(seq.iter().copied().collect::<Vec<_>>(), ['a', 'b']);
+    }
+}
@@ -1,11 +1,27 @@
+use alloc::{collections::BTreeSet, string::String, vec::Vec};
 use core::char::from_u32;
+#[cfg(feature = "std")]
+use core::iter;
+#[cfg(feature = "std")]
+use core::fmt::Write;
 
+use regex_syntax::hir::{Class, HirKind};
 use unconst::unconst;
 
 use crate::context::Context;
+#[cfg(feature = "std")]
+use crate::error::{MatchError, ReplaceError};
 use crate::interval::Interval;
 use crate::derivative::LiteralSearcher;
+#[cfg(feature = "std")]
+use crate::exec::Exec;
+use crate::parse::{merge, negate, union};
+#[cfg(feature = "std")]
+use crate::partition::Cursor;
+#[cfg(feature = "std")]
+use crate::replace;
 use crate::repr::{Repr, Integral, Zero};
+use crate::seq::Seq;
 
 #[unconst]
 impl const Integral for char {
@@ -31,8 +47,64 @@ impl Repr<char> {
     /// expression that matches any character, including `\n`, use the `any`
     /// method.
     pub const fn dot() -> Self {
-        Self::Or(box Self::Interval(Interval('\0', '\x09')),
-                 box Self::Interval(Interval('\x0B', '\u{10FFFF}')))
+        Self::dot_except('\n')
+    }
+
+    /// `.` for input whose lines end with `\r\n`, `\r` or `\n`: any
+    /// character but `\r` and `\n`.
+    pub const fn dot_crlf() -> Self {
+        Self::Interval(Interval('\0', '\x09'))
+            .or(Self::Interval(Interval('\x0B', '\x0C')))
+            .or(Self::Interval(Interval('\x0E', '\u{10FFFF}')))
+    }
+
+    /// `.` for input whose lines end with `line_terminator`: any character
+    /// but it. `dot_except('\n')` is `dot()`.
+    pub const fn dot_except(line_terminator: char) -> Self {
+        let lower = (line_terminator > '\0')
+            .then(|| Self::Interval(Interval('\0', line_terminator.pred())));
+        let upper = (line_terminator < '\u{10FFFF}')
+            .then(|| Self::Interval(Interval(line_terminator.succ(),
+                                             '\u{10FFFF}')));
+        match (lower, upper) {
+            (Some(lower), Some(upper)) => lower.or(upper),
+            (Some(repr), None) | (None, Some(repr)) => repr,
+            (None, None) => unreachable!(),
+        }
+    }
+
+    /// `.` over extended grapheme clusters as defined by UAX #29: matches
+    /// what a reader takes for one character, such as `e\u{301}`, a Hangul
+    /// syllable spelt in jamo or an emoji ZWJ sequence, where `dot` would
+    /// match its first `char` only. Like `dot`, it doesn't match a lone
+    /// `\n`, but `\r\n` is a single cluster.
+    pub fn dot_grapheme() -> Self {
+        let control = [property("gcb=Control"), property("gcb=CR"),
+                       property("gcb=LF")].concat();
+        let extend = union(property("gcb=Extend"));
+        let pictographic = union(property("Extended_Pictographic"));
+        let ri = union(property("gcb=RI"));
+        let [l, v, t, lv, lvt] = ["L", "V", "T", "LV", "LVT"]
+            .map(|name| union(property(&format!("gcb={}", name))));
+        let hangul = l.clone().exp()
+            .mul(v.clone().plus().or(lv.mul(v.exp())).or(lvt))
+            .mul(t.clone().exp())
+            .or(l.plus())
+            .or(t.plus());
+        let emoji = pictographic.clone()
+            .mul(extend.clone().exp().mul(Self::one('\u{200D}')).mul(pictographic)
+                     .exp());
+        let core = hangul
+            .or(ri.clone().mul(ri))
+            .or(emoji)
+            .or(union(negate(control)));
+        let postcore = extend
+            .or(union(property("gcb=ZWJ")))
+            .or(union(property("gcb=SpacingMark")));
+        Self::literal("\r\n")
+            .or(union([property("gcb=Control"), property("gcb=CR")].concat()))
+            .or(union(property("gcb=Prepend")).exp().mul(core)
+                    .mul(postcore.exp()))
     }
 
     // /// `(?s).` expression that matches any character, including `\n`. To build an
@@ -41,49 +113,491 @@ impl Repr<char> {
     // pub const fn any() -> Self {
     //     Self::Interval(Interval('\0', '\u{10FFFF}'))
     // }
-}
 
-/// An abstraction over input used in the matching engines.
-impl Context<char> {
-    /// Return true if the given empty width instruction matches at the
-    /// input position given.
-    pub fn is_empty_match(&self, at: usize, look: &Zero) -> bool {
-        match look {
-            Zero::StartLine => {
-                let c = &self[at - 1];
-                at == 0 || c == '\n'
+    /// Literal `s`, a single `One` holding all of its characters, or
+    /// `Repr::zero()` if `s` is empty.
+    pub const fn literal(s: &str) -> Self {
+        if s.is_empty() {
+            Self::zero()
+        } else {
+            Self::One(Seq::from(s))
+        }
+    }
+
+    /// The class of `chars`, in any order and with repeats: the alternation
+    /// of as few intervals as cover them, characters next to each other
+    /// being merged, and `One` for a lone character. Without any characters,
+    /// it is `Repr::void()`.
+    pub fn from_char_set(chars: &[char]) -> Self {
+        let intervals = merge(chars.iter().map(|&c| Interval(c, c)).collect());
+        if intervals.is_empty() {
+            Self::void()
+        } else {
+            union(intervals)
+        }
+    }
+
+    /// Returns the word this repr matches if it is a literal, see
+    /// `is_literal`, so that it can be searched for with `str::find`
+    /// without compiling anything.
+    pub fn as_literal(&self) -> Option<String> {
+        match self {
+            Self::One(seq) => Some(seq.iter().collect()),
+            Self::Mul(lhs, rhs) => {
+                let mut s = lhs.as_literal()?;
+                s.push_str(&rhs.as_literal()?);
+                Some(s)
+            }
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns true if there is a match anywhere in `text`.
+    ///
+    /// # Panics
+    ///
+    /// If the repr can't be compiled, see `try_is_match`.
+    pub const fn is_match(&self, text: &str) -> bool {
+        self.is_match_at(&Context::from(text), 0)
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns true if there is a match anywhere in `text`, as `is_match`
+    /// does. A repr that `as_literal` is searched for with `str::contains`
+    /// without compiling it. Otherwise, like `is_match`, the search stops at
+    /// the first position where a match ends, computing neither its start
+    /// nor its groups.
+    pub fn contains(&self, text: &str) -> bool {
+        match self.as_literal() {
+            Some(literal) => text.contains(literal.as_str()),
+            None => self.is_match(text),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns true if the whole of `text` is matched.
+    pub const fn is_full_match(&self, text: &str) -> bool {
+        Self::Zero(Zero::StartText)
+            .mul(self.clone())
+            .mul(Self::Zero(Zero::EndText))
+            .is_match(text)
+    }
+
+    /// Returns true if the whole of `text` is matched, deciding it by taking
+    /// the derivative by each character in turn instead of compiling. Unlike
+    /// the compiled engines, this supports `And`, `Not` and any `Div`, but
+    /// zero-width assertions are taken as the empty word.
+    pub fn is_match_derivative(&self, text: &str) -> bool {
+        self.after_prefix(text).nullable()
+    }
+
+    /// Returns the words this repr matches if it `is_finite` and has at most
+    /// `cap` of them by `language_size`, or `None` otherwise. Words come in
+    /// a deterministic order, each once: those of the first branch of an
+    /// `Or` before those of the second, and for a `Mul`, by the word of its
+    /// left side, then of its right side. Assertions are taken to hold, as
+    /// for `language_size`, and an `And` keeps the words of one side the
+    /// other side matches by `is_match_derivative`.
+    pub fn enumerate(&self, cap: usize)
+        -> Option<impl Iterator<Item = String>>
+    {
+        if self.language_size()? > cap as u128 {
+            return None;
+        }
+        Some(self.words().into_iter())
+    }
+
+    /// See `enumerate`, which makes sure the words are few enough.
+    fn words(&self) -> Vec<String> {
+        match self {
+            Self::Zero(_) => vec![String::new()],
+            Self::One(seq) => vec![seq.iter().collect()],
+            Self::Interval(_) if self.is_void() => vec![],
+            Self::Interval(interval)
+                => interval.iter().map(String::from).collect(),
+            // Without checking, a void side would leave the other one to
+            // enumerate, however large.
+            Self::Mul(lhs, rhs) if lhs.language_size() == Some(0)
+                || rhs.language_size() == Some(0) => vec![],
+            Self::Mul(lhs, rhs) => {
+                let rhs = rhs.words();
+                dedup(lhs.words().iter().flat_map(|lhs| {
+                    rhs.iter().map(move |rhs| format!("{}{}", lhs, rhs))
+                }))
             }
-            Zero::EndLine => {
-                let c = &self[at + 1];
-                at == self.len() || c == '\n'
+            Self::Or(lhs, rhs)
+                => dedup(lhs.words().into_iter().chain(rhs.words())),
+            Self::And(lhs, rhs) => {
+                // The side with the fewest words is enumerated.
+                let (words, other) = match (lhs.language_size(),
+                                            rhs.language_size()) {
+                    (Some(l), Some(r)) if r < l => (rhs, lhs),
+                    (Some(_), _) => (lhs, rhs),
+                    _ => (rhs, lhs),
+                };
+                words.words().into_iter()
+                    .filter(|word| other.is_match_derivative(word))
+                    .collect()
             }
-            Zero::StartText => at == 0,
-            Zero::EndText => at == self.len(),
-            Zero::WordBoundary => {
-                let (c1, c2) = (&self[at - 1], &self[at + 1]);
-                is_word_char(c1) != is_word_char(c2)
+            Self::Cap(repr, _) => repr.words(),
+            Self::Div(_, _) | Self::Exp(_) | Self::Lazy(_) | Self::Not(_)
+            | Self::Add(_, _) => unreachable!("not finite"),
+        }
+    }
+
+    /// Returns how many characters of `text` can be read while they still
+    /// start a word this repr matches, so that a validator can point at the
+    /// first one that doesn't. It is the number of characters of `text` if
+    /// it is matched whole, or could be completed into a match. Like
+    /// `after_prefix`, it takes derivatives, and stops once `simplify`
+    /// makes one void: with `And` or `Not`, a dead end it doesn't see is
+    /// counted as read.
+    pub fn match_prefix_len(&self, text: &str) -> usize {
+        let mut repr = self.clone().simplify();
+        let mut len = 0;
+        for c in text.chars() {
+            repr = repr.derivative(c).simplify();
+            if repr.is_void() {
+                break;
+            }
+            len += 1;
+        }
+        len
+    }
+
+    /// Returns what remains to match once `prefix` is consumed: the words
+    /// `w` such that `self` matches `prefix` followed by `w`, which is the
+    /// derivative by each character of `prefix` in turn. An editor can feed
+    /// it characters as they are typed, and `nullable` tells whether what
+    /// was typed so far matches already.
+    pub fn after_prefix(&self, prefix: &str) -> Self {
+        let mut repr = self.clone().simplify();
+        for c in prefix.chars() {
+            if repr.is_void() {
+                break;
+            }
+            repr = repr.derivative(c).simplify();
+        }
+        repr
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns the start and end byte offsets of the leftmost-first match in
+    /// `text`.
+    ///
+    /// # Panics
+    ///
+    /// If the repr can't be compiled, see `try_find`.
+    pub const fn find(&self, text: &str) -> Option<(usize, usize)> {
+        self.find_at(text, 0, false)
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `is_match`, but returns a `MatchError` rather than panicking if
+    /// the repr can't be run, see `try_is_match_at`.
+    pub fn try_is_match(&self, text: &str) -> Result<bool, MatchError> {
+        self.try_is_match_at(&Context::from(text), 0)
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `find`, but returns a `MatchError` rather than panicking if the
+    /// repr can't be run, see `try_is_match_at`.
+    pub fn try_find(&self, text: &str)
+        -> Result<Option<(usize, usize)>, MatchError>
+    {
+        let context = Context::from(text);
+        let span = self.try_find_in(&context, 0, false)?;
+        Ok(span.map(|(start, end)| {
+            (context.byte_offset(start), context.byte_offset(end))
+        }))
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `find`, but starts at byte offset `start` of `text`. If
+    /// `anchored` is true, the match must begin exactly at `start`, which is
+    /// what a tokenizer advancing a cursor wants; otherwise the search goes
+    /// forward from `start`.
+    ///
+    /// Each call decodes `text` and compiles the repr again, unless it is a
+    /// literal. A tokenizer calling it at every token should instead build
+    /// the `Context` and the `Exec` once, and advance its cursor with
+    /// `Exec::find_anchored_at`, which finds the same matches in positions
+    /// rather than byte offsets.
+    ///
+    /// # Panics
+    ///
+    /// If `start` is not on a char boundary of `text`, or if the repr isn't
+    /// a literal and can't be compiled, see `try_find_in`.
+    pub const fn find_at(&self, text: &str, start: usize, anchored: bool)
+        -> Option<(usize, usize)>
+    {
+        if let Some(literal) = self.as_literal() {
+            let at = if anchored {
+                text[start..].starts_with(&literal).then_some(0)?
+            } else {
+                text[start..].find(&literal)?
+            };
+            return Some((start + at, start + at + literal.len()));
+        }
+        let context = Context::from(text);
+        self.find_in(&context, context.position(start), anchored)
+            .map(|(start, end)| {
+                (context.byte_offset(start), context.byte_offset(end))
+            })
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns the number of non-overlapping matches in `text`, the same
+    /// matches `Partition` goes through. The repr is compiled once, and no
+    /// span is kept beyond the search that found it.
+    ///
+    /// # Panics
+    ///
+    /// If the repr can't be run, see `try_count_matches`.
+    pub fn count_matches(&self, text: &str) -> usize {
+        self.try_count_matches(text).unwrap()
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `count_matches`, but returns a `MatchError` rather than
+    /// panicking if the repr can't be run, see `try_is_match_at`.
+    pub fn try_count_matches(&self, text: &str) -> Result<usize, MatchError> {
+        let context = Context::from(text);
+        let exec = Exec::new(self.clone())?;
+        let mut cursor = Cursor::new();
+        let mut count = 0;
+        while cursor.last_end <= context.len() {
+            let m = match exec.try_find_at(&context, cursor.last_end)? {
+                None => break,
+                Some(m) => m,
+            };
+            if cursor.advance(m.start(), m.end()) {
+                count += 1;
             }
-            Zero::NotWordBoundary => {
-                let (c1, c2) = (&self[at - 1], &self[at + 1]);
-                is_word_char(c1) == is_word_char(c2)
+        }
+        Ok(count)
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns an iterator over the text of the non-overlapping matches in
+    /// `text`, the same matches `Partition` goes through. An empty match
+    /// right after the previous match is skipped, so that the iterator
+    /// always moves on.
+    ///
+    /// # Panics
+    ///
+    /// If the repr can't be compiled, see `try_matches`.
+    pub fn matches<'t>(&self, text: &'t str)
+        -> impl Iterator<Item = &'t str>
+    {
+        self.try_matches(text).unwrap()
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `matches`, but returns a `MatchError` rather than panicking if
+    /// the repr can't be compiled, see `From<CompileError> for MatchError`.
+    pub fn try_matches<'t>(&self, text: &'t str)
+        -> Result<impl Iterator<Item = &'t str>, MatchError>
+    {
+        let context = Context::from(text);
+        let exec = Exec::new(self.clone())?;
+        let mut cursor = Cursor::new();
+        Ok(iter::from_fn(move || {
+            while cursor.last_end <= context.len() {
+                let m = exec.find_at(&context, cursor.last_end)?;
+                if cursor.advance(m.start(), m.end()) {
+                    return Some(&text[context.byte_offset(m.start())
+                                      ..context.byte_offset(m.end())]);
+                }
             }
-            Zero::WordBoundaryAscii => {
-                let (c1, c2) = (&self[at - 1], &self[at + 1]);
-                is_word_byte(c1) != is_word_byte(c2)
+            None
+        }))
+    }
+
+    #[cfg(feature = "std")]
+    /// Returns an iterator over the pieces of `text` between the matches
+    /// `matches` goes through, the text before the first and after the last
+    /// included, so that there is always one more piece than matches.
+    ///
+    /// # Panics
+    ///
+    /// If the repr can't be compiled, see `try_split`.
+    pub fn split<'t>(&self, text: &'t str) -> impl Iterator<Item = &'t str> {
+        self.try_split(text).unwrap()
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `split`, but returns a `MatchError` rather than panicking if the
+    /// repr can't be compiled, see `From<CompileError> for MatchError`.
+    pub fn try_split<'t>(&self, text: &'t str)
+        -> Result<impl Iterator<Item = &'t str>, MatchError>
+    {
+        let context = Context::from(text);
+        let exec = Exec::new(self.clone())?;
+        let mut cursor = Cursor::new();
+        // Where the next piece starts, in bytes, until the last is returned.
+        let mut next = Some(0);
+        Ok(iter::from_fn(move || {
+            let start = next?;
+            while cursor.last_end <= context.len() {
+                let Some(m) = exec.find_at(&context, cursor.last_end)
+                    else { break };
+                if cursor.advance(m.start(), m.end()) {
+                    next = Some(context.byte_offset(m.end()));
+                    return Some(&text[start..context.byte_offset(m.start())]);
+                }
             }
-            Zero::NotWordBoundaryAscii => {
-                let (c1, c2) = (&self[at - 1], &self[at + 1]);
-                is_word_byte(c1) == is_word_byte(c2)
+            next = None;
+            Some(&text[start..])
+        }))
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `split`, but only splits on matches with a word boundary, see
+    /// `Zero::WordBoundary`, on both sides, so that a delimiter counts only
+    /// between tokens.
+    pub fn split_words<'t>(&self, text: &'t str)
+        -> impl Iterator<Item = &'t str>
+    {
+        Self::Zero(Zero::WordBoundary)
+            .mul(self.clone())
+            .mul(Self::Zero(Zero::WordBoundary))
+            .split(text)
+    }
+
+    #[cfg(feature = "std")]
+    /// Replaces every non-overlapping match in `text` with `replacement`.
+    /// See `replace_all_write` for how `$` is expanded in `replacement`.
+    ///
+    /// # Panics
+    ///
+    /// If the repr can't be compiled, see `replace_all_write`.
+    pub fn replace_all(&self, text: &str, replacement: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        // Writing into a `String` never fails, so only compiling can.
+        match self.replace_all_write(text, replacement, &mut out) {
+            Ok(()) => out,
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    /// Like `replace_all`, but writes the result into `out` as matches are
+    /// found instead of buffering it.
+    ///
+    /// In `replacement`, `$$` is a literal `$`, and `$name` or `${name}` is
+    /// the text of group `name` of the match, `$0` being the whole match.
+    /// Unknown groups expand to nothing.
+    ///
+    /// The repr is compiled once, and each match is found along with its
+    /// groups by a single `Exec::captures_at`. If the repr can't be
+    /// compiled, returns why, see `From<CompileError> for MatchError`,
+    /// before writing anything.
+    pub fn replace_all_write<W: Write>(
+        &self,
+        text: &str,
+        replacement: &str,
+        out: &mut W,
+    ) -> Result<(), ReplaceError> {
+        let context = Context::from(text);
+        let exec = Exec::new(self.clone()).map_err(MatchError::from)?;
+        let mut cursor = Cursor::new();
+        let mut last = 0;
+        while cursor.last_end <= context.len() {
+            let Some(captures) = exec.captures_at(&context, cursor.last_end)
+                else { break };
+            let Some(m) = captures.get(0) else { break };
+            if !cursor.advance(m.start(), m.end()) {
+                continue;
             }
-            Zero::Any => unimplemented!()
+            let start = context.byte_offset(m.start());
+            out.write_str(&text[last..start])?;
+            let group = |name: &str| {
+                let m = match name.parse() {
+                    Ok(i) => captures.get(i),
+                    Err(_) => captures.name(name),
+                }?;
+                Some(&text[context.byte_offset(m.start())
+                           ..context.byte_offset(m.end())])
+            };
+            replace::expand(replacement, group, out)?;
+            last = context.byte_offset(m.end());
+        }
+        out.write_str(&text[last..])?;
+        Ok(())
+    }
+}
+
+/// An abstraction over input used in the matching engines.
+impl Context<char> {
+    /// Wraps already decoded characters without going through UTF-8.
+    /// `byte_offset` then reports character positions.
+    pub fn from_chars(chars: &[char]) -> Self {
+        Self::from_slice(chars)
+    }
+
+    /// Appends the characters of `s`, which continues the input this
+    /// context was decoded from, so that a growing input such as a log
+    /// being tailed isn't decoded again. Positions and byte offsets of what
+    /// was there already don't change. If the context wasn't decoded from a
+    /// `&str`, `byte_offset` keeps reporting positions.
+    ///
+    /// `EndText`, and `EndLine` at the last position, only hold at the
+    /// current end, so a match ending there may not be found again once more
+    /// is appended: rerun the search from where the last complete match
+    /// ended rather than trusting the ones that touched the end.
+    pub fn push_str(&mut self, s: &str) {
+        if let Some(len) = self.offsets.pop() {
+            self.offsets.extend(s.char_indices().map(|(at, _)| len + at));
+            self.offsets.push(len + s.len());
         }
+        self.seq.extend(s.chars());
+    }
+
+    /// Return true if the given empty width instruction matches at the
+    /// input position given, lines ending with `line_terminator`, or with
+    /// `\r\n`, `\r` or `\n` if `crlf` is true. Past either end of the
+    /// input, the character is absent, which is neither a line terminator
+    /// nor a word character.
+    pub fn is_empty_match(
+        &self,
+        at: usize,
+        look: &Zero,
+        line_terminator: char,
+        crlf: bool,
+    ) -> bool {
+        is_empty_match_between(self.prev(at), self.next(at), look,
+                               line_terminator, crlf)
     }
 
     /// Scan the input for a matching prefix.
     pub fn prefix_at(&self, prefixes: &LiteralSearcher<char>, at: usize)
-        -> Option<char>
+        -> Option<usize>
     {
-        prefixes.find(&self[at..]).map(|(s, _)| self[at + s])
+        prefixes.find(&self[at..]).map(|(s, _)| at + s)
+    }
+}
+
+/// Collects `words`, leaving out those seen before.
+fn dedup(words: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    words.filter(|word| seen.insert(word.clone())).collect()
+}
+
+/// The intervals of the Unicode property `name`, written as in `\p{name}`.
+fn property(name: &str) -> Vec<Interval<char>> {
+    try_property(name).unwrap()
+}
+
+/// Like `property`, but `None` if there is no property `name`.
+pub(crate) fn try_property(name: &str) -> Option<Vec<Interval<char>>> {
+    let pattern = format!(r"\p{{{}}}", name);
+    match regex_syntax::Parser::new().parse(&pattern).ok()?.into_kind() {
+        HirKind::Class(Class::Unicode(class)) => Some(class.iter()
+            .map(|range| Interval(range.start(), range.end()))
+            .collect()),
+        _ => None,
     }
 }
 
@@ -91,28 +605,55 @@ impl Context<char> {
 /// Returns true iff the character is a word character.
 ///
 /// If the character is absent, then false is returned.
-pub const fn is_word_char(c: char) -> bool {
+pub const fn is_word_char(c: Option<char>) -> bool {
     // is_word_character can panic if the Unicode data for \w isn't
     // available. However, our compiler ensures that if a Unicode word
     // boundary is used, then the data must also be available. If it isn't,
     // then the compiler returns an error.
-    from_u32(c).map_or(false, regex_syntax::is_word_character)
+    c.map_or(false, regex_syntax::is_word_character)
 }
 
 #[unconst]
 /// Returns true iff the byte is a word byte.
 ///
 /// If the byte is absent, then false is returned.
-pub const fn is_word_byte(c: char) -> bool {
-    match from_u32(c) {
+pub const fn is_word_byte(c: Option<char>) -> bool {
+    match c {
         Some(c) if c <= '\u{7F}' => regex_syntax::is_word_byte(c as u8),
         None | Some(_) => false,
     }
 }
 
-#[unconst]
-/// Returns true iff the character is absent.
-#[inline]
-pub const fn is_none(c: char) -> bool {
-    c == u32::MAX
+/// Returns true if `look` matches between `c1` and `c2`, see
+/// `Context::is_empty_match`.
+pub(crate) fn is_empty_match_between(
+    c1: Option<char>,
+    c2: Option<char>,
+    look: &Zero,
+    line_terminator: char,
+    crlf: bool,
+) -> bool {
+    match look {
+        Zero::Any => true,
+        // Between the `\r` and the `\n` of a `\r\n` is inside the
+        // terminator, so neither a start nor an end of line.
+        Zero::StartLine if crlf => match c1 {
+            None | Some('\n') => true,
+            Some('\r') => c2 != Some('\n'),
+            _ => false,
+        },
+        Zero::EndLine if crlf => match c2 {
+            None | Some('\r') => true,
+            Some('\n') => c1 != Some('\r'),
+            _ => false,
+        },
+        Zero::StartLine => c1.map_or(true, |c| c == line_terminator),
+        Zero::EndLine => c2.map_or(true, |c| c == line_terminator),
+        Zero::StartText => c1.is_none(),
+        Zero::EndText => c2.is_none(),
+        Zero::WordBoundary => is_word_char(c1) != is_word_char(c2),
+        Zero::NotWordBoundary => is_word_char(c1) == is_word_char(c2),
+        Zero::WordBoundaryAscii => is_word_byte(c1) != is_word_byte(c2),
+        Zero::NotWordBoundaryAscii => is_word_byte(c1) == is_word_byte(c2),
+    }
 }
@@ -0,0 +1,120 @@
+//! A visitor over the nodes of a `Repr`, so that an analysis only writes
+//! the cases it cares about and leaves the traversal to `Repr::visit`.
+
+use unconst::unconst;
+
+use crate::interval::Interval;
+use crate::repr::{Integral, Repr, Zero};
+use crate::seq::Seq;
+
+/// Callbacks for `Repr::visit`. `enter` is called on each node before its
+/// operands, left operand first, and `leave` after them, so that overriding
+/// one or the other gives a pre-order or a post-order walk.
+///
+/// By default, `enter` calls the `on_*` method of the variant of the node,
+/// and every method does nothing.
+pub trait Visitor<I: Integral> {
+    fn enter(&mut self, repr: &Repr<I>) {
+        match repr {
+            Repr::Zero(zero) => self.on_zero(zero),
+            Repr::One(seq) => self.on_one(seq),
+            Repr::Interval(interval) => self.on_interval(interval),
+            Repr::Mul(lhs, rhs) => self.on_mul(lhs, rhs),
+            Repr::Or(lhs, rhs) => self.on_or(lhs, rhs),
+            Repr::Div(lhs, rhs) => self.on_div(lhs, rhs),
+            Repr::Exp(repr) => self.on_exp(repr),
+            Repr::Not(repr) => self.on_not(repr),
+            Repr::Add(lhs, rhs) => self.on_add(lhs, rhs),
+            Repr::And(lhs, rhs) => self.on_and(lhs, rhs),
+            Repr::Cap(repr, name) => self.on_cap(repr, name),
+            Repr::Lazy(repr) => self.on_lazy(repr),
+        }
+    }
+
+    fn leave(&mut self, _repr: &Repr<I>) {}
+
+    fn on_zero(&mut self, _zero: &Zero) {}
+    fn on_one(&mut self, _seq: &Seq<I>) {}
+    fn on_interval(&mut self, _interval: &Interval<I>) {}
+    fn on_mul(&mut self, _lhs: &Repr<I>, _rhs: &Repr<I>) {}
+    fn on_or(&mut self, _lhs: &Repr<I>, _rhs: &Repr<I>) {}
+    fn on_div(&mut self, _lhs: &Repr<I>, _rhs: &Repr<I>) {}
+    fn on_exp(&mut self, _repr: &Repr<I>) {}
+    fn on_not(&mut self, _repr: &Repr<I>) {}
+    fn on_add(&mut self, _lhs: &Repr<I>, _rhs: &Repr<I>) {}
+    fn on_and(&mut self, _lhs: &Repr<I>, _rhs: &Repr<I>) {}
+    fn on_cap(&mut self, _repr: &Repr<I>, _name: &str) {}
+    fn on_lazy(&mut self, _repr: &Repr<I>) {}
+}
+
+#[unconst]
+impl<I: ~const Integral> Repr<I> {
+    /// Walks this repr depth first, calling `visitor` on every node, see
+    /// `Visitor`. Like `depth`, it doesn't recurse, so any repr can be
+    /// visited.
+    pub fn visit<V: Visitor<I>>(&self, visitor: &mut V) {
+        enum Step<'a, I: Integral> {
+            Enter(&'a Repr<I>),
+            Leave(&'a Repr<I>),
+        }
+        let mut stack = vec![Step::Enter(self)];
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(repr) => {
+                    visitor.enter(repr);
+                    stack.push(Step::Leave(repr));
+                    stack.extend(
+                        repr.operands().into_iter().rev().map(Step::Enter)
+                    );
+                }
+                Step::Leave(repr) => visitor.leave(repr),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Intervals(usize);
+
+    impl Visitor<char> for Intervals {
+        fn on_interval(&mut self, _interval: &Interval<char>) {
+            self.0 += 1;
+        }
+    }
+
+    /// Records the nodes entered and left, as rendered.
+    #[derive(Default)]
+    struct Order(Vec<String>);
+
+    impl Visitor<char> for Order {
+        fn enter(&mut self, repr: &Repr<char>) {
+            self.0.push(format!("+{}", repr));
+        }
+
+        fn leave(&mut self, repr: &Repr<char>) {
+            self.0.push(format!("-{}", repr));
+        }
+    }
+
+    #[test]
+    fn intervals() {
+        let repr = Repr::dot().mul(Repr::one('a').or(Repr::from('0'..='9')))
+            .cap("x")
+            .exp();
+        let mut intervals = Intervals::default();
+        repr.visit(&mut intervals);
+        assert_eq!(intervals.0, 3);
+    }
+
+    #[test]
+    fn order() {
+        let repr = Repr::one('a').mul(Repr::one('b').exp());
+        let mut order = Order::default();
+        repr.visit(&mut order);
+        assert_eq!(order.0, ["+ab*", "+a", "-a", "+b*", "+b", "-b", "-b*", "-ab*"]);
+    }
+}
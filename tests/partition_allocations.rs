@@ -0,0 +1,43 @@
+//! Lives in its own test binary because it replaces the global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use repr::{Context, Interval, Partition, Repr};
+
+/// Counts the allocations of each thread, so that tests running alongside
+/// don't add to them.
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[test]
+fn partition_allocations() {
+    let repr = Repr::Interval(Interval('0', '9')).plus();
+    let allocations = |n: usize| {
+        let context = Context::from("ab12 ".repeat(n).as_str());
+        let before = ALLOCATIONS.with(|n| n.get());
+        assert_eq!(Partition::new(&context, repr.clone()).count(), n);
+        ALLOCATIONS.with(|n| n.get()) - before
+    };
+    // Compiling and warming the caches up costs the same either way, while
+    // the 990 extra searches only borrow the context.
+    let (few, many) = (allocations(10), allocations(1000));
+    assert!(many.saturating_sub(few) < 100, "{} {}", few, many);
+}